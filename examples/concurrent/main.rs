@@ -0,0 +1,24 @@
+use discuit_rs::client::*;
+use futures::future::join_all;
+
+#[tokio::main]
+async fn main() {
+    let base_url = "https://discuit.net";
+    let client = DiscuitClient::new(base_url);
+    client.initialize().await.unwrap();
+
+    let communities = ["general", "gaming", "programming"];
+    let responses = join_all(
+        communities
+            .iter()
+            .map(|community| client.get_posts(Some("new"), Some(community))),
+    )
+    .await;
+
+    for (community, response) in communities.iter().zip(responses) {
+        match response {
+            Ok(feed) => println!("{}: {} posts", community, feed.posts.len()),
+            Err(e) => println!("{}: {}", community, e),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! This module contains `Aggregator`, which merges the post feeds of several
+//! Discuit instances into one.
+//!
+//! Every instance is queried concurrently with its own `DiscuitClient`, and
+//! the pages are merged by a common sort. Every post is wrapped in an `Origin`
+//! naming the instance it came from, so its links resolve against the right
+//! instance.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::aggregator::Aggregator;
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::structs::api_types::Sort;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let aggregator = Aggregator::new()
+//!     .instance(DiscuitClient::new("https://discuit.net"))
+//!     .instance(DiscuitClient::new("https://discuit.example.org"));
+//!
+//! let mut feed = aggregator.feed(Sort::New, None);
+//! while let Some(page) = feed.next_page().await {
+//!     for post in &page.posts {
+//!         println!("{} ({})", post.title, post.url());
+//!     }
+//!     for (instance, error) in &page.errors {
+//!         eprintln!("{} failed: {}", instance, error);
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::client::DiscuitClient;
+use crate::error::Error;
+use crate::structs::api_types::*;
+use futures_util::future::join_all;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::ops::Deref;
+use tracing::warn;
+
+/// The number of posts in an aggregated page when none is configured.
+const DEFAULT_PAGE_SIZE: usize = 25;
+
+/// `Origin` tags a post or comment with the instance it came from.
+///
+/// It derefs to the wrapped item, so its fields can be used directly.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Origin<T> {
+    /// The base URL of the instance, e.g. `https://discuit.net`.
+    pub instance: String,
+    /// The post or comment.
+    pub item: T,
+}
+
+impl<T> Origin<T> {
+    /// Tag an item with the instance at the given base URL.
+    pub fn new(instance: &str, item: T) -> Self {
+        Self {
+            instance: instance.trim_end_matches('/').to_string(),
+            item,
+        }
+    }
+
+    /// Returns the wrapped item.
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+}
+
+impl<T> Deref for Origin<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+impl Origin<Post> {
+    /// Returns the URL of the post on its instance.
+    pub fn url(&self) -> String {
+        format!(
+            "{}/{}/post/{}",
+            self.instance, self.item.community_name, self.item.public_id
+        )
+    }
+}
+
+impl Origin<Comment> {
+    /// Returns the URL of the comment on its instance.
+    pub fn url(&self) -> String {
+        format!(
+            "{}/{}/post/{}/{}",
+            self.instance, self.item.community_name, self.item.post_public_id, self.item.id
+        )
+    }
+}
+
+/// `Aggregator` holds the clients of the instances whose feeds are merged.
+#[derive(Clone, Debug, Default)]
+pub struct Aggregator {
+    clients: Vec<DiscuitClient>,
+}
+
+impl Aggregator {
+    /// Create a new `Aggregator` without instances.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an instance, queried with the given client.
+    pub fn instance(mut self, client: DiscuitClient) -> Self {
+        self.clients.push(client);
+        self
+    }
+
+    /// Returns the base URLs of the instances in the order they were added.
+    pub fn instances(&self) -> impl Iterator<Item = &str> {
+        self.clients.iter().map(|client| client.base_url.as_str())
+    }
+
+    /// Returns the client of the instance a post or comment came from.
+    pub fn client_for<T>(&self, origin: &Origin<T>) -> Option<&DiscuitClient> {
+        self.clients
+            .iter()
+            .find(|client| client.base_url == origin.instance)
+    }
+
+    /// Returns the merged feed of all instances with the given sort, of the
+    /// community with the given name on every instance or of the whole sites.
+    pub fn feed(&self, sort: Sort, community: Option<&str>) -> AggregatedFeed {
+        AggregatedFeed {
+            sort,
+            community: community.map(str::to_string),
+            page_size: DEFAULT_PAGE_SIZE,
+            sources: self
+                .clients
+                .iter()
+                .map(|client| Source {
+                    client: client.clone(),
+                    buffer: VecDeque::new(),
+                    next: None,
+                    done: false,
+                })
+                .collect(),
+        }
+    }
+
+    /// Fetch the comments of a post from the instance it came from.
+    pub async fn post_comments(&self, post: &Origin<Post>) -> Result<Vec<Origin<Comment>>, Error> {
+        let Some(client) = self.client_for(post) else {
+            return Ok(Vec::new());
+        };
+
+        let mut comments = Vec::new();
+        let mut next = None;
+        loop {
+            let page = client
+                .get_post_comments(&post.public_id, next.as_deref())
+                .await?;
+            comments.extend(
+                page.comments
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|comment| Origin::new(&post.instance, comment)),
+            );
+            next = page.next;
+            if next.is_none() {
+                return Ok(comments);
+            }
+        }
+    }
+}
+
+/// `AggregatedPage` is a page of an `AggregatedFeed`.
+#[derive(Debug, Default)]
+pub struct AggregatedPage {
+    /// The posts of the page, merged by the sort of the feed.
+    pub posts: Vec<Origin<Post>>,
+    /// The instances that failed while fetching the page, with their errors.
+    /// A failed instance is left out of the rest of the feed.
+    pub errors: Vec<(String, Error)>,
+}
+
+/// The state of a single instance in an `AggregatedFeed`.
+#[derive(Debug)]
+struct Source {
+    client: DiscuitClient,
+    /// Posts fetched but not yet returned.
+    buffer: VecDeque<Post>,
+    /// The cursor of the next page.
+    next: Option<String>,
+    /// Whether all pages were fetched, or the instance failed.
+    done: bool,
+}
+
+impl Source {
+    /// Returns true if the buffer is empty but more posts can be fetched.
+    fn needs_page(&self) -> bool {
+        self.buffer.is_empty() && !self.done
+    }
+}
+
+/// `AggregatedFeed` pages through the merged feed of several instances.
+///
+/// The posts of every instance are buffered, and a page ends early when an
+/// instance runs out of buffered posts, so the merge order holds across pages.
+#[derive(Debug)]
+pub struct AggregatedFeed {
+    sort: Sort,
+    community: Option<String>,
+    page_size: usize,
+    sources: Vec<Source>,
+}
+
+impl AggregatedFeed {
+    /// Set the maximum number of posts in a page. Defaults to 25.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Fetch the next page of the merged feed.
+    /// Returns `None` once every instance is exhausted.
+    pub async fn next_page(&mut self) -> Option<AggregatedPage> {
+        let mut page = AggregatedPage::default();
+        self.refill(&mut page.errors).await;
+
+        while page.posts.len() < self.page_size {
+            let Some(index) = self.best_source() else {
+                break;
+            };
+            let source = &mut self.sources[index];
+            let post = source.buffer.pop_front()?;
+            page.posts.push(Origin::new(&source.client.base_url, post));
+            // The next post of this instance isn't known yet, so nothing more
+            // can be merged without fetching it first.
+            if source.needs_page() {
+                break;
+            }
+        }
+
+        if page.posts.is_empty() && page.errors.is_empty() {
+            None
+        } else {
+            Some(page)
+        }
+    }
+
+    /// Fetches the next page of every instance without buffered posts, concurrently.
+    /// For internal use only.
+    async fn refill(&mut self, errors: &mut Vec<(String, Error)>) {
+        let sort = self.sort.as_str();
+        let community = self.community.as_deref();
+        let requests = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter(|(_, source)| source.needs_page())
+            .map(|(index, source)| async move {
+                let response = source
+                    .client
+                    .get_posts_page(Some(sort), community, source.next.as_deref())
+                    .await;
+                (index, response)
+            });
+        let responses = join_all(requests).await;
+
+        for (index, response) in responses {
+            let source = &mut self.sources[index];
+            match response {
+                Ok(response) => {
+                    source.buffer.extend(response.posts);
+                    source.next = response.next;
+                    source.done = source.next.is_none();
+                }
+                Err(e) => {
+                    let instance = source.client.base_url.clone();
+                    warn!(%instance, error = %e, "Failed to fetch posts");
+                    source.done = true;
+                    errors.push((instance, e));
+                }
+            }
+        }
+    }
+
+    /// Returns the index of the instance whose next post comes first.
+    /// For internal use only.
+    fn best_source(&self) -> Option<usize> {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter_map(|(index, source)| source.buffer.front().map(|post| (index, post)))
+            // `min_by` keeps the first of equal posts, so ties go to the
+            // instance added first.
+            .min_by(|(_, a), (_, b)| compare(self.sort, a, b))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Orders two posts by a sort, the post that comes first being the lesser.
+/// For internal use only.
+fn compare(sort: Sort, a: &Post, b: &Post) -> Ordering {
+    let score = |post: &Post| post.upvotes - post.downvotes;
+    match sort {
+        Sort::Hot => b.hotness.cmp(&a.hotness),
+        Sort::New => b.created_at.cmp(&a.created_at),
+        Sort::Activity => b.last_activity_at.cmp(&a.last_activity_at),
+        Sort::Day | Sort::Week | Sort::Month | Sort::Year => score(b).cmp(&score(a)),
+    }
+    .then_with(|| b.created_at.cmp(&a.created_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::json;
+
+    /// Starts an instance serving two pages of posts created at the given hours.
+    async fn instance(name: &'static str, hours: [[u32; 2]; 2]) -> MockServer {
+        MockServer::start(move |request| {
+            let (hours, next) = if request.path.contains("next=2") {
+                (hours[1], None)
+            } else {
+                (hours[0], Some("2"))
+            };
+            let posts: Vec<_> = hours
+                .iter()
+                .map(|hour| {
+                    let created_at = format!("2024-05-01T{:02}:00:00Z", hour);
+                    let id = format!("{}{}", name, hour);
+                    fixtures::post(&id, "general", name, &created_at)
+                })
+                .collect();
+            MockResponse::json(200, &json!({"posts": posts, "next": next}).to_string())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_merges_by_sort() {
+        let a = instance("a", [[23, 20], [10, 5]]).await;
+        let b = instance("b", [[22, 21], [4, 3]]).await;
+        let aggregator = Aggregator::new()
+            .instance(DiscuitClient::new(&a.url))
+            .instance(DiscuitClient::new(&b.url));
+
+        let mut feed = aggregator.feed(Sort::New, Some("general"));
+        let mut ids = Vec::new();
+        while let Some(page) = feed.next_page().await {
+            assert!(page.errors.is_empty());
+            for post in page.posts {
+                let instance = if post.instance == a.url { "a" } else { "b" };
+                assert!(post.public_id.as_str().starts_with(instance));
+                ids.push(post.public_id.to_string());
+            }
+        }
+
+        assert_eq!(ids, ["a23", "b22", "b21", "a20", "a10", "a5", "b4", "b3"]);
+        assert!(a.requests()[0].path.contains("sort=new"));
+        assert!(a.requests()[0].path.contains("community=general"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_instance_is_left_out() {
+        let a = instance("a", [[23, 20], [10, 5]]).await;
+        let broken = MockServer::start(|_| MockResponse::json(500, "{}")).await;
+        let aggregator = Aggregator::new()
+            .instance(DiscuitClient::new(&a.url))
+            .instance(DiscuitClient::new(&broken.url));
+
+        let mut feed = aggregator.feed(Sort::Hot, None).page_size(3);
+        let page = feed.next_page().await.unwrap();
+        assert_eq!(page.errors.len(), 1);
+        assert_eq!(page.errors[0].0, broken.url);
+        assert_eq!(page.posts.len(), 2);
+        assert_eq!(page.posts[0].url(), format!("{}/general/post/a23", a.url));
+    }
+}
@@ -0,0 +1,549 @@
+//! This module contains an archiver that mirrors whole communities to local storage.
+//!
+//! The archive of a community is a directory laid out as follows:
+//!
+//! ```text
+//! <dir>/
+//!   state.json              the progress of the archiver
+//!   posts/<publicId>.jsonl  the post on the first line, then one comment per line
+//!   media/<imageId>.<fmt>   the images of the posts
+//! ```
+//!
+//! Archiving is resumable: the state is saved after every post, so an
+//! interrupted run continues where it stopped. Posts whose `last_activity_at`
+//! didn't change since they were archived are not fetched again.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::archive::Archiver;
+//! use discuit_rs::client::*;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = DiscuitClient::new("https://discuit.net");
+//!     let stats = Archiver::new(client, "general", "archive/general")
+//!         .run()
+//!         .await
+//!         .unwrap();
+//!     println!("{:?}", stats);
+//! }
+//! ```
+
+use crate::client::DiscuitClient;
+use crate::error::Error;
+#[cfg(feature = "index")]
+use crate::index::{IndexError, LocalIndex};
+use crate::structs::api_types::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// `ArchiveError` represents everything that can go wrong while archiving.
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// A request to the Discuit API failed.
+    Client(Error),
+    /// Reading or writing the archive failed.
+    Io(std::io::Error),
+    /// A name sent by the server, like a post or image ID, can't be used as a
+    /// file name.
+    InvalidName(String),
+    /// Adding to the index failed.
+    #[cfg(feature = "index")]
+    Index(IndexError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Client(e) => write!(f, "{}", e),
+            ArchiveError::Io(e) => write!(f, "Archive I/O error: {}", e),
+            ArchiveError::InvalidName(name) => {
+                write!(f, "Invalid file name in archive: {:?}", name)
+            }
+            #[cfg(feature = "index")]
+            ArchiveError::Index(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::Client(e) => Some(e),
+            ArchiveError::Io(e) => Some(e),
+            ArchiveError::InvalidName(_) => None,
+            #[cfg(feature = "index")]
+            ArchiveError::Index(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for ArchiveError {
+    fn from(e: Error) -> Self {
+        ArchiveError::Client(e)
+    }
+}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(e: std::io::Error) -> Self {
+        ArchiveError::Io(e)
+    }
+}
+
+#[cfg(feature = "index")]
+impl From<IndexError> for ArchiveError {
+    fn from(e: IndexError) -> Self {
+        ArchiveError::Index(e)
+    }
+}
+
+impl From<serde_json::Error> for ArchiveError {
+    fn from(e: serde_json::Error) -> Self {
+        ArchiveError::Io(e.into())
+    }
+}
+
+/// `ArchiveState` is the progress of an archiver, saved in `state.json`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArchiveState {
+    /// The cursor of the next page of posts, if a run was interrupted.
+    pub next: Option<String>,
+    /// The `last_activity_at` of every archived post, keyed by public ID.
+    pub posts: HashMap<PublicPostId, DateTime<Utc>>,
+}
+
+/// `ArchiveStats` summarizes a run of the archiver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    /// The number of posts walked.
+    pub posts_seen: usize,
+    /// The number of posts that were new or changed and were archived.
+    pub posts_archived: usize,
+    /// The number of comments archived.
+    pub comments_archived: usize,
+    /// The number of images downloaded.
+    pub images_downloaded: usize,
+    /// The number of images that couldn't be downloaded, e.g. because they
+    /// were deleted or have an invalid name. Their posts are archived anyway.
+    pub images_failed: usize,
+}
+
+/// `ArchivedPost` is a post read back from an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedPost {
+    /// The post.
+    pub post: Post,
+    /// All comments of the post.
+    pub comments: Vec<Comment>,
+}
+
+/// A line of a post file.
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "kind", content = "data", rename_all = "lowercase")]
+enum Record {
+    Post(Box<Post>),
+    Comment(Box<Comment>),
+}
+
+/// Archiver mirrors a community to a directory.
+#[derive(Clone, Debug)]
+pub struct Archiver {
+    client: DiscuitClient,
+    community: String,
+    dir: PathBuf,
+    download_media: bool,
+    #[cfg(feature = "index")]
+    index: Option<LocalIndex>,
+}
+
+impl Archiver {
+    /// Create a new Archiver mirroring the given community to the given directory.
+    pub fn new(client: DiscuitClient, community: &str, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            community: community.to_string(),
+            dir: dir.into(),
+            download_media: true,
+            #[cfg(feature = "index")]
+            index: None,
+        }
+    }
+
+    /// Set whether the images of posts are downloaded. Defaults to `true`.
+    pub fn download_media(mut self, download_media: bool) -> Self {
+        self.download_media = download_media;
+        self
+    }
+
+    /// Add every archived post and its comments to the given index as well.
+    /// The index is committed after every page of posts.
+    /// Requires the `index` feature.
+    #[cfg(feature = "index")]
+    pub fn index(mut self, index: LocalIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Returns the directory of the archive.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Walk all posts of the community, archiving the new and changed ones.
+    pub async fn run(&self) -> Result<ArchiveStats, ArchiveError> {
+        std::fs::create_dir_all(self.dir.join("posts"))?;
+        std::fs::create_dir_all(self.dir.join("media"))?;
+        let mut state = self.load_state()?;
+        let mut stats = ArchiveStats::default();
+        if state.next.is_some() {
+            info!(community = %self.community, "Resuming archive ...");
+        } else {
+            info!(community = %self.community, "Archiving ...");
+        }
+
+        loop {
+            let page = self
+                .client
+                .get_posts_page(Some("new"), Some(&self.community), state.next.as_deref())
+                .await?;
+
+            for post in page.posts {
+                stats.posts_seen += 1;
+                let unchanged = state.posts.get(&post.public_id) == Some(&post.last_activity_at)
+                    && self.post_path(&post.public_id)?.exists();
+                if unchanged {
+                    continue;
+                }
+
+                let public_id = post.public_id.clone();
+                let last_activity_at = post.last_activity_at;
+                self.archive_post(post, &mut stats).await?;
+                state.posts.insert(public_id, last_activity_at);
+                self.save_state(&state)?;
+            }
+
+            state.next = page.next;
+            self.save_state(&state)?;
+            #[cfg(feature = "index")]
+            if let Some(index) = &self.index {
+                index.commit()?;
+            }
+            if state.next.is_none() {
+                break;
+            }
+        }
+
+        info!(
+            posts_seen = stats.posts_seen,
+            posts_archived = stats.posts_archived,
+            "Archive complete."
+        );
+        Ok(stats)
+    }
+
+    /// Read an archived post back.
+    pub fn read_post(&self, public_id: &PublicPostId) -> Result<ArchivedPost, ArchiveError> {
+        let file = std::fs::File::open(self.post_path(public_id)?)?;
+        let mut post = None;
+        let mut comments = Vec::new();
+        for line in BufReader::new(file).lines() {
+            match serde_json::from_str(&line?)? {
+                Record::Post(p) => post = Some(*p),
+                Record::Comment(c) => comments.push(*c),
+            }
+        }
+
+        let post = post.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "post file without a post")
+        })?;
+        Ok(ArchivedPost { post, comments })
+    }
+
+    /// Add every post of the archive and its comments to the given index, e.g.
+    /// to build an index of an archive made without one.
+    /// Returns the number of posts added.
+    /// Requires the `index` feature.
+    #[cfg(feature = "index")]
+    pub fn index_archive(&self, index: &LocalIndex) -> Result<usize, ArchiveError> {
+        let mut posts = 0;
+        for entry in std::fs::read_dir(self.dir.join("posts"))? {
+            let path = entry?.path();
+            let Some(public_id) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".jsonl"))
+            else {
+                continue;
+            };
+            let archived = self.read_post(&PublicPostId::from(public_id))?;
+            index.add_post(&archived.post)?;
+            index.add_comments(&archived.comments)?;
+            posts += 1;
+        }
+
+        index.commit()?;
+        info!(posts, "Archive indexed.");
+        Ok(posts)
+    }
+
+    /// Fetches the comments and image of a post and writes it to the archive.
+    async fn archive_post(&self, post: Post, stats: &mut ArchiveStats) -> Result<(), ArchiveError> {
+        debug!(public_id = %post.public_id, "Archiving post");
+        let mut comments = Vec::new();
+        let mut next = None;
+        loop {
+            let page = self
+                .client
+                .get_post_comments(&post.public_id, next.as_deref())
+                .await?;
+            comments.extend(page.comments.unwrap_or_default());
+            next = page.next;
+            if next.is_none() {
+                break;
+            }
+        }
+
+        if let Some(image) = post.image.as_ref().filter(|_| self.download_media) {
+            match self.archive_image(image).await {
+                Ok(true) => stats.images_downloaded += 1,
+                Ok(false) => {}
+                Err(e @ (ArchiveError::Client(_) | ArchiveError::InvalidName(_))) => {
+                    warn!(error = %e, public_id = %post.public_id, "Failed to archive image");
+                    stats.images_failed += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        #[cfg(feature = "index")]
+        if let Some(index) = &self.index {
+            index.add_post(&post)?;
+            index.add_comments(&comments)?;
+        }
+
+        let mut lines = Vec::new();
+        let public_id = post.public_id.clone();
+        stats.comments_archived += comments.len();
+        for record in std::iter::once(Record::Post(Box::new(post)))
+            .chain(comments.into_iter().map(|c| Record::Comment(Box::new(c))))
+        {
+            serde_json::to_writer(&mut lines, &record)?;
+            lines.write_all(b"\n")?;
+        }
+        write_atomically(&self.post_path(&public_id)?, &lines)?;
+        stats.posts_archived += 1;
+        Ok(())
+    }
+
+    /// Downloads an image to the archive unless it was downloaded before.
+    /// Returns true if it was downloaded.
+    async fn archive_image(&self, image: &Image) -> Result<bool, ArchiveError> {
+        let path = self.dir.join("media").join(format!(
+            "{}.{}",
+            file_name(image.id.as_str())?,
+            file_name(&image.format)?
+        ));
+        if path.exists() {
+            return Ok(false);
+        }
+        let bytes = self.client.download_image(image).await?;
+        write_atomically(&path, &bytes)?;
+        Ok(true)
+    }
+
+    fn post_path(&self, public_id: &PublicPostId) -> Result<PathBuf, ArchiveError> {
+        Ok(self
+            .dir
+            .join("posts")
+            .join(format!("{}.jsonl", file_name(public_id.as_str())?)))
+    }
+
+    fn load_state(&self) -> Result<ArchiveState, ArchiveError> {
+        match std::fs::read_to_string(self.dir.join("state.json")) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ArchiveState::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_state(&self, state: &ArchiveState) -> Result<(), ArchiveError> {
+        write_atomically(&self.dir.join("state.json"), &serde_json::to_vec(state)?)?;
+        Ok(())
+    }
+}
+
+/// Checks that a name sent by the server only contains `[A-Za-z0-9_-]`, so it
+/// can't escape the archive directory when used as a file name.
+fn file_name(name: &str) -> Result<&str, ArchiveError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(name)
+    } else {
+        Err(ArchiveError::InvalidName(name.to_string()))
+    }
+}
+
+/// Writes a file by writing a temporary file and renaming it, so an
+/// interrupted write never leaves a partial file behind.
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_archive_and_update() {
+        let changed = Arc::new(AtomicBool::new(false));
+        let server_changed = changed.clone();
+        let server = MockServer::start(move |request| {
+            let changed = server_changed.load(Ordering::SeqCst);
+            let path = request.path.as_str();
+            if path.starts_with("/images/") {
+                return MockResponse::json(200, "image bytes");
+            }
+            if let Some(rest) = path.strip_prefix("/api/posts/") {
+                let public_id = rest.split('/').next().unwrap();
+                let comments = if path.contains("next=2") {
+                    vec![fixtures::comment(
+                        "c2",
+                        public_id,
+                        "bob",
+                        "2",
+                        "2024-06-01T10:00:00Z",
+                    )]
+                } else {
+                    vec![fixtures::comment(
+                        "c1",
+                        public_id,
+                        "bob",
+                        "1",
+                        "2024-06-01T10:00:00Z",
+                    )]
+                };
+                let next = (!path.contains("next=2")).then_some("2");
+                return MockResponse::json(
+                    200,
+                    &json!({ "comments": comments, "next": next }).to_string(),
+                );
+            }
+
+            let mut a = fixtures::post("a", "general", "alice", "2024-06-01T11:00:00Z");
+            a["image"] = fixtures::image("img");
+            let mut b = fixtures::post("b", "general", "alice", "2024-06-01T10:00:00Z");
+            if changed {
+                b["lastActivityAt"] = json!("2024-06-02T10:00:00Z");
+            }
+            let body = if path.contains("next=") {
+                json!({ "posts": [b], "next": null })
+            } else {
+                json!({ "posts": [a], "next": "page2" })
+            };
+            MockResponse::json(200, &body.to_string())
+        })
+        .await;
+
+        let dir = std::env::temp_dir().join(format!("discuit-archive-{}", std::process::id()));
+        let archiver = Archiver::new(DiscuitClient::new(&server.url), "general", &dir);
+
+        let stats = archiver.run().await.unwrap();
+        assert_eq!(
+            stats,
+            ArchiveStats {
+                posts_seen: 2,
+                posts_archived: 2,
+                comments_archived: 4,
+                images_downloaded: 1,
+                images_failed: 0,
+            }
+        );
+        let archived = archiver.read_post(&PublicPostId::from("a")).unwrap();
+        assert_eq!(archived.post.public_id, "a");
+        assert_eq!(archived.comments.len(), 2);
+        assert_eq!(
+            std::fs::read(dir.join("media/img.jpeg")).unwrap(),
+            b"image bytes"
+        );
+
+        // Only the post whose activity changed is fetched again.
+        changed.store(true, Ordering::SeqCst);
+        let requests = server.requests().len();
+        let stats = archiver.run().await.unwrap();
+        assert_eq!(stats.posts_archived, 1);
+        let fetched: Vec<_> = server.requests()[requests..]
+            .iter()
+            .filter(|request| request.path.contains("/comments"))
+            .map(|request| request.path.clone())
+            .collect();
+        assert!(fetched.iter().all(|path| path.starts_with("/api/posts/b/")));
+
+        #[cfg(feature = "index")]
+        {
+            use crate::index::{IndexQuery, IndexedKind, LocalIndex};
+
+            let index = LocalIndex::open(dir.join("index")).unwrap();
+            assert_eq!(archiver.index_archive(&index).unwrap(), 2);
+            let posts = index.search(&IndexQuery::new("").kind(IndexedKind::Post));
+            assert_eq!(posts.unwrap().len(), 2);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_images_are_skipped() {
+        let server = MockServer::start(move |request| {
+            if request.path.contains("/comments") {
+                return MockResponse::json(200, r#"{"comments":[],"next":null}"#);
+            }
+            if request.path.starts_with("/images/") {
+                return MockResponse::json(404, "404 page not found");
+            }
+            let mut a = fixtures::post("a", "general", "alice", "2024-06-01T11:00:00Z");
+            a["image"] = fixtures::image("../../escaped");
+            let mut b = fixtures::post("b", "general", "alice", "2024-06-01T10:00:00Z");
+            b["image"] = fixtures::image("deleted");
+            MockResponse::json(200, &json!({ "posts": [a, b], "next": null }).to_string())
+        })
+        .await;
+
+        let dir = std::env::temp_dir().join(format!("discuit-archive-{}", rand::random::<u64>()));
+        let archiver = Archiver::new(DiscuitClient::new(&server.url), "general", &dir);
+        let stats = archiver.run().await.unwrap();
+        assert_eq!(stats.posts_archived, 2);
+        assert_eq!(stats.images_failed, 2);
+        assert!(!dir.join("escaped.jpeg").exists());
+        assert!(archiver.read_post(&PublicPostId::from("a")).is_ok());
+        assert!(matches!(
+            archiver.read_post(&PublicPostId::from("../state")),
+            Err(ArchiveError::InvalidName(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_state_defaults() {
+        let state: ArchiveState = serde_json::from_str("{}").unwrap();
+        assert_eq!(state, ArchiveState::default());
+    }
+}
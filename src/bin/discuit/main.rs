@@ -0,0 +1,493 @@
+//! `discuit` is a command-line tool for scripting Discuit, built on `DiscuitClient`.
+//!
+//! Log in once with `discuit login`; the session is stored and reused by the
+//! following invocations until `discuit logout` is run. Every command prints a
+//! table by default, or JSON with `--output json`.
+
+mod output;
+mod session;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use discuit_rs::client::*;
+use discuit_rs::structs::api_requests::CreatePostRequest;
+use discuit_rs::structs::api_responses::UserResponse;
+use discuit_rs::structs::api_types::{
+    Comment, CommentId, Post, PublicPostId, Sort, User, UserGroup,
+};
+use output::{details, Format, Table};
+use session::StoredSession;
+use std::io::{BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+
+/// The instance used when none is given and no session is stored.
+const DEFAULT_INSTANCE: &str = "https://discuit.net";
+
+#[derive(Parser)]
+#[command(
+    name = "discuit",
+    version,
+    about = "A command-line tool for scripting Discuit"
+)]
+struct Cli {
+    /// The base URL of the Discuit instance.
+    /// Defaults to the instance of the stored session, or https://discuit.net.
+    #[arg(long, global = true, env = "DISCUIT_INSTANCE")]
+    instance: Option<String>,
+
+    /// The output format.
+    #[arg(long, short, global = true, value_enum, default_value_t = Format::Table)]
+    output: Format,
+
+    /// Validate and print writes instead of sending them.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Log in and store the session for the following commands.
+    Login {
+        /// The username to log in with.
+        #[arg(long, short, env = "DISCUIT_USERNAME")]
+        username: String,
+        /// The password to log in with. Read from standard input if not given.
+        #[arg(long, env = "DISCUIT_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Log out and remove the stored session.
+    Logout,
+    /// Show the authenticated user.
+    Whoami,
+    /// List the posts of a community, or site-wide posts.
+    Posts {
+        /// The name of the community. Lists site-wide posts if not given.
+        #[arg(long, short)]
+        community: Option<String>,
+        /// How to sort the posts: hot, activity, new, day, week, month or year.
+        #[arg(long, short, default_value = "hot")]
+        sort: Sort,
+    },
+    /// Show, create or delete posts.
+    #[command(subcommand)]
+    Post(PostCommand),
+    /// Comment on a post.
+    Comment {
+        /// The public ID of the post.
+        post: PublicPostId,
+        /// The body of the comment.
+        #[arg(long, short)]
+        body: String,
+        /// The ID of the comment to reply to.
+        #[arg(long)]
+        parent: Option<CommentId>,
+    },
+    /// Vote on a post or a comment.
+    #[command(subcommand)]
+    Vote(VoteCommand),
+    /// List notifications.
+    Notifications {
+        /// Mark all notifications as seen afterwards.
+        #[arg(long)]
+        mark_seen: bool,
+    },
+    /// Moderate posts and comments.
+    #[command(subcommand)]
+    Mod(ModCommand),
+}
+
+#[derive(Subcommand)]
+enum PostCommand {
+    /// Show a post.
+    Show {
+        /// The public ID of the post.
+        public_id: PublicPostId,
+    },
+    /// Create a text post, or a link post if `--url` is given.
+    Create {
+        /// The name of the community to post in.
+        #[arg(long, short)]
+        community: String,
+        /// The title of the post.
+        #[arg(long, short)]
+        title: String,
+        /// The body of a text post.
+        #[arg(long, short, conflicts_with = "url", required_unless_present = "url")]
+        body: Option<String>,
+        /// The URL of a link post.
+        #[arg(long, short)]
+        url: Option<String>,
+    },
+    /// Delete one of your posts.
+    Delete {
+        /// The public ID of the post.
+        public_id: PublicPostId,
+    },
+}
+
+#[derive(Subcommand)]
+enum VoteCommand {
+    /// Vote on a post.
+    Post {
+        /// The public ID of the post.
+        public_id: PublicPostId,
+        direction: Direction,
+    },
+    /// Vote on a comment.
+    Comment {
+        /// The ID of the comment.
+        comment_id: CommentId,
+        direction: Direction,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Direction {
+    Up,
+    Down,
+}
+
+#[derive(Subcommand)]
+enum ModCommand {
+    /// Lock a post.
+    Lock { public_id: PublicPostId },
+    /// Unlock a post.
+    Unlock { public_id: PublicPostId },
+    /// Pin a post.
+    Pin {
+        public_id: PublicPostId,
+        /// Pin the post site-wide instead of in its community (admins only).
+        #[arg(long)]
+        site_wide: bool,
+    },
+    /// Unpin a post.
+    Unpin {
+        public_id: PublicPostId,
+        /// Unpin a site-wide pinned post (admins only).
+        #[arg(long)]
+        site_wide: bool,
+    },
+    /// Remove a post as a moderator.
+    RemovePost {
+        public_id: PublicPostId,
+        /// Also delete the body, link or image of the post.
+        #[arg(long)]
+        delete_content: bool,
+    },
+    /// Remove a comment as a moderator.
+    RemoveComment {
+        /// The public ID of the post.
+        post: PublicPostId,
+        /// The ID of the comment.
+        comment_id: CommentId,
+    },
+}
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> CliResult {
+    let stored = session::load();
+    let instance = cli
+        .instance
+        .clone()
+        .or_else(|| stored.as_ref().map(|s| s.instance.clone()))
+        .unwrap_or_else(|| DEFAULT_INSTANCE.to_string());
+    let client = DiscuitClient::builder(&instance)
+        .user_agent(concat!("discuit-cli/", env!("CARGO_PKG_VERSION")))
+        .dry_run(cli.dry_run)
+        .build()?;
+
+    // Reuse the stored session if it belongs to this instance, otherwise
+    // start an anonymous one. Logging in always starts a fresh session.
+    match stored {
+        _ if matches!(cli.command, Command::Login { .. }) => {}
+        Some(stored) if stored.instance == client.base_url => client.set_session(stored.session),
+        _ => {
+            client.initialize().await?;
+        }
+    }
+
+    match cli.command {
+        Command::Login { username, password } => login(&client, &username, password).await,
+        Command::Logout => {
+            client.logout().await?;
+            session::clear()?;
+            eprintln!("Logged out.");
+            Ok(())
+        }
+        Command::Whoami => whoami(&client, cli.output).await,
+        Command::Posts { community, sort } => {
+            let feed = client
+                .get_posts(Some(sort.as_str()), community.as_deref())
+                .await?;
+            output::print(cli.output, &feed.posts, || posts_table(&feed.posts));
+            Ok(())
+        }
+        Command::Post(command) => post(&client, cli.output, command).await,
+        Command::Comment { post, body, parent } => {
+            let comment = client.create_comment(&post, parent.as_ref(), &body).await?;
+            output::print(cli.output, &comment, || comment_details(&comment));
+            Ok(())
+        }
+        Command::Vote(command) => vote(&client, cli.output, command).await,
+        Command::Notifications { mark_seen } => notifications(&client, cli.output, mark_seen).await,
+        Command::Mod(command) => moderate(&client, cli.output, command).await,
+    }
+}
+
+async fn login(client: &DiscuitClient, username: &str, password: Option<String>) -> CliResult {
+    let password = match password {
+        Some(password) => password,
+        None => read_password()?,
+    };
+
+    client.initialize().await?;
+    match client.login(username, &password).await? {
+        UserResponse::User(user) => {
+            session::save(&StoredSession {
+                instance: client.base_url.clone(),
+                session: client.session(),
+            })?;
+            eprintln!("Logged in as {}.", user.username);
+            Ok(())
+        }
+        UserResponse::Error(e) => Err(format!("failed to log in: {}", e.message).into()),
+    }
+}
+
+fn read_password() -> Result<String, std::io::Error> {
+    // Piped passwords are read as the first line of stdin.
+    if std::io::stdin().is_terminal() {
+        return rpassword::prompt_password("Password: ");
+    }
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn whoami(client: &DiscuitClient, format: Format) -> CliResult {
+    let username = client
+        .user()
+        .ok_or("not logged in, run `discuit login` first")?
+        .username;
+    match client.get_user_by_username(&username).await? {
+        UserResponse::User(user) => {
+            output::print(format, &user, || user_details(&user));
+            Ok(())
+        }
+        UserResponse::Error(e) => Err(e.message.into()),
+    }
+}
+
+async fn post(client: &DiscuitClient, format: Format, command: PostCommand) -> CliResult {
+    let post = match command {
+        PostCommand::Show { public_id } => client.get_post(&public_id).await?,
+        PostCommand::Create {
+            community,
+            title,
+            body,
+            url,
+        } => {
+            let request = match url {
+                Some(url) => CreatePostRequest::link(&community, &title, &url),
+                None => CreatePostRequest::text(&community, &title, &body.unwrap_or_default()),
+            };
+            client.create_post(&request).await?
+        }
+        PostCommand::Delete { public_id } => {
+            client
+                .delete_post(&public_id, &UserGroup::Normal, true)
+                .await?
+        }
+    };
+
+    output::print(format, &post, || post_details(&post));
+    Ok(())
+}
+
+async fn vote(client: &DiscuitClient, format: Format, command: VoteCommand) -> CliResult {
+    match command {
+        VoteCommand::Post {
+            public_id,
+            direction,
+        } => {
+            // Votes are cast on the post ID, which differs from the public ID
+            // found in post URLs.
+            let post = client.get_post(&public_id).await?;
+            let post = client
+                .vote_post(&post, matches!(direction, Direction::Up))
+                .await?;
+            output::print(format, &post, || post_details(&post));
+        }
+        VoteCommand::Comment {
+            comment_id,
+            direction,
+        } => {
+            let comment = client
+                .vote_comment(&comment_id, matches!(direction, Direction::Up))
+                .await?;
+            output::print(format, &comment, || comment_details(&comment));
+        }
+    }
+    Ok(())
+}
+
+async fn notifications(client: &DiscuitClient, format: Format, mark_seen: bool) -> CliResult {
+    let response = client.get_notifications(None).await?;
+    let items = response.items.unwrap_or_default();
+    output::print(format, &items, || {
+        let mut table = Table::new(vec!["id", "type", "seen", "created", "post"]);
+        for notification in &items {
+            table.row(vec![
+                notification.id.to_string(),
+                notification.notification_type.clone(),
+                notification.seen.to_string(),
+                notification.created_at.format("%Y-%m-%d %H:%M").to_string(),
+                notification
+                    .post()
+                    .map(|post| post.title)
+                    .unwrap_or_default(),
+            ]);
+        }
+        table
+    });
+
+    if mark_seen {
+        client.mark_all_notifications_seen().await?;
+    }
+    Ok(())
+}
+
+async fn moderate(client: &DiscuitClient, format: Format, command: ModCommand) -> CliResult {
+    let post = match command {
+        ModCommand::Lock { public_id } => {
+            client
+                .set_post_locked(&public_id, &UserGroup::Mods, true)
+                .await?
+        }
+        ModCommand::Unlock { public_id } => {
+            client
+                .set_post_locked(&public_id, &UserGroup::Mods, false)
+                .await?
+        }
+        ModCommand::Pin {
+            public_id,
+            site_wide,
+        } => client.set_post_pinned(&public_id, true, site_wide).await?,
+        ModCommand::Unpin {
+            public_id,
+            site_wide,
+        } => client.set_post_pinned(&public_id, false, site_wide).await?,
+        ModCommand::RemovePost {
+            public_id,
+            delete_content,
+        } => {
+            client
+                .delete_post(&public_id, &UserGroup::Mods, delete_content)
+                .await?
+        }
+        ModCommand::RemoveComment { post, comment_id } => {
+            let comment = client
+                .delete_comment(&post, &comment_id, &UserGroup::Mods)
+                .await?;
+            output::print(format, &comment, || comment_details(&comment));
+            return Ok(());
+        }
+    };
+
+    output::print(format, &post, || post_details(&post));
+    Ok(())
+}
+
+fn posts_table(posts: &[Post]) -> Table {
+    let mut table = Table::new(vec![
+        "id",
+        "community",
+        "user",
+        "score",
+        "comments",
+        "title",
+    ]);
+    for post in posts {
+        table.row(vec![
+            post.public_id.to_string(),
+            post.community_name.clone(),
+            post.username.clone(),
+            (post.upvotes - post.downvotes).to_string(),
+            post.no_comments.to_string(),
+            post.title.clone(),
+        ]);
+    }
+    table
+}
+
+fn post_details(post: &Post) -> Table {
+    details(vec![
+        ("id", post.public_id.to_string()),
+        ("title", post.title.clone()),
+        ("community", post.community_name.clone()),
+        ("user", post.username.clone()),
+        ("created", post.created_at.to_string()),
+        ("score", (post.upvotes - post.downvotes).to_string()),
+        ("comments", post.no_comments.to_string()),
+        ("locked", post.locked.to_string()),
+        ("pinned", post.is_pinned.to_string()),
+        ("deleted", post.deleted.to_string()),
+        (
+            "content",
+            post.link
+                .as_ref()
+                .map(|link| link.url.clone())
+                .or_else(|| post.body.clone())
+                .unwrap_or_default(),
+        ),
+    ])
+}
+
+fn comment_details(comment: &Comment) -> Table {
+    details(vec![
+        ("id", comment.id.to_string()),
+        ("post", comment.post_public_id.to_string()),
+        ("user", comment.username.clone()),
+        (
+            "parent",
+            comment
+                .parent_id
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        ),
+        ("score", (comment.upvotes - comment.downvotes).to_string()),
+        ("deleted", comment.deleted.to_string()),
+        ("body", comment.body.clone()),
+    ])
+}
+
+fn user_details(user: &User) -> Table {
+    details(vec![
+        ("id", user.id.to_string()),
+        ("username", user.username.clone()),
+        ("points", user.points.to_string()),
+        ("posts", user.no_posts.to_string()),
+        ("comments", user.no_comments.to_string()),
+        ("admin", user.is_admin.to_string()),
+        ("created", user.created_at.to_string()),
+        ("notifications", user.notifications_new_count.to_string()),
+    ])
+}
@@ -0,0 +1,109 @@
+//! Output formatting for the `discuit` command-line tool.
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// The format command output is printed in.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Format {
+    /// Human readable, aligned columns.
+    #[default]
+    Table,
+    /// Pretty-printed JSON, for scripting.
+    Json,
+}
+
+/// The maximum width of a table cell; longer values are truncated.
+const MAX_CELL_WIDTH: usize = 60;
+
+/// `Table` is a simple table printed with aligned columns.
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) {
+        self.rows
+            .push(row.into_iter().map(|cell| truncate(&cell)).collect());
+    }
+
+    fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_uppercase()).collect();
+        let mut out = String::new();
+        for row in std::iter::once(&headers).chain(&self.rows) {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            out.push_str(line.join("  ").trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Truncates a cell to `MAX_CELL_WIDTH` characters and flattens newlines.
+fn truncate(cell: &str) -> String {
+    let cell = cell.replace(['\n', '\r'], " ");
+    if cell.chars().count() <= MAX_CELL_WIDTH {
+        return cell;
+    }
+    let truncated: String = cell.chars().take(MAX_CELL_WIDTH - 3).collect();
+    format!("{}...", truncated)
+}
+
+/// Prints `value` as JSON, or the table built by `table` otherwise.
+pub fn print<T: Serialize>(format: Format, value: &T, table: impl FnOnce() -> Table) {
+    match format {
+        Format::Json => match serde_json::to_string_pretty(value) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("error: failed to encode output: {}", e),
+        },
+        Format::Table => print!("{}", table().render()),
+    }
+}
+
+/// Prints a single key/value table, e.g. for a user or a post.
+pub fn details(fields: Vec<(&'static str, String)>) -> Table {
+    let mut table = Table::new(vec!["field", "value"]);
+    for (key, value) in fields {
+        table.row(vec![key.to_string(), value]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_render() {
+        let mut table = Table::new(vec!["id", "title"]);
+        table.row(vec!["1".to_string(), "first\npost".to_string()]);
+        table.row(vec!["1234".to_string(), "x".repeat(100)]);
+
+        let rendered = table.render();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[0], "ID    TITLE");
+        assert_eq!(lines[1], "1     first post");
+        assert!(lines[2].ends_with("..."));
+        assert_eq!(lines[2].len(), "1234  ".len() + MAX_CELL_WIDTH);
+    }
+}
@@ -0,0 +1,77 @@
+//! Session persistence for the `discuit` command-line tool.
+//!
+//! The session of the last login is stored as JSON so that following
+//! invocations are authenticated as well. The file contains the session ID,
+//! so it is only readable by the current user.
+
+use discuit_rs::structs::internal_types::Session;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// `StoredSession` is the content of the session file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StoredSession {
+    /// The base URL of the instance the session belongs to.
+    pub instance: String,
+    /// The session itself.
+    pub session: Session,
+}
+
+/// Returns the path of the session file: `$DISCUIT_SESSION_FILE` if set,
+/// otherwise `discuit/session.json` in `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("DISCUIT_SESSION_FILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("discuit").join("session.json"))
+}
+
+/// Loads the stored session, if there is one.
+pub fn load() -> Option<StoredSession> {
+    let text = fs::read_to_string(path()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Stores the session, replacing any previous one.
+pub fn save(stored: &StoredSession) -> io::Result<()> {
+    let path = path().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "no configuration directory found")
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    // The mode above only applies when the file is created, so an existing
+    // file is restricted before the session ID is written to it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    let json = serde_json::to_string_pretty(stored).map_err(io::Error::other)?;
+    io::Write::write_all(&mut file, json.as_bytes())
+}
+
+/// Removes the stored session.
+pub fn clear() -> io::Result<()> {
+    match path().map(fs::remove_file) {
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,391 @@
+//! This module contains a blocking (synchronous) version of `DiscuitClient`.
+//!
+//! It is only available with the `blocking` feature enabled:
+//!
+//! ```toml
+//! [dependencies.discuit-rs]
+//! git = "https://github.com/ttaylor-st/discuit-rs.git"
+//! features = ["blocking"]
+//! ```
+//!
+//! The blocking client wraps the async client and drives it on its own
+//! single-threaded runtime, so no runtime has to be set up by the caller.
+//! Like `reqwest::blocking`, it must not be used from within an async runtime.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::blocking::DiscuitClient;
+//!
+//! let client = DiscuitClient::new("https://discuit.net");
+//! let response = client.initialize();
+//! println!("{:?}", response);
+//! ```
+
+use crate::capabilities::InstanceCapabilities;
+use crate::client;
+use crate::error::Error;
+use crate::search::{self, SearchMode, Searchable};
+use crate::structs::api_requests::*;
+use crate::structs::api_responses::*;
+use crate::structs::api_types::*;
+use crate::structs::internal_types::*;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::{Builder, Runtime};
+
+/// DiscuitClient is a blocking client for the Discuit API.
+/// Every method mirrors the method of the same name on the async
+/// `client::DiscuitClient`.
+///
+/// Cloning the client is cheap; clones share the same runtime and async client.
+#[derive(Clone, Debug)]
+pub struct DiscuitClient {
+    inner: client::DiscuitClient,
+    runtime: Arc<Runtime>,
+}
+
+impl DiscuitClient {
+    /// Create a new blocking DiscuitClient with the given base URL and the default configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client or the runtime could not be created.
+    pub fn new(base_url: &str) -> Self {
+        Self::from_async(client::DiscuitClient::new(base_url)).expect("Failed to build the runtime")
+    }
+
+    /// Create a new blocking DiscuitClient wrapping the given async client,
+    /// e.g. one created with `client::DiscuitClient::builder`.
+    /// Returns an error if the runtime could not be created.
+    pub fn from_async(inner: client::DiscuitClient) -> std::io::Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Returns the wrapped async client.
+    pub fn inner(&self) -> &client::DiscuitClient {
+        &self.inner
+    }
+
+    /// Runs a future to completion on the client's runtime.
+    /// For internal use only.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Returns a copy of the current session.
+    pub fn session(&self) -> Session {
+        self.inner.session()
+    }
+
+    /// Replace the current session, e.g. to restore a session saved earlier.
+    pub fn set_session(&self, session: Session) {
+        self.inner.set_session(session)
+    }
+
+    /// Returns the CSRF token of the current session.
+    pub fn csrf_token(&self) -> String {
+        self.inner.csrf_token()
+    }
+
+    /// Returns the session ID of the current session.
+    pub fn session_id(&self) -> String {
+        self.inner.session_id()
+    }
+
+    /// Returns the User object of the authenticated user.
+    /// If the client is not authenticated, this is None.
+    pub fn user(&self) -> Option<User> {
+        self.inner.user()
+    }
+
+    /// Resets the client to its initial state.
+    pub fn reset(&self) {
+        self.inner.reset()
+    }
+
+    /// Initialize the client by fetching a CSRF token and a session ID.
+    /// Returns `InitialResponse`.
+    pub fn initialize(&self) -> Result<InitialResponse, Error> {
+        self.block_on(self.inner.initialize())
+    }
+
+    /// Probe the instance for the features it supports.
+    pub fn probe(&self) -> Result<InstanceCapabilities, Error> {
+        self.block_on(self.inner.probe())
+    }
+
+    /// Log in to the Discuit instance with the given username and password.
+    pub fn login(&self, username: &str, password: &str) -> Result<UserResponse, Error> {
+        self.block_on(self.inner.login(username, password))
+    }
+
+    /// Log out of the Discuit instance.
+    /// Returns either `()` or a `reqwest::Error`.
+    pub fn logout(&self) -> Result<(), reqwest::Error> {
+        self.block_on(self.inner.logout())
+    }
+
+    /// Fetch the current user from the Discuit instance.
+    /// Returns either `User` or an `Error`.
+    pub fn get_user(&self) -> Result<User, Error> {
+        self.block_on(self.inner.get_user())
+    }
+
+    /// Fetch another user from the Discuit instance.
+    /// Returns UserResponse
+    pub fn get_user_by_username(&self, username: &str) -> Result<UserResponse, Error> {
+        self.block_on(self.inner.get_user_by_username(username))
+    }
+
+    /// Fetch a user's feed.
+    pub fn get_feed(&self, username: &str) -> Result<FeedResponse, Error> {
+        self.block_on(self.inner.get_feed(username))
+    }
+
+    /// Fetch community or sitewide posts with optional query parameters.
+    pub fn get_posts(
+        &self,
+        sort: Option<&str>,
+        community: Option<&str>,
+    ) -> Result<PostFeedResponse, Box<dyn std::error::Error>> {
+        self.block_on(self.inner.get_posts(sort, community))
+    }
+
+    /// Fetch a page of community or sitewide posts.
+    pub fn get_posts_page(
+        &self,
+        sort: Option<&str>,
+        community: Option<&str>,
+        next: Option<&str>,
+    ) -> Result<PostFeedResponse, Error> {
+        self.block_on(self.inner.get_posts_page(sort, community, next))
+    }
+
+    /// Fetch a single post by its public ID, including its community.
+    pub fn get_post(&self, public_id: &PublicPostId) -> Result<Post, Error> {
+        self.block_on(self.inner.get_post(public_id))
+    }
+
+    /// Create a post.
+    pub fn create_post(&self, post: &CreatePostRequest) -> Result<Post, Error> {
+        self.block_on(self.inner.create_post(post))
+    }
+
+    /// Delete a post.
+    pub fn delete_post(
+        &self,
+        public_id: &PublicPostId,
+        delete_as: &UserGroup,
+        delete_content: bool,
+    ) -> Result<Post, Error> {
+        self.block_on(self.inner.delete_post(public_id, delete_as, delete_content))
+    }
+
+    /// Lock or unlock a post.
+    /// `lock_as` is the capacity in which the post is locked, e.g. as a moderator.
+    pub fn set_post_locked(
+        &self,
+        public_id: &PublicPostId,
+        lock_as: &UserGroup,
+        locked: bool,
+    ) -> Result<Post, Error> {
+        self.block_on(self.inner.set_post_locked(public_id, lock_as, locked))
+    }
+
+    /// Pin or unpin a post in its community, or site-wide if `site_wide` is true.
+    pub fn set_post_pinned(
+        &self,
+        public_id: &PublicPostId,
+        pinned: bool,
+        site_wide: bool,
+    ) -> Result<Post, Error> {
+        self.block_on(self.inner.set_post_pinned(public_id, pinned, site_wide))
+    }
+
+    /// Vote on a post, e.g. one returned by `get_post`.
+    pub fn vote_post(&self, post: &Post, up: bool) -> Result<Post, Error> {
+        self.block_on(self.inner.vote_post(post, up))
+    }
+
+    /// Add a comment to a post.
+    pub fn create_comment(
+        &self,
+        post_public_id: &PublicPostId,
+        parent_id: Option<&CommentId>,
+        body: &str,
+    ) -> Result<Comment, Error> {
+        self.block_on(self.inner.create_comment(post_public_id, parent_id, body))
+    }
+
+    /// Fetch a page of the comments of a post.
+    pub fn get_post_comments(
+        &self,
+        post_public_id: &PublicPostId,
+        next: Option<&str>,
+    ) -> Result<CommentsResponse, Error> {
+        self.block_on(self.inner.get_post_comments(post_public_id, next))
+    }
+
+    /// Fetch a comment by its ID.
+    pub fn get_comment(&self, comment_id: &CommentId) -> Result<Comment, Error> {
+        self.block_on(self.inner.get_comment(comment_id))
+    }
+
+    /// Delete a comment.
+    pub fn delete_comment(
+        &self,
+        post_public_id: &PublicPostId,
+        comment_id: &CommentId,
+        delete_as: &UserGroup,
+    ) -> Result<Comment, Error> {
+        self.block_on(
+            self.inner
+                .delete_comment(post_public_id, comment_id, delete_as),
+        )
+    }
+
+    /// Vote on a comment.
+    pub fn vote_comment(&self, comment_id: &CommentId, up: bool) -> Result<Comment, Error> {
+        self.block_on(self.inner.vote_comment(comment_id, up))
+    }
+
+    /// Fetch a community by its name.
+    pub fn get_community_by_name(&self, name: &str) -> Result<Community, Error> {
+        self.block_on(self.inner.get_community_by_name(name))
+    }
+
+    /// Fetch the communities of the instance.
+    pub fn get_communities(&self) -> Result<Vec<Community>, Error> {
+        self.block_on(self.inner.get_communities())
+    }
+
+    /// Search for posts whose title or body contains the words of the query.
+    pub fn search_posts(&self, query: &str) -> Search<Post> {
+        self.search(self.inner.search_posts(query))
+    }
+
+    /// Search for communities whose name starts with the given prefix.
+    pub fn search_communities(&self, prefix: &str) -> Search<Community> {
+        self.search(self.inner.search_communities(prefix))
+    }
+
+    /// Search for users matching the query.
+    pub fn search_users(&self, query: &str) -> Search<User> {
+        self.search(self.inner.search_users(query))
+    }
+
+    /// Wraps an async search to run on the client's runtime.
+    /// For internal use only.
+    fn search<T: Searchable>(&self, inner: search::Search<T>) -> Search<T> {
+        Search {
+            inner,
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Fetch a page of the reports of a community. Only available to moderators.
+    pub fn get_reports(
+        &self,
+        community_id: &CommunityId,
+        page: u32,
+    ) -> Result<ReportsResponse, Error> {
+        self.block_on(self.inner.get_reports(community_id, page))
+    }
+
+    /// Download the contents of an image, e.g. the image of a post.
+    pub fn download_image(&self, image: &Image) -> Result<Vec<u8>, Error> {
+        self.block_on(self.inner.download_image(image))
+    }
+
+    /// Fetch the notifications of the authenticated user.
+    pub fn get_notifications(&self, next: Option<&str>) -> Result<NotificationsResponse, Error> {
+        self.block_on(self.inner.get_notifications(next))
+    }
+
+    /// Mark all notifications of the authenticated user as seen.
+    pub fn mark_all_notifications_seen(&self) -> Result<(), Error> {
+        self.block_on(self.inner.mark_all_notifications_seen())
+    }
+}
+
+/// Search is a blocking version of `search::Search`, iterating over the pages
+/// of results.
+#[derive(Debug)]
+pub struct Search<T> {
+    inner: search::Search<T>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T: Searchable> Search<T> {
+    /// Only search the community with the given name. Only applies to posts.
+    pub fn community(self, name: &str) -> Self {
+        Self {
+            inner: self.inner.community(name),
+            ..self
+        }
+    }
+
+    /// Set whether to search server-side, client-side or both.
+    pub fn mode(self, mode: SearchMode) -> Self {
+        Self {
+            inner: self.inner.mode(mode),
+            ..self
+        }
+    }
+
+    /// Set the maximum number of pages of posts a client-side search crawls.
+    pub fn max_pages(self, max_pages: usize) -> Self {
+        Self {
+            inner: self.inner.max_pages(max_pages),
+            ..self
+        }
+    }
+
+    /// Returns true if the results come from a client-side search.
+    pub fn is_fallback(&self) -> bool {
+        self.inner.is_fallback()
+    }
+
+    /// Fetch the next page of results.
+    pub fn next_page(&mut self) -> Option<Result<Vec<T>, Error>> {
+        self.runtime.block_on(self.inner.next_page())
+    }
+}
+
+impl<T: Searchable> Iterator for Search<T> {
+    type Item = Result<Vec<T>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_page()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+
+    #[test]
+    fn test_blocking_request() {
+        // The mock server needs a runtime of its own, as the blocking client
+        // can't be used from within one.
+        let server_runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = server_runtime.block_on(MockServer::start(|_| {
+            MockResponse::json(200, r#"{"posts": [], "next": null}"#)
+        }));
+
+        let client = DiscuitClient::new(&server.url);
+        let posts = client.get_posts(Some("new"), Some("general")).unwrap();
+
+        assert!(posts.posts.is_empty());
+        let path = &server.requests()[0].path;
+        assert!(path.starts_with("/api/posts?"));
+        assert!(path.contains("sort=new") && path.contains("community=general"));
+    }
+}
@@ -0,0 +1,1019 @@
+//! This module contains a framework for writing bots on top of `DiscuitClient`.
+//!
+//! A `Bot` polls the instance for new posts and comments in the communities it
+//! watches, for new notifications and for new reports, and calls the handlers
+//! registered for each kind of event. Seen IDs are remembered so every event is
+//! handled once, and can be persisted in a checkpoint file so a restarted bot
+//! picks up where it left off.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::bot::Bot;
+//! use discuit_rs::client::*;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = DiscuitClient::new("https://discuit.net");
+//!     client.initialize().await.unwrap();
+//!     client.login("ourbot", "password").await.unwrap();
+//!
+//!     Bot::new(client)
+//!         .community("general")
+//!         .checkpoint("ourbot.checkpoint.json")
+//!         .on_new_post(|_client, post| async move {
+//!             println!("New post in {}: {}", post.community_name, post.title);
+//!             Ok(())
+//!         })
+//!         .on_mention(|client, comment| async move {
+//!             client
+//!                 .create_comment(&comment.post_public_id, Some(&comment.id), "Hello!")
+//!                 .await?;
+//!             Ok(())
+//!         })
+//!         .run()
+//!         .await;
+//! }
+//! ```
+
+use crate::client::DiscuitClient;
+use crate::error::Error;
+use crate::structs::api_types::*;
+use chrono::{DateTime, Utc};
+use commands::CommandRouter;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+pub mod commands;
+
+/// The result returned by event handlers. Errors are logged and don't stop the bot.
+pub type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+type HandlerFuture = Pin<Box<dyn Future<Output = HandlerResult> + Send>>;
+type Handler<T> = Box<dyn Fn(DiscuitClient, T) -> HandlerFuture + Send + Sync>;
+
+/// Boxes a handler so handlers of different types can be stored together.
+fn boxed<T, F, Fut>(handler: F) -> Handler<T>
+where
+    F: Fn(DiscuitClient, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HandlerResult> + Send + 'static,
+{
+    Box::new(move |client, value| Box::pin(handler(client, value)))
+}
+
+/// `BotConfig` configures how a `Bot` polls the instance.
+#[derive(Clone, Debug)]
+pub struct BotConfig {
+    /// The time between two polls. Defaults to 30 seconds.
+    pub poll_interval: Duration,
+    /// The maximum time to wait between polls after errors. Defaults to 10 minutes.
+    pub max_backoff: Duration,
+    /// If true, events that happened before the bot first started are skipped
+    /// instead of being handled. Defaults to `true`.
+    pub skip_backlog: bool,
+    /// The maximum number of pages of posts or comments fetched per community
+    /// and poll. Defaults to 5.
+    pub max_pages: u32,
+    /// The maximum number of IDs remembered per kind of event. Defaults to 10000.
+    pub max_seen: usize,
+    /// The number of times a command whose handler or reply failed is run
+    /// before the bot gives up on it. Defaults to 3.
+    pub max_command_attempts: u32,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(600),
+            skip_backlog: true,
+            max_pages: 5,
+            max_seen: 10_000,
+            max_command_attempts: 3,
+        }
+    }
+}
+
+/// `SeenSet` remembers the most recently seen IDs, forgetting the oldest ones
+/// once it holds more than a given number of IDs.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(from = "VecDeque<T>", into = "VecDeque<T>")]
+#[serde(bound(
+    serialize = "T: Clone + Serialize",
+    deserialize = "T: Eq + Hash + Clone + Deserialize<'de>"
+))]
+pub struct SeenSet<T: Eq + Hash> {
+    order: VecDeque<T>,
+    set: HashSet<T>,
+}
+
+impl<T: Eq + Hash + Clone> SeenSet<T> {
+    /// Returns true if the ID was seen.
+    pub fn contains(&self, id: &T) -> bool {
+        self.set.contains(id)
+    }
+
+    /// Remembers an ID, forgetting the oldest ones if more than `max` IDs are held.
+    /// Returns true if the ID was not seen before.
+    pub fn insert(&mut self, id: T, max: usize) -> bool {
+        if !self.set.insert(id.clone()) {
+            return false;
+        }
+        self.order.push_back(id);
+        while self.order.len() > max {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Returns the number of IDs held.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Returns true if no IDs are held.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl<T: Eq + Hash + Clone> From<VecDeque<T>> for SeenSet<T> {
+    fn from(order: VecDeque<T>) -> Self {
+        let set = order.iter().cloned().collect();
+        Self { order, set }
+    }
+}
+
+impl<T: Eq + Hash> From<SeenSet<T>> for VecDeque<T> {
+    fn from(seen: SeenSet<T>) -> Self {
+        seen.order
+    }
+}
+
+/// `Checkpoint` is the state of a `Bot` that is persisted between runs.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Checkpoint {
+    /// The time at which the bot first polled. Null before the first poll.
+    pub started_at: Option<DateTime<Utc>>,
+    /// The IDs of seen posts.
+    pub seen_posts: SeenSet<PostId>,
+    /// The IDs of seen comments.
+    pub seen_comments: SeenSet<CommentId>,
+    /// The IDs and edit times of seen edited comments.
+    pub seen_edits: SeenSet<(CommentId, DateTime<Utc>)>,
+    /// The IDs of comments the bot replied to with the result of a command.
+    pub replied_comments: SeenSet<CommentId>,
+    /// The IDs of comments whose command failed, with the number of failed
+    /// attempts. The command is run again when the post is next polled.
+    pub pending_commands: HashMap<CommentId, u32>,
+    /// The IDs of seen notifications.
+    pub seen_notifications: SeenSet<NotificationId>,
+    /// The IDs of seen reports.
+    pub seen_reports: SeenSet<ReportId>,
+    /// The last activity time of recently active posts, keyed by community
+    /// name and post ID. Used to only fetch the comments of posts that changed.
+    pub post_activity: HashMap<String, HashMap<PostId, DateTime<Utc>>>,
+}
+
+impl Checkpoint {
+    /// Loads a checkpoint from a JSON file.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(std::io::Error::other)
+    }
+
+    /// Saves the checkpoint to a JSON file. The file is replaced atomically,
+    /// so an interrupted save never leaves a corrupt checkpoint behind.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+/// Bot polls a Discuit instance and calls the registered handlers for new events.
+pub struct Bot {
+    client: DiscuitClient,
+    config: BotConfig,
+    communities: Vec<String>,
+    checkpoint: Checkpoint,
+    checkpoint_path: Option<PathBuf>,
+    post_handlers: Vec<Handler<Post>>,
+    comment_handlers: Vec<Handler<Comment>>,
+    mention_handlers: Vec<Handler<Comment>>,
+    notification_handlers: Vec<Handler<Notification>>,
+    report_handlers: Vec<Handler<Report>>,
+    commands: Option<CommandRouter>,
+    /// The IDs of the watched communities, resolved when reports are first polled.
+    community_ids: HashMap<String, CommunityId>,
+    /// Communities whose reports can't be fetched, e.g. because the bot isn't a moderator.
+    reports_unavailable: HashSet<String>,
+}
+
+impl Bot {
+    /// Create a new Bot using the given client, which should already be logged in.
+    pub fn new(client: DiscuitClient) -> Self {
+        Self {
+            client,
+            config: BotConfig::default(),
+            communities: Vec::new(),
+            checkpoint: Checkpoint::default(),
+            checkpoint_path: None,
+            post_handlers: Vec::new(),
+            comment_handlers: Vec::new(),
+            mention_handlers: Vec::new(),
+            notification_handlers: Vec::new(),
+            report_handlers: Vec::new(),
+            commands: None,
+            community_ids: HashMap::new(),
+            reports_unavailable: HashSet::new(),
+        }
+    }
+
+    /// Set the polling configuration.
+    pub fn config(mut self, config: BotConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Watch a community for new posts, comments, mentions and reports.
+    pub fn community(mut self, name: &str) -> Self {
+        self.communities.push(name.to_string());
+        self
+    }
+
+    /// Persist the checkpoint to the given file after every poll.
+    /// If the file exists, the bot resumes from the checkpoint stored in it.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        match Checkpoint::load(&path) {
+            Ok(checkpoint) => self.checkpoint = checkpoint,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(error = %e, path = %path.display(), "Failed to load checkpoint"),
+        }
+        self.checkpoint_path = Some(path);
+        self
+    }
+
+    /// Returns the current checkpoint.
+    pub fn current_checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    /// Call `handler` for every new post in the watched communities.
+    pub fn on_new_post<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(DiscuitClient, Post) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.post_handlers.push(boxed(handler));
+        self
+    }
+
+    /// Call `handler` for every new comment in the watched communities.
+    pub fn on_new_comment<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(DiscuitClient, Comment) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.comment_handlers.push(boxed(handler));
+        self
+    }
+
+    /// Call `handler` for every new comment in the watched communities that
+    /// mentions the bot as `@username` or `u/username`.
+    pub fn on_mention<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(DiscuitClient, Comment) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.mention_handlers.push(boxed(handler));
+        self
+    }
+
+    /// Call `handler` for every new notification of the bot.
+    pub fn on_notification<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(DiscuitClient, Notification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.notification_handlers.push(boxed(handler));
+        self
+    }
+
+    /// Call `handler` for every new report in the watched communities.
+    /// The bot has to be a moderator of the communities.
+    pub fn on_report<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(DiscuitClient, Report) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.report_handlers.push(boxed(handler));
+        self
+    }
+
+    /// Reply to commands given to the bot in comments in the watched communities,
+    /// e.g. `u/ourbot remindme 2d`. Edited comments are parsed again, but the bot
+    /// never replies to the same comment twice. A command whose handler or reply
+    /// failed is run again on the next polls, up to `max_command_attempts` times.
+    pub fn commands(mut self, router: CommandRouter) -> Self {
+        self.commands = Some(router);
+        self
+    }
+
+    /// Poll forever, waiting `poll_interval` between polls. After failed polls,
+    /// the wait is doubled every time, up to `max_backoff`.
+    pub async fn run(mut self) {
+        let mut failures = 0;
+        loop {
+            let delay = match self.poll().await {
+                Ok(()) => {
+                    failures = 0;
+                    self.config.poll_interval
+                }
+                Err(e) => {
+                    failures += 1;
+                    let delay = backoff(&self.config, failures);
+                    warn!(error = %e, failures, delay_secs = delay.as_secs(), "Poll failed");
+                    delay
+                }
+            };
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Poll once, calling the handlers for all new events, and save the checkpoint.
+    pub async fn poll(&mut self) -> Result<(), Error> {
+        // Events from before the first poll are only recorded, not handled.
+        let record_only = self.checkpoint.started_at.is_none() && self.config.skip_backlog;
+        if self.checkpoint.started_at.is_none() {
+            self.checkpoint.started_at = Some(Utc::now());
+        }
+
+        let result = self.poll_all(record_only).await;
+        self.save_checkpoint();
+        result
+    }
+
+    async fn poll_all(&mut self, record_only: bool) -> Result<(), Error> {
+        for community in self.communities.clone() {
+            if !self.post_handlers.is_empty() {
+                self.poll_posts(&community, record_only).await?;
+            }
+            if !self.comment_handlers.is_empty()
+                || !self.mention_handlers.is_empty()
+                || self.commands.is_some()
+            {
+                self.poll_comments(&community, record_only).await?;
+            }
+            if !self.report_handlers.is_empty() {
+                self.poll_reports(&community, record_only).await?;
+            }
+        }
+        if !self.notification_handlers.is_empty() {
+            self.poll_notifications(record_only).await?;
+        }
+        Ok(())
+    }
+
+    fn save_checkpoint(&self) {
+        if let Some(path) = &self.checkpoint_path {
+            if let Err(e) = self.checkpoint.save(path) {
+                warn!(error = %e, path = %path.display(), "Failed to save checkpoint");
+            }
+        }
+    }
+
+    /// Returns true if the content was created by the bot itself.
+    fn is_own(&self, username: &str) -> bool {
+        self.client
+            .user()
+            .is_some_and(|user| user.username.eq_ignore_ascii_case(username))
+    }
+
+    /// Walks the newest posts of a community until a seen post is reached.
+    async fn poll_posts(&mut self, community: &str, record_only: bool) -> Result<(), Error> {
+        let mut new_posts = Vec::new();
+        let mut next = None;
+        for _ in 0..self.config.max_pages {
+            let page = self
+                .client
+                .get_posts_page(Some("new"), Some(community), next.as_deref())
+                .await?;
+
+            let mut reached_seen = false;
+            for post in page.posts {
+                if self.checkpoint.seen_posts.contains(&post.id) {
+                    reached_seen = true;
+                } else {
+                    new_posts.push(post);
+                }
+            }
+
+            next = page.next;
+            if reached_seen || record_only || next.is_none() {
+                break;
+            }
+        }
+
+        // Handle the oldest posts first.
+        for post in new_posts.into_iter().rev() {
+            self.checkpoint
+                .seen_posts
+                .insert(post.id.clone(), self.config.max_seen);
+            if record_only || self.is_own(&post.username) {
+                continue;
+            }
+            debug!(public_id = %post.public_id, "New post");
+            dispatch(&self.client, &self.post_handlers, &post).await;
+        }
+        Ok(())
+    }
+
+    /// Fetches the comments of recently active posts whose activity changed.
+    async fn poll_comments(&mut self, community: &str, record_only: bool) -> Result<(), Error> {
+        let page = self
+            .client
+            .get_posts_page(Some("activity"), Some(community), None)
+            .await?;
+
+        let mut activity = self
+            .checkpoint
+            .post_activity
+            .remove(community)
+            .unwrap_or_default();
+        // Only remember the activity of posts that are still recently active.
+        activity.retain(|id, _| page.posts.iter().any(|post| &post.id == id));
+
+        for post in page.posts {
+            let changed = activity.get(&post.id) != Some(&post.last_activity_at);
+            if changed && !record_only && post.no_comments > 0 {
+                match self.poll_post_comments(&post).await {
+                    Ok(true) => {}
+                    // Some comments weren't handled, fetch them again next poll.
+                    Ok(false) => continue,
+                    Err(e) => {
+                        self.checkpoint
+                            .post_activity
+                            .insert(community.to_string(), activity);
+                        return Err(e);
+                    }
+                }
+            }
+            // The activity is only recorded once the comments were handled,
+            // so they are fetched again if that failed.
+            activity.insert(post.id, post.last_activity_at);
+        }
+
+        self.checkpoint
+            .post_activity
+            .insert(community.to_string(), activity);
+        Ok(())
+    }
+
+    /// Handles the new and edited comments of a post. Returns false if a
+    /// command failed and should be run again, so the post is polled again.
+    async fn poll_post_comments(&mut self, post: &Post) -> Result<bool, Error> {
+        let comments = self.fetch_comments(&post.public_id).await?;
+        let username = self.client.user().map(|user| user.username);
+
+        // Replies of the bot found in the thread, in case the checkpoint was lost.
+        let replied: HashSet<&CommentId> = comments
+            .iter()
+            .filter(|comment| self.is_own(&comment.username))
+            .filter_map(|comment| comment.parent_id.as_ref())
+            .collect();
+
+        let mut handled_all = true;
+        for comment in &comments {
+            let new = self
+                .checkpoint
+                .seen_comments
+                .insert(comment.id.clone(), self.config.max_seen);
+            let edited = comment.edited_at.is_some_and(|edited_at| {
+                self.checkpoint
+                    .seen_edits
+                    .insert((comment.id.clone(), edited_at), self.config.max_seen)
+            });
+            let pending = self.checkpoint.pending_commands.contains_key(&comment.id);
+            if !new && !edited && !pending {
+                continue;
+            }
+            if self.is_before_start(comment.created_at) || self.is_own(&comment.username) {
+                continue;
+            }
+            let Some(username) = username.as_deref() else {
+                // Without a user, mentions and commands can't be recognized.
+                if new {
+                    dispatch(&self.client, &self.comment_handlers, comment).await;
+                }
+                continue;
+            };
+            let mentioned = mentions(&comment.body, username);
+
+            if new {
+                debug!(comment_id = %comment.id, "New comment");
+                dispatch(&self.client, &self.comment_handlers, comment).await;
+                if mentioned {
+                    debug!(comment_id = %comment.id, "New mention");
+                    dispatch(&self.client, &self.mention_handlers, comment).await;
+                }
+            }
+            if mentioned && !replied.contains(&comment.id) {
+                handled_all &= self.run_command(comment, username).await;
+            } else {
+                self.checkpoint.pending_commands.remove(&comment.id);
+            }
+        }
+        Ok(handled_all)
+    }
+
+    /// Runs the command given in a comment and replies to it, unless the bot
+    /// already replied to the comment. Returns false if the command or the
+    /// reply failed and will be retried.
+    async fn run_command(&mut self, comment: &Comment, username: &str) -> bool {
+        let Some(router) = &self.commands else {
+            return true;
+        };
+        if self.checkpoint.replied_comments.contains(&comment.id) {
+            self.checkpoint.pending_commands.remove(&comment.id);
+            return true;
+        }
+
+        let error = match router.reply(&self.client, comment, username).await {
+            Ok(Some(reply)) => {
+                debug!(comment_id = %comment.id, reply_id = %reply.id, "Replied to command");
+                self.checkpoint
+                    .replied_comments
+                    .insert(comment.id.clone(), self.config.max_seen);
+                self.checkpoint.pending_commands.remove(&comment.id);
+                return true;
+            }
+            Ok(None) => {
+                self.checkpoint.pending_commands.remove(&comment.id);
+                return true;
+            }
+            Err(e) => e,
+        };
+
+        let attempts = self
+            .checkpoint
+            .pending_commands
+            .entry(comment.id.clone())
+            .or_default();
+        *attempts += 1;
+        if *attempts >= self.config.max_command_attempts {
+            warn!(error = %error, comment_id = %comment.id, attempts = *attempts, "Command failed, giving up");
+            self.checkpoint.pending_commands.remove(&comment.id);
+            return true;
+        }
+        warn!(error = %error, comment_id = %comment.id, attempts = *attempts, "Command failed, will retry");
+        false
+    }
+
+    /// Fetches up to `max_pages` pages of the comments of a post.
+    async fn fetch_comments(&self, post_public_id: &PublicPostId) -> Result<Vec<Comment>, Error> {
+        let mut comments = Vec::new();
+        let mut next = None;
+        for _ in 0..self.config.max_pages {
+            let page = self
+                .client
+                .get_post_comments(post_public_id, next.as_deref())
+                .await?;
+            comments.extend(page.comments.unwrap_or_default());
+            next = page.next;
+            if next.is_none() {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
+    /// Returns true if the given time is before the bot first started and the
+    /// backlog should be skipped.
+    fn is_before_start(&self, time: DateTime<Utc>) -> bool {
+        match (self.config.skip_backlog, self.checkpoint.started_at) {
+            (true, Some(started_at)) => time < started_at,
+            _ => false,
+        }
+    }
+
+    async fn poll_notifications(&mut self, record_only: bool) -> Result<(), Error> {
+        let response = self.client.get_notifications(None).await?;
+
+        // Notifications are returned newest first; handle the oldest first.
+        for notification in response.items.unwrap_or_default().into_iter().rev() {
+            if !self
+                .checkpoint
+                .seen_notifications
+                .insert(notification.id, self.config.max_seen)
+                || record_only
+            {
+                continue;
+            }
+            debug!(id = %notification.id, "New notification");
+            dispatch(&self.client, &self.notification_handlers, &notification).await;
+        }
+        Ok(())
+    }
+
+    async fn poll_reports(&mut self, community: &str, record_only: bool) -> Result<(), Error> {
+        if self.reports_unavailable.contains(community) {
+            return Ok(());
+        }
+        let community_id = match self.community_ids.get(community) {
+            Some(id) => id.clone(),
+            None => {
+                let id = self.client.get_community_by_name(community).await?.id;
+                self.community_ids.insert(community.to_string(), id.clone());
+                id
+            }
+        };
+
+        let response = match self.client.get_reports(&community_id, 1).await {
+            Ok(response) => response,
+            Err(Error::Api(e)) if e.status == 401 || e.status == 403 => {
+                warn!(community, "Can't fetch reports, is the bot a moderator?");
+                self.reports_unavailable.insert(community.to_string());
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        for report in response.reports.unwrap_or_default().into_iter().rev() {
+            if !self
+                .checkpoint
+                .seen_reports
+                .insert(report.id, self.config.max_seen)
+                || record_only
+            {
+                continue;
+            }
+            info!(id = %report.id, community, "New report");
+            dispatch(&self.client, &self.report_handlers, &report).await;
+        }
+        Ok(())
+    }
+}
+
+/// Calls every handler with the given value, logging handler errors.
+async fn dispatch<T: Clone>(client: &DiscuitClient, handlers: &[Handler<T>], value: &T) {
+    for handler in handlers {
+        if let Err(e) = handler(client.clone(), value.clone()).await {
+            warn!(error = %e, "Handler failed");
+        }
+    }
+}
+
+/// Returns how long to wait after the given number of consecutive failed polls.
+fn backoff(config: &BotConfig, failures: u32) -> Duration {
+    config
+        .poll_interval
+        .saturating_mul(2u32.saturating_pow(failures.min(16)))
+        .min(config.max_backoff)
+}
+
+/// Returns true if `body` mentions `username` as `@username` or `u/username`.
+/// Usernames are matched case-insensitively.
+pub fn mentions(body: &str, username: &str) -> bool {
+    find_mention(body, username).is_some()
+}
+
+/// Returns the byte range of the first mention of `username` in `body`.
+fn find_mention(body: &str, username: &str) -> Option<Range<usize>> {
+    // ASCII lowercasing keeps byte offsets valid for the original body.
+    let body = body.to_ascii_lowercase();
+    let username = username.to_ascii_lowercase();
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    ["@", "u/"]
+        .iter()
+        .filter_map(|prefix| {
+            let needle = format!("{}{}", prefix, username);
+            body.match_indices(&needle)
+                .map(|(start, _)| start..start + needle.len())
+                .find(|range| {
+                    let before = body[..range.start].chars().next_back();
+                    let after = body[range.end..].chars().next();
+                    !before.is_some_and(|c| is_name_char(c) || c == '/')
+                        && !after.is_some_and(is_name_char)
+                })
+        })
+        .min_by_key(|range| range.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+    use crate::structs::internal_types::Session;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("hey u/OurBot remindme 2d", "ourbot"));
+        assert!(mentions("@ourbot, help", "ourbot"));
+        assert!(mentions("(u/ourbot)", "ourbot"));
+        assert!(!mentions("u/ourbot2 help", "ourbot"));
+        assert!(!mentions("email@ourbot.com", "ourbot_"));
+        assert!(!mentions("mail me at x@ourbotmail", "ourbot"));
+        assert!(!mentions("nu/ourbot", "ourbot"));
+    }
+
+    #[test]
+    fn test_seen_set() {
+        let mut seen = SeenSet::default();
+        assert!(seen.insert(1, 2));
+        assert!(!seen.insert(1, 2));
+        assert!(seen.insert(2, 2));
+        assert!(seen.insert(3, 2));
+
+        assert!(!seen.contains(&1));
+        assert!(seen.contains(&3));
+        assert_eq!(seen.len(), 2);
+
+        let json = serde_json::to_string(&seen).unwrap();
+        assert_eq!(json, "[2,3]");
+        assert_eq!(serde_json::from_str::<SeenSet<i32>>(&json).unwrap(), seen);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = BotConfig {
+            poll_interval: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+            ..BotConfig::default()
+        };
+
+        assert_eq!(backoff(&config, 1), Duration::from_secs(20));
+        assert_eq!(backoff(&config, 2), Duration::from_secs(40));
+        assert_eq!(backoff(&config, 3), Duration::from_secs(60));
+        assert_eq!(backoff(&config, 100), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_new_posts_are_handled_once() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let server_polls = polls.clone();
+        let server = MockServer::start(move |_| {
+            let mut posts = vec![fixtures::post(
+                "b",
+                "general",
+                "alice",
+                "2024-06-01T10:00:00Z",
+            )];
+            if server_polls.fetch_add(1, Ordering::SeqCst) > 0 {
+                posts.insert(
+                    0,
+                    fixtures::post("c", "general", "bob", "2024-06-01T11:00:00Z"),
+                );
+                posts.insert(
+                    0,
+                    fixtures::post("d", "general", "ourbot", "2024-06-01T12:00:00Z"),
+                );
+            }
+            MockResponse::json(200, &json!({ "posts": posts, "next": null }).to_string())
+        })
+        .await;
+
+        let client = DiscuitClient::new(&server.url);
+        client.set_session(Session {
+            user: Some(serde_json::from_value(fixtures::user("ourbot")).unwrap()),
+            ..Session::default()
+        });
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let recorded = handled.clone();
+        let mut bot = Bot::new(client)
+            .community("general")
+            .on_new_post(move |_, post| {
+                let recorded = recorded.clone();
+                async move {
+                    recorded.lock().unwrap().push(post.public_id);
+                    Ok(())
+                }
+            });
+
+        // The first poll only records the backlog.
+        bot.poll().await.unwrap();
+        assert!(handled.lock().unwrap().is_empty());
+
+        // Posts by the bot itself are skipped.
+        bot.poll().await.unwrap();
+        bot.poll().await.unwrap();
+        assert_eq!(*handled.lock().unwrap(), vec![PublicPostId::from("c")]);
+        assert_eq!(bot.current_checkpoint().seen_posts.len(), 3);
+        assert!(server.requests()[0].path.contains("sort=new"));
+    }
+
+    #[tokio::test]
+    async fn test_mentions_are_handled() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let server_polls = polls.clone();
+        let server = MockServer::start(move |request| {
+            if request.path.starts_with("/api/posts/a/comments") {
+                let comments = vec![
+                    fixtures::comment("c1", "a", "alice", "u/ourbot help", "2099-01-01T00:00:00Z"),
+                    fixtures::comment("c2", "a", "bob", "no mention", "2099-01-01T00:00:00Z"),
+                    fixtures::comment("c3", "a", "ourbot", "@ourbot", "2099-01-01T00:00:00Z"),
+                ];
+                return MockResponse::json(
+                    200,
+                    &json!({ "comments": comments, "next": null }).to_string(),
+                );
+            }
+            let poll = server_polls.fetch_add(1, Ordering::SeqCst);
+            let mut post = fixtures::post("a", "general", "alice", "2024-06-01T10:00:00Z");
+            post["noComments"] = json!(3);
+            post["lastActivityAt"] = json!(format!("2024-06-01T10:0{}:00Z", poll));
+            MockResponse::json(200, &json!({ "posts": [post], "next": null }).to_string())
+        })
+        .await;
+
+        let client = DiscuitClient::new(&server.url);
+        client.set_session(Session {
+            user: Some(serde_json::from_value(fixtures::user("ourbot")).unwrap()),
+            ..Session::default()
+        });
+        let mentioned = Arc::new(Mutex::new(Vec::new()));
+        let recorded = mentioned.clone();
+        let mut bot = Bot::new(client)
+            .community("general")
+            .on_mention(move |_, comment| {
+                let recorded = recorded.clone();
+                async move {
+                    recorded.lock().unwrap().push(comment.id);
+                    Ok(())
+                }
+            });
+
+        bot.poll().await.unwrap();
+        bot.poll().await.unwrap();
+        bot.poll().await.unwrap();
+        assert_eq!(*mentioned.lock().unwrap(), vec![CommentId::from("c1")]);
+    }
+
+    #[tokio::test]
+    async fn test_commands_are_replied_to_once() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let server_polls = polls.clone();
+        let server = MockServer::start(move |request| {
+            let poll = server_polls.load(Ordering::SeqCst);
+            if request.method == "POST" {
+                let reply = fixtures::comment("r", "a", "ourbot", "pong", "2099-01-01T00:00:00Z");
+                return MockResponse::json(200, &reply.to_string());
+            }
+            if request.path.starts_with("/api/posts/a/comments") {
+                let mut c1 =
+                    fixtures::comment("c1", "a", "alice", "u/ourbot ping", "2099-01-01T00:00:00Z");
+                let mut c2 = fixtures::comment("c2", "a", "bob", "hi", "2099-01-01T00:00:00Z");
+                if poll >= 3 {
+                    // Editing an answered command doesn't trigger a second reply,
+                    // but editing a command into a comment does.
+                    c1["body"] = json!("u/ourbot ping again");
+                    c1["editedAt"] = json!("2099-01-01T00:01:00Z");
+                    c2["body"] = json!("@ourbot ping");
+                    c2["editedAt"] = json!("2099-01-01T00:01:00Z");
+                }
+                return MockResponse::json(
+                    200,
+                    &json!({ "comments": [c1, c2], "next": null }).to_string(),
+                );
+            }
+            server_polls.fetch_add(1, Ordering::SeqCst);
+            let mut post = fixtures::post("a", "general", "alice", "2024-06-01T10:00:00Z");
+            post["noComments"] = json!(2);
+            post["lastActivityAt"] = json!(format!("2024-06-01T10:0{}:00Z", poll));
+            MockResponse::json(200, &json!({ "posts": [post], "next": null }).to_string())
+        })
+        .await;
+
+        let client = DiscuitClient::new(&server.url);
+        client.set_session(Session {
+            user: Some(serde_json::from_value(fixtures::user("ourbot")).unwrap()),
+            ..Session::default()
+        });
+        let router = CommandRouter::new().command("ping", |_| async { Ok(Some("pong".into())) });
+        let mut bot = Bot::new(client).community("general").commands(router);
+
+        for _ in 0..4 {
+            bot.poll().await.unwrap();
+        }
+
+        let replies: Vec<_> = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "POST")
+            .collect();
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0].body.contains(r#""parentCommentId":"c1""#));
+        assert!(replies[1].body.contains(r#""parentCommentId":"c2""#));
+        assert_eq!(bot.current_checkpoint().replied_comments.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_replies_are_retried() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let server_polls = polls.clone();
+        let c1_replies = Arc::new(AtomicUsize::new(0));
+        let server_c1_replies = c1_replies.clone();
+        let server = MockServer::start(move |request| {
+            if request.method == "POST" {
+                // Replies to c1 fail once, replies to c2 always fail.
+                let c1 = request.body.contains(r#""parentCommentId":"c1""#);
+                if !c1 || server_c1_replies.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return MockResponse::json(403, r#"{"status":403,"message":"Post locked."}"#);
+                }
+                let reply = fixtures::comment("r", "a", "ourbot", "pong", "2099-01-01T00:00:00Z");
+                return MockResponse::json(200, &reply.to_string());
+            }
+            if request.path.starts_with("/api/posts/a/comments") {
+                let c1 =
+                    fixtures::comment("c1", "a", "alice", "u/ourbot ping", "2099-01-01T00:00:00Z");
+                let c2 =
+                    fixtures::comment("c2", "a", "bob", "u/ourbot ping", "2099-01-01T00:00:00Z");
+                return MockResponse::json(
+                    200,
+                    &json!({ "comments": [c1, c2], "next": null }).to_string(),
+                );
+            }
+            // The comments arrive after the first poll; the activity of the
+            // post doesn't change after that.
+            let poll = server_polls.fetch_add(1, Ordering::SeqCst);
+            let mut post = fixtures::post("a", "general", "alice", "2024-06-01T10:00:00Z");
+            post["noComments"] = json!(2);
+            post["lastActivityAt"] = json!(format!("2024-06-01T10:0{}:00Z", poll.min(1)));
+            MockResponse::json(200, &json!({ "posts": [post], "next": null }).to_string())
+        })
+        .await;
+
+        let client = DiscuitClient::new(&server.url);
+        client.set_session(Session {
+            user: Some(serde_json::from_value(fixtures::user("ourbot")).unwrap()),
+            ..Session::default()
+        });
+        let mentioned = Arc::new(AtomicUsize::new(0));
+        let counted = mentioned.clone();
+        let router = CommandRouter::new().command("ping", |_| async { Ok(Some("pong".into())) });
+        let mut bot = Bot::new(client)
+            .community("general")
+            .commands(router)
+            .on_mention(move |_, _| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            });
+
+        for _ in 0..6 {
+            bot.poll().await.unwrap();
+        }
+
+        // Handlers run once per comment, however often the command is retried.
+        assert_eq!(mentioned.load(Ordering::SeqCst), 2);
+        let replies: Vec<_> = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "POST")
+            .collect();
+        let c2_replies = replies
+            .iter()
+            .filter(|request| request.body.contains(r#""parentCommentId":"c2""#))
+            .count();
+        assert_eq!(c1_replies.load(Ordering::SeqCst), 2);
+        assert_eq!(c2_replies, 3);
+        let checkpoint = bot.current_checkpoint();
+        assert!(checkpoint.replied_comments.contains(&CommentId::from("c1")));
+        assert!(!checkpoint.replied_comments.contains(&CommentId::from("c2")));
+        assert!(checkpoint.pending_commands.is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = std::env::temp_dir().join(format!("discuit-bot-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+
+        let mut checkpoint = Checkpoint {
+            started_at: Some(Utc::now()),
+            ..Checkpoint::default()
+        };
+        checkpoint.seen_comments.insert(CommentId::from("abc"), 10);
+        checkpoint.seen_reports.insert(ReportId(7), 10);
+        checkpoint.save(&path).unwrap();
+
+        assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
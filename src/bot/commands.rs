@@ -0,0 +1,273 @@
+//! This module contains a router for commands given to a bot in comments.
+//!
+//! A command is a mention of the bot followed by the command name and its
+//! arguments, e.g. `u/ourbot remindme 2d "call mom"`. The handler registered for
+//! the command returns the body of the reply, which the bot posts as a reply to
+//! the comment.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::bot::commands::CommandRouter;
+//! use discuit_rs::bot::Bot;
+//! # use discuit_rs::client::DiscuitClient;
+//! # async fn run(client: DiscuitClient) {
+//!
+//! let router = CommandRouter::new()
+//!     .command("ping", |_ctx| async move { Ok(Some("pong".to_string())) })
+//!     .command("remindme", |ctx| async move {
+//!         let when = ctx.command.args.first().cloned().unwrap_or_default();
+//!         Ok(Some(format!("I will remind you in {}.", when)))
+//!     });
+//!
+//! Bot::new(client).community("general").commands(router).run().await;
+//! # }
+//! ```
+
+use super::find_mention;
+use crate::client::DiscuitClient;
+use crate::structs::api_types::Comment;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The result returned by command handlers: the body of the reply, if any.
+pub type CommandResult = Result<Option<String>, Box<dyn std::error::Error + Send + Sync>>;
+
+type CommandFuture = Pin<Box<dyn Future<Output = CommandResult> + Send>>;
+type CommandHandler = Box<dyn Fn(CommandContext) -> CommandFuture + Send + Sync>;
+
+/// `Command` is a command parsed from a comment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Command {
+    /// The mention of the bot as it was written, e.g. `u/ourbot`.
+    pub prefix: String,
+    /// The name of the command, in lowercase.
+    pub name: String,
+    /// The arguments of the command. Arguments in double quotes may contain spaces.
+    pub args: Vec<String>,
+}
+
+impl Command {
+    /// Parses the first command given to `username` in `body`.
+    /// Returns None if the bot isn't mentioned or no command follows the mention.
+    pub fn parse(body: &str, username: &str) -> Option<Self> {
+        let mention = find_mention(body, username)?;
+        let rest = &body[mention.end..];
+        let line = rest.lines().next().unwrap_or_default();
+
+        let mut words = split_args(line).into_iter();
+        let name = words.next()?.to_lowercase();
+        Some(Self {
+            prefix: body[mention].to_string(),
+            name,
+            args: words.collect(),
+        })
+    }
+}
+
+/// Splits a line into whitespace-separated words, keeping words in double quotes together.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+    args
+}
+
+/// `CommandContext` is passed to command handlers.
+#[derive(Clone, Debug)]
+pub struct CommandContext {
+    /// The client of the bot.
+    pub client: DiscuitClient,
+    /// The comment the command was given in.
+    pub comment: Comment,
+    /// The parsed command.
+    pub command: Command,
+}
+
+/// `CommandRouter` dispatches commands to the handlers registered for them.
+#[derive(Default)]
+pub struct CommandRouter {
+    handlers: HashMap<String, CommandHandler>,
+    fallback: Option<CommandHandler>,
+}
+
+impl CommandRouter {
+    /// Create a new CommandRouter without any commands.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `handler` for the command with the given name. Names are case-insensitive.
+    pub fn command<F, Fut>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(CommandContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CommandResult> + Send + 'static,
+    {
+        self.handlers.insert(
+            name.to_lowercase(),
+            Box::new(move |ctx| Box::pin(handler(ctx))),
+        );
+        self
+    }
+
+    /// Call `handler` for commands without a handler of their own,
+    /// e.g. to reply with a list of the known commands.
+    pub fn fallback<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(CommandContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CommandResult> + Send + 'static,
+    {
+        self.fallback = Some(Box::new(move |ctx| Box::pin(handler(ctx))));
+        self
+    }
+
+    /// Returns the names of the registered commands, sorted.
+    pub fn commands(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.handlers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Parses the command given to `username` in the comment and calls its handler.
+    /// Returns the body of the reply, or None if there is nothing to reply.
+    pub async fn dispatch(
+        &self,
+        client: &DiscuitClient,
+        comment: &Comment,
+        username: &str,
+    ) -> CommandResult {
+        let Some(command) = Command::parse(&comment.body, username) else {
+            return Ok(None);
+        };
+        let Some(handler) = self.handlers.get(&command.name).or(self.fallback.as_ref()) else {
+            return Ok(None);
+        };
+
+        handler(CommandContext {
+            client: client.clone(),
+            comment: comment.clone(),
+            command,
+        })
+        .await
+    }
+
+    /// Dispatches the command in the comment and posts the reply, if any, as a
+    /// reply to the comment.
+    pub async fn reply(
+        &self,
+        client: &DiscuitClient,
+        comment: &Comment,
+        username: &str,
+    ) -> Result<Option<Comment>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(body) = self.dispatch(client, comment, username).await? else {
+            return Ok(None);
+        };
+        let reply = client
+            .create_comment(&comment.post_public_id, Some(&comment.id), &body)
+            .await?;
+        Ok(Some(reply))
+    }
+}
+
+impl std::fmt::Debug for CommandRouter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandRouter")
+            .field("commands", &self.commands())
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_parse() {
+        let command = Command::parse("hey U/OurBot RemindMe 2d \"call mom\"\nthanks", "ourbot");
+        assert_eq!(
+            command,
+            Some(Command {
+                prefix: "U/OurBot".to_string(),
+                name: "remindme".to_string(),
+                args: vec!["2d".to_string(), "call mom".to_string()],
+            })
+        );
+
+        assert_eq!(
+            Command::parse("@ourbot ping", "ourbot").unwrap().name,
+            "ping"
+        );
+        assert_eq!(Command::parse("@ourbot\nping", "ourbot"), None);
+        assert_eq!(Command::parse("u/ourbot2 ping", "ourbot"), None);
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("  a  b "), vec!["a", "b"]);
+        assert_eq!(split_args("a \"\" b"), vec!["a", "", "b"]);
+        assert_eq!(split_args("x\"y z\""), vec!["xy z"]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let router = CommandRouter::new()
+            .command("Ping", |_| async move { Ok(Some("pong".to_string())) })
+            .command(
+                "echo",
+                |ctx| async move { Ok(Some(ctx.command.args.join(" "))) },
+            );
+        let client = DiscuitClient::new("http://localhost");
+        let comment = |body: &str| -> Comment {
+            serde_json::from_value(fixtures::comment(
+                "c",
+                "a",
+                "alice",
+                body,
+                "2024-06-01T10:00:00Z",
+            ))
+            .unwrap()
+        };
+
+        let reply = |body: &'static str| {
+            let router = &router;
+            let client = &client;
+            async move {
+                router
+                    .dispatch(client, &comment(body), "ourbot")
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(reply("u/ourbot ping").await, Some("pong".to_string()));
+        assert_eq!(reply("u/ourbot echo a b").await, Some("a b".to_string()));
+        assert_eq!(reply("u/ourbot unknown").await, None);
+        assert_eq!(router.commands(), vec!["echo", "ping"]);
+    }
+}
@@ -0,0 +1,292 @@
+//! This module contains the `DiscuitClientBuilder`, which is used to configure
+//! and create a `DiscuitClient`.
+
+use crate::cache::ResponseCache;
+use crate::client::DiscuitClient;
+use crate::hook::DecodeHook;
+use crate::metrics::Metrics;
+use crate::middleware::Middleware;
+use crate::rate_limit::RateLimiter;
+use crate::schema::{SchemaDrift, SchemaMode};
+use crate::structs::internal_types::*;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The user agent used when none is configured.
+const DEFAULT_USER_AGENT: &str = "DiscuitClient";
+
+/// DiscuitClientBuilder is used to configure and create a `DiscuitClient`.
+///
+/// # Example
+///
+/// ```
+/// # use discuit_rs::client::*;
+/// use std::time::Duration;
+///
+/// let client = DiscuitClient::builder("https://discuit.net")
+///     .user_agent("my-bot/1.0")
+///     .timeout(Duration::from_secs(30))
+///     .connect_timeout(Duration::from_secs(5))
+///     .build()
+///     .expect("Failed to build client");
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiscuitClientBuilder {
+    base_url: String,
+    user_agent: String,
+    log_level: LogLevel,
+    rate_limiter: RateLimiter,
+    max_retries: u32,
+    schema_mode: SchemaMode,
+    cache: Option<ResponseCache>,
+    hooks: Vec<Arc<dyn DecodeHook>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    metrics: Option<Metrics>,
+    dry_run: bool,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxies: Vec<Proxy>,
+    root_certificates: Vec<Certificate>,
+    built_in_root_certificates: bool,
+    default_headers: HeaderMap,
+    http_client: Option<Client>,
+}
+
+impl DiscuitClientBuilder {
+    /// Create a new DiscuitClientBuilder for the given base URL.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            log_level: LogLevel::default(),
+            rate_limiter: RateLimiter::default(),
+            max_retries: 0,
+            schema_mode: SchemaMode::default(),
+            cache: None,
+            hooks: Vec::new(),
+            middleware: Vec::new(),
+            metrics: None,
+            dry_run: false,
+            timeout: None,
+            connect_timeout: None,
+            proxies: Vec::new(),
+            root_certificates: Vec::new(),
+            built_in_root_certificates: true,
+            default_headers: HeaderMap::new(),
+            http_client: None,
+        }
+    }
+
+    /// Set the user agent sent with every request.
+    /// Defaults to "DiscuitClient".
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Set the level of logging to use.
+    /// Defaults to `LogLevel::Info`.
+    pub fn log_level(mut self, log_level: LogLevel) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    /// Set the rate limiter used to throttle requests.
+    /// Defaults to no limits.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Set the maximum number of times a read is retried after a connection
+    /// error, a timeout or a `429`/`502`/`503`/`504` response, with exponential
+    /// backoff of up to a minute between attempts, or as long as the
+    /// `Retry-After` of a `429` asks, in seconds or as a date. A `429` asking
+    /// to wait longer than a minute is returned rather than retried. Writes
+    /// are never retried. Defaults to 0.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set how fields of API responses that the structs don't know about are
+    /// handled. See the `schema` module. Defaults to `SchemaMode::Ignore`.
+    pub fn schema_mode(mut self, schema_mode: SchemaMode) -> Self {
+        self.schema_mode = schema_mode;
+        self
+    }
+
+    /// Set the cache successful reads are served from. See the `cache` module.
+    /// Defaults to no cache.
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Add a hook that is called with every decoded response. See the `hook`
+    /// module. Can be called multiple times to add several hooks.
+    pub fn hook(mut self, hook: impl DecodeHook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Add a middleware that every request passes through. See the
+    /// `middleware` module. Can be called multiple times; requests pass
+    /// through the middleware in the order they were added.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Set the `Metrics` the client's requests are recorded in. See the
+    /// `metrics` module. Defaults to no metrics.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Set whether writes (posting, commenting, voting, deleting, moderating,
+    /// ...) are only validated and logged instead of sent, returning a
+    /// synthesized result. Reads are still sent. Defaults to false.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Set a timeout for the whole request, from connecting until the
+    /// response body has been read. Defaults to no timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for only the connect phase of a request.
+    /// Defaults to no timeout.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Add a proxy to route requests through, e.g. `Proxy::https("http://proxy:8080")`.
+    /// Can be called multiple times to add HTTP and HTTPS proxies separately.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Add a custom root certificate to trust, e.g. for self-hosted instances
+    /// using a private certificate authority.
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Whether to trust the built-in root certificates in addition to the
+    /// custom ones. Defaults to `true`.
+    pub fn built_in_root_certificates(mut self, enabled: bool) -> Self {
+        self.built_in_root_certificates = enabled;
+        self
+    }
+
+    /// Add a header sent with every request.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    /// Add headers sent with every request.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// Use a prebuilt `reqwest::Client` instead of building one.
+    ///
+    /// The user agent, timeouts, proxies, certificates and default headers set
+    /// on this builder are ignored, as they have to be configured on the
+    /// given client instead.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Returns whether a prebuilt `reqwest::Client` was given.
+    /// For internal use only.
+    pub(crate) fn has_http_client(&self) -> bool {
+        self.http_client.is_some()
+    }
+
+    /// Build the `DiscuitClient`.
+    /// Returns an error if the HTTP client could not be created, e.g. because
+    /// the user agent is not a valid header value or the TLS backend failed to
+    /// initialize.
+    pub fn build(self) -> Result<DiscuitClient, reqwest::Error> {
+        let client = match self.http_client {
+            Some(client) => client,
+            None => {
+                let mut builder = ClientBuilder::new()
+                    .user_agent(self.user_agent.as_str())
+                    .cookie_store(true)
+                    .default_headers(self.default_headers)
+                    .tls_built_in_root_certs(self.built_in_root_certificates);
+
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                for proxy in self.proxies {
+                    builder = builder.proxy(proxy);
+                }
+                for certificate in self.root_certificates {
+                    builder = builder.add_root_certificate(certificate);
+                }
+
+                builder.build()?
+            }
+        };
+
+        Ok(DiscuitClient::from_parts(
+            client,
+            self.base_url,
+            self.user_agent,
+            self.log_level,
+            self.rate_limiter,
+            self.max_retries,
+            SchemaDrift::new(self.schema_mode),
+            self.cache,
+            self.hooks,
+            self.middleware,
+            self.metrics,
+            self.dry_run,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let client = DiscuitClientBuilder::new("https://discuit.net/")
+            .build()
+            .unwrap();
+
+        assert_eq!(client.base_url, "https://discuit.net");
+        assert_eq!(client.user_agent, DEFAULT_USER_AGENT);
+        assert_eq!(client.log_level, LogLevel::Info);
+    }
+
+    #[test]
+    fn test_invalid_user_agent() {
+        let result = DiscuitClientBuilder::new("https://discuit.net")
+            .user_agent("bad\nagent")
+            .build();
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,579 @@
+//! This module contains the response cache used by `DiscuitClient`.
+//!
+//! Successful `GET` responses are stored in a `CacheBackend`, keyed by the
+//! authenticated user and the URL, and served from there until their TTL runs
+//! out. Expired responses with an `ETag` are revalidated with `If-None-Match`,
+//! so an unchanged resource costs a `304` instead of the whole body.
+//!
+//! Writes invalidate what they change: a write to `/api/posts/{id}/...` drops
+//! every cached response under `/api/posts/{id}`, and voting drops the voted
+//! post. Responses that set cookies, e.g. `/api/_initial`, are never cached.
+//!
+//! # Example
+//!
+//! ```
+//! use discuit_rs::cache::{MemoryCache, ResponseCache};
+//! use discuit_rs::client::DiscuitClient;
+//! use std::time::Duration;
+//!
+//! let cache = ResponseCache::new(MemoryCache::new(1000))
+//!     .default_ttl(Duration::from_secs(30))
+//!     .ttl("/api/users/", Duration::from_secs(300))
+//!     .ttl("/api/notifications", Duration::ZERO);
+//! let client = DiscuitClient::builder("https://discuit.net")
+//!     .cache(cache)
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use reqwest::header::{CONTENT_TYPE, ETAG};
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tracing::{debug, warn};
+use url::Url;
+
+/// The TTL used for endpoints without a TTL of their own.
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// `CachedResponse` is a response stored in a cache.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
+pub struct CachedResponse {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The `Content-Type` header, if any.
+    pub content_type: Option<String>,
+    /// The `ETag` header, if any.
+    pub etag: Option<String>,
+    /// The time at which the response was received or last revalidated.
+    pub stored_at: DateTime<Utc>,
+    /// The body of the response.
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl CachedResponse {
+    /// Reads a response into a `CachedResponse`.
+    /// For internal use only.
+    pub(crate) async fn read(response: Response) -> Result<Self, reqwest::Error> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let (content_type, etag) = (header(CONTENT_TYPE), header(ETAG));
+        Ok(Self {
+            status: response.status().as_u16(),
+            content_type,
+            etag,
+            stored_at: Utc::now(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+
+    /// Turns the cached response back into a `Response`.
+    /// For internal use only.
+    pub(crate) fn into_response(self) -> Response {
+        let mut builder = http::Response::builder().status(self.status);
+        if let Some(content_type) = &self.content_type {
+            builder = builder.header(CONTENT_TYPE, content_type);
+        }
+        if let Some(etag) = &self.etag {
+            builder = builder.header(ETAG, etag);
+        }
+        builder
+            .body(self.body)
+            // Rebuilding a response that was received always works, as its
+            // status and headers were valid then.
+            .unwrap_or_default()
+            .into()
+    }
+}
+
+/// `CacheBackend` stores cached responses.
+///
+/// Backends are shared between tasks, so they have to synchronize internally.
+pub trait CacheBackend: Send + Sync + fmt::Debug {
+    /// Returns the response stored under the given key.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Stores a response under the given key, replacing any previous one.
+    fn put(&self, key: &str, response: CachedResponse);
+
+    /// Removes every response whose key matches the predicate.
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool);
+
+    /// Removes every response.
+    fn clear(&self);
+}
+
+/// `MemoryCache` is an in-memory backend that evicts the least recently used
+/// response once it is full.
+#[derive(Debug)]
+pub struct MemoryCache {
+    entries: Mutex<LruCache<String, CachedResponse>>,
+}
+
+impl MemoryCache {
+    /// Create a new `MemoryCache` holding up to `capacity` responses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).expect("capacity must be at least 1");
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Locks the entries.
+    /// For internal use only.
+    fn lock(&self) -> MutexGuard<'_, LruCache<String, CachedResponse>> {
+        // The entries are only plain data, so they are still usable if a
+        // thread panicked while holding the lock.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.lock().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        self.lock().put(key.to_string(), response);
+    }
+
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) {
+        let mut entries = self.lock();
+        let keys: Vec<String> = entries
+            .iter()
+            .filter(|(key, _)| predicate(key))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            entries.pop(&key);
+        }
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+/// `DiskCache` is a backend that stores every response in a file, so the
+/// cache survives restarts.
+///
+/// Each file starts with a line of JSON holding the key and the metadata of
+/// the response, followed by the raw body.
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+/// The first line of a `DiskCache` file.
+#[derive(Deserialize, Serialize)]
+struct DiskEntry {
+    key: String,
+    #[serde(flatten)]
+    response: CachedResponse,
+}
+
+impl DiskCache {
+    /// Create a new `DiskCache` in the given directory, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory the responses are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path of the file a key is stored in.
+    /// For internal use only.
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", fnv1a(key)))
+    }
+
+    /// Reads a stored entry.
+    /// For internal use only.
+    fn read(path: &Path) -> io::Result<DiskEntry> {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let mut entry: DiskEntry = serde_json::from_str(&header).map_err(io::Error::other)?;
+        reader.read_to_end(&mut entry.response.body)?;
+        Ok(entry)
+    }
+
+    /// Writes an entry, going through a temporary file so a crash never
+    /// leaves a partial entry behind.
+    /// For internal use only.
+    fn write(path: &Path, entry: &DiskEntry) -> io::Result<()> {
+        // The temporary file is unique, as other threads or processes may
+        // write the same entry at the same time.
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let tmp = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = fs::File::create(&tmp)?;
+        serde_json::to_writer(&mut file, entry).map_err(io::Error::other)?;
+        file.write_all(b"\n")?;
+        file.write_all(&entry.response.body)?;
+        file.sync_all()?;
+        fs::rename(tmp, path)
+    }
+}
+
+impl CacheBackend for DiskCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        match Self::read(&self.path(key)) {
+            // Different keys can hash to the same file.
+            Ok(entry) if entry.key == key => Some(entry.response),
+            Ok(_) => None,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!(error = %e, "Failed to read cached response");
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let entry = DiskEntry {
+            key: key.to_string(),
+            response,
+        };
+        if let Err(e) = Self::write(&self.path(key), &entry) {
+            warn!(error = %e, "Failed to write cached response");
+        }
+    }
+
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            // Temporary files belong to writes in progress.
+            if path.extension().is_some_and(|extension| extension == "tmp") {
+                continue;
+            }
+            let matches = Self::read(&path).is_ok_and(|entry| predicate(&entry.key));
+            if matches {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    fn clear(&self) {
+        self.remove_where(&|_| true);
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of a string, which unlike the hasher of the
+/// standard library is stable across Rust versions.
+/// For internal use only.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// `ResponseCache` decides which responses are cached and for how long.
+///
+/// It is cheap to clone; clones share the same backend.
+#[derive(Clone, Debug)]
+pub struct ResponseCache {
+    backend: Arc<dyn CacheBackend>,
+    default_ttl: Duration,
+    /// TTLs of the endpoints with a TTL of their own, keyed by path prefix.
+    ttls: Vec<(String, Duration)>,
+}
+
+/// `Lookup` is the result of looking a request up in the cache.
+/// For internal use only.
+pub(crate) enum Lookup {
+    /// A fresh response that can be used as is.
+    Fresh(CachedResponse),
+    /// An expired response that can be revalidated with its ETag.
+    Stale(CachedResponse),
+    /// Nothing usable is cached.
+    Miss,
+}
+
+impl ResponseCache {
+    /// Create a new `ResponseCache` storing responses in the given backend.
+    pub fn new(backend: impl CacheBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            default_ttl: DEFAULT_TTL,
+            ttls: Vec::new(),
+        }
+    }
+
+    /// Set the TTL of endpoints without a TTL of their own.
+    /// Defaults to 60 seconds.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Set the TTL of the endpoints whose path starts with `path_prefix`,
+    /// e.g. `/api/users/`. The longest matching prefix wins.
+    /// A TTL of zero disables caching for those endpoints.
+    pub fn ttl(mut self, path_prefix: &str, ttl: Duration) -> Self {
+        self.ttls.retain(|(prefix, _)| prefix != path_prefix);
+        self.ttls.push((path_prefix.to_string(), ttl));
+        self
+    }
+
+    /// Returns the backend the responses are stored in.
+    pub fn backend(&self) -> &dyn CacheBackend {
+        self.backend.as_ref()
+    }
+
+    /// Returns the TTL of the endpoint at the given path.
+    pub fn ttl_for(&self, path: &str) -> Duration {
+        self.ttls
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default_ttl, |(_, ttl)| *ttl)
+    }
+
+    /// Removes every cached response for the resource at `path` and the ones
+    /// below it, for all users: `/api/posts/abc` covers `/api/posts/abc` and
+    /// `/api/posts/abc/comments`, but not `/api/posts/abcd`.
+    pub fn invalidate(&self, path: &str) {
+        debug!(path, "Invalidating cached responses");
+        let path = path.trim_end_matches('/');
+        self.backend.remove_where(&|key| {
+            key_url(key).is_some_and(|url| {
+                url.path()
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+        });
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) {
+        self.backend.clear();
+    }
+
+    /// Returns the key of a request to the given URL made by the given user.
+    /// For internal use only.
+    pub(crate) fn key(identity: &str, url: &Url) -> String {
+        format!("{} {}", identity, url)
+    }
+
+    /// Looks up the response cached under the given key.
+    /// For internal use only.
+    pub(crate) fn lookup(&self, key: &str) -> Lookup {
+        let Some(url) = key_url(key) else {
+            return Lookup::Miss;
+        };
+        let ttl = self.ttl_for(url.path());
+        if ttl.is_zero() {
+            return Lookup::Miss;
+        }
+        let Some(response) = self.backend.get(key) else {
+            return Lookup::Miss;
+        };
+
+        let age = (Utc::now() - response.stored_at)
+            .to_std()
+            .unwrap_or_default();
+        if age < ttl {
+            Lookup::Fresh(response)
+        } else if response.etag.is_some() {
+            Lookup::Stale(response)
+        } else {
+            Lookup::Miss
+        }
+    }
+
+    /// Stores a response under the given key, unless its endpoint isn't cached.
+    /// For internal use only.
+    pub(crate) fn store(&self, key: &str, response: CachedResponse) {
+        let cached = key_url(key).is_some_and(|url| !self.ttl_for(url.path()).is_zero());
+        if cached {
+            self.backend.put(key, response);
+        }
+    }
+
+    /// Invalidates what a successful write to the given path changed: everything
+    /// under the resource it belongs to, e.g. `/api/posts/{id}` for a write to
+    /// `/api/posts/{id}/comments`.
+    /// For internal use only.
+    pub(crate) fn invalidate_write(&self, path: &str) {
+        let resource: Vec<&str> = path.split('/').take(4).collect();
+        self.invalidate(&resource.join("/"));
+    }
+}
+
+/// Returns the URL of a cache key.
+/// For internal use only.
+fn key_url(key: &str) -> Option<Url> {
+    key.split_once(' ')
+        .and_then(|(_, url)| Url::parse(url).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &str, etag: Option<&str>, age: Duration) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            content_type: Some("application/json".to_string()),
+            etag: etag.map(str::to_string),
+            stored_at: Utc::now() - chrono::Duration::from_std(age).unwrap(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn key(path: &str) -> String {
+        ResponseCache::key(
+            "-",
+            &Url::parse("https://discuit.net")
+                .unwrap()
+                .join(path)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_ttls() {
+        let cache = ResponseCache::new(MemoryCache::new(10))
+            .ttl("/api/users/", Duration::from_secs(300))
+            .ttl("/api/users/admin", Duration::ZERO);
+
+        assert_eq!(cache.ttl_for("/api/posts"), DEFAULT_TTL);
+        assert_eq!(cache.ttl_for("/api/users/ferris"), Duration::from_secs(300));
+        assert_eq!(cache.ttl_for("/api/users/admin"), Duration::ZERO);
+
+        let admin = key("/api/users/admin");
+        cache.store(&admin, response("{}", None, Duration::ZERO));
+        assert!(cache.backend().get(&admin).is_none());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let cache = ResponseCache::new(MemoryCache::new(10));
+        let (fresh, stale, expired) = (key("/api/a"), key("/api/b"), key("/api/c"));
+        let old = Duration::from_secs(120);
+        cache.store(&fresh, response("a", None, Duration::ZERO));
+        cache.store(&stale, response("b", Some("\"v1\""), old));
+        cache.store(&expired, response("c", None, old));
+
+        assert!(matches!(cache.lookup(&fresh), Lookup::Fresh(r) if r.body == b"a"));
+        assert!(matches!(cache.lookup(&stale), Lookup::Stale(_)));
+        assert!(matches!(cache.lookup(&expired), Lookup::Miss));
+        assert!(matches!(cache.lookup(&key("/api/d")), Lookup::Miss));
+    }
+
+    #[test]
+    fn test_invalidate_write() {
+        let cache = ResponseCache::new(MemoryCache::new(10));
+        let keys = [
+            key("/api/posts/abc"),
+            key("/api/posts/abc/comments?next=1"),
+            key("/api/posts/abcd"),
+            key("/api/posts?sort=new"),
+        ];
+        for key in &keys {
+            cache.store(key, response("{}", None, Duration::ZERO));
+        }
+
+        cache.invalidate_write("/api/posts/abc/comments/123");
+
+        let cached: Vec<bool> = keys
+            .iter()
+            .map(|key| cache.backend().get(key).is_some())
+            .collect();
+        // `/api/posts/abcd` shares the prefix but is a different post.
+        assert_eq!(cached, [false, false, true, true]);
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.put("a", response("a", None, Duration::ZERO));
+        cache.put("b", response("b", None, Duration::ZERO));
+        cache.get("a");
+        cache.put("c", response("c", None, Duration::ZERO));
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = std::env::temp_dir().join(format!("discuit-cache-{}", rand::random::<u64>()));
+        let cache = DiskCache::new(&dir).unwrap();
+        let stored = response("{\"id\": 1}\n", Some("\"v1\""), Duration::ZERO);
+        cache.put("a", stored.clone());
+        cache.put("b", response("b", None, Duration::ZERO));
+
+        // A new backend in the same directory sees the stored responses.
+        let cache = DiskCache::new(&dir).unwrap();
+        assert_eq!(cache.get("a"), Some(stored));
+        cache.remove_where(&|key| key == "a");
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+        cache.clear();
+        assert!(cache.get("b").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_disk_cache_concurrent_writes() {
+        let dir = std::env::temp_dir().join(format!("discuit-cache-{}", rand::random::<u64>()));
+        let cache = Arc::new(DiskCache::new(&dir).unwrap());
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        cache.put("a", response(&i.to_string(), None, Duration::ZERO));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(cache.get("a").is_some());
+
+        // A write in progress is left alone.
+        let tmp = dir.join("0000000000000000.1-1.tmp");
+        fs::write(&tmp, "partial").unwrap();
+        cache.clear();
+        assert!(cache.get("a").is_none());
+        assert!(tmp.exists());
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().flatten().collect();
+        assert_eq!(files.len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+//! This module contains `InstanceCapabilities`, the features a Discuit
+//! instance supports, as found by `DiscuitClient::probe`.
+//!
+//! Self-hosted instances run different versions of Discuit, so apps can use
+//! the capabilities to hide features an instance doesn't have instead of
+//! running into errors.
+
+use crate::structs::api_types::ReportReason;
+use serde_json::{Map, Value};
+
+/// The fields of `/api/_initial` that `InitialResponse` knows about.
+const KNOWN_INITIAL_FIELDS: [&str; 8] = [
+    "reportReasons",
+    "user",
+    "lists",
+    "communities",
+    "noUsers",
+    "bannedFrom",
+    "vapidPublicKey",
+    "mutes",
+];
+
+/// `InstanceCapabilities` describes what a Discuit instance supports.
+///
+/// Image upload limits aren't included, as Discuit reports them neither in
+/// `/api/_initial` nor elsewhere; an instance that adds them to
+/// `/api/_initial` has them in `extra_fields`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct InstanceCapabilities {
+    /// The base URL of the instance.
+    pub base_url: String,
+    /// The value of the `Server` header, if the instance sends one.
+    pub server: Option<String>,
+    /// The names of the fields of `/api/_initial`, sorted. Which fields are
+    /// present tells different versions of Discuit apart.
+    pub initial_fields: Vec<String>,
+    /// The fields of `/api/_initial` that this crate doesn't know about, e.g.
+    /// settings added by newer versions of Discuit.
+    pub extra_fields: Map<String, Value>,
+    /// The total number of users, if the instance reports it.
+    pub no_users: Option<i32>,
+    /// Whether the instance supports web push notifications, i.e. whether it
+    /// has a VAPID public key.
+    pub push_notifications: bool,
+    /// The VAPID public key used for push notifications, if any.
+    pub vapid_public_key: Option<String>,
+    /// Whether posts and comments can be reported, i.e. whether there are
+    /// report reasons.
+    pub reports: bool,
+    /// The reasons posts and comments can be reported for.
+    pub report_reasons: Vec<ReportReason>,
+    /// Whether the instance supports muting users and communities.
+    pub mutes: bool,
+    /// Whether the instance supports lists of saved posts and comments.
+    /// `None` if it couldn't be determined, e.g. because the client isn't
+    /// logged in and `/api/_initial` doesn't mention lists.
+    pub lists: Option<bool>,
+}
+
+impl InstanceCapabilities {
+    /// Reads the capabilities from the body of `/api/_initial`.
+    /// Capabilities that depend on other endpoints are left unknown.
+    pub fn from_initial(base_url: &str, initial: &Value) -> Self {
+        let empty = Map::new();
+        let fields = initial.as_object().unwrap_or(&empty);
+
+        let mut initial_fields: Vec<String> = fields.keys().cloned().collect();
+        initial_fields.sort();
+        let extra_fields = fields
+            .iter()
+            .filter(|(key, _)| !KNOWN_INITIAL_FIELDS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let vapid_public_key = fields
+            .get("vapidPublicKey")
+            .and_then(Value::as_str)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string);
+        let report_reasons: Vec<ReportReason> = fields
+            .get("reportReasons")
+            .and_then(|reasons| serde_json::from_value(reasons.clone()).ok())
+            .unwrap_or_default();
+        // A logged out user gets `null` lists, so only a present field counts.
+        let lists = fields
+            .get("lists")
+            .and_then(|lists| (!lists.is_null()).then_some(true));
+
+        Self {
+            base_url: base_url.to_string(),
+            server: None,
+            initial_fields,
+            extra_fields,
+            no_users: fields
+                .get("noUsers")
+                .and_then(Value::as_i64)
+                .and_then(|n| i32::try_from(n).ok()),
+            push_notifications: vapid_public_key.is_some(),
+            vapid_public_key,
+            reports: !report_reasons.is_empty(),
+            report_reasons,
+            mutes: fields.contains_key("mutes"),
+            lists,
+        }
+    }
+
+    /// Returns true if `/api/_initial` has a field with the given name.
+    pub fn has_initial_field(&self, name: &str) -> bool {
+        self.initial_fields.iter().any(|field| field == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_initial() {
+        let initial: Value =
+            serde_json::from_str(include_str!("structs/payloads/initial.json")).unwrap();
+        let capabilities = InstanceCapabilities::from_initial("https://discuit.net", &initial);
+
+        assert!(capabilities.push_notifications);
+        assert!(capabilities.reports);
+        assert_eq!(capabilities.report_reasons.len(), 2);
+        assert!(capabilities.mutes);
+        assert_eq!(capabilities.no_users, Some(18204));
+        // The payload is of a logged in user without lists.
+        assert_eq!(capabilities.lists, None);
+        assert!(capabilities.extra_fields.is_empty());
+        assert!(capabilities.has_initial_field("bannedFrom"));
+    }
+
+    #[test]
+    fn test_from_minimal_initial() {
+        let initial = json!({
+            "reportReasons": [],
+            "communities": [],
+            "noUsers": 3,
+            "vapidPublicKey": "",
+            "maxImageSize": 26214400
+        });
+        let capabilities = InstanceCapabilities::from_initial("https://example.org", &initial);
+
+        assert!(!capabilities.push_notifications);
+        assert!(!capabilities.reports);
+        assert!(!capabilities.mutes);
+        assert_eq!(capabilities.lists, None);
+        assert_eq!(capabilities.extra_fields["maxImageSize"], 26214400);
+    }
+}
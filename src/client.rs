@@ -1,146 +1,529 @@
+pub use crate::builder::DiscuitClientBuilder;
+use crate::cache::ResponseCache;
+use crate::capabilities::InstanceCapabilities;
+use crate::dry_run::{self, Dispatched, Write};
+pub use crate::error::Error;
+use crate::hook::DecodeHook;
+use crate::metrics::Metrics;
+use crate::middleware::{
+    CacheLayer, LoggingLayer, MetricsLayer, Middleware, Next, RateLimitLayer, RetryLayer,
+};
+use crate::rate_limit::RateLimiter;
+use crate::schema::SchemaDrift;
+use crate::search::{Search, Searchable};
 use crate::structs::api_requests::*;
 use crate::structs::api_responses::*;
 use crate::structs::api_types::*;
 use crate::structs::internal_types::*;
-use reqwest::{Client, ClientBuilder};
+use reqwest::header::{HeaderValue, COOKIE, SERVER};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::collections::HashMap;
-use url::Url;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::{debug, info, warn};
 
 /// DiscuitClient represents a client for the Discuit API and
 /// provides methods to interact with the API.
-#[derive(Debug)]
+///
+/// Cloning the client is cheap; clones share the same HTTP connection pool,
+/// rate limiter, session, schema drift report and cache, so a client can be
+/// shared between tasks and all methods take `&self`.
+#[derive(Clone, Debug)]
 pub struct DiscuitClient {
     /// The HTTP client.
     pub client: Client,
-    /// The CSRF token.
-    pub csrf_token: String,
-    /// The session ID.
-    pub session_id: String,
     /// The base URL of the Discuit instance.
     pub base_url: String,
-    /// The user agent to use for requests.
-    /// Defaults to "DiscuitClient".
+    /// The user agent sent with every request.
+    /// Defaults to "DiscuitClient", set it with `DiscuitClientBuilder::user_agent`.
     pub user_agent: String,
-    /// The User object of the authenticated user.
-    /// If the client is not authenticated, this is None.
-    pub user: Option<User>,
-    /// `log_level` is the level of logging to use.
+    /// `log_level` is the level of logging that was configured for the client.
     /// Defaults to `LogLevel::Info`.
+    ///
+    /// This is kept for compatibility only: the client emits its logs through
+    /// `tracing`, so filtering is up to the subscriber. Use
+    /// `LevelFilter::from(log_level)` to configure one from this value.
     pub log_level: LogLevel,
+    /// The rate limiter used to throttle requests.
+    /// Defaults to no limits.
+    pub rate_limiter: RateLimiter,
+    /// The maximum number of times a failed read is retried.
+    /// Defaults to 0.
+    pub max_retries: u32,
+    /// The schema drift detection applied to decoded responses.
+    /// Defaults to `SchemaMode::Ignore`.
+    pub schema_drift: SchemaDrift,
+    /// The cache successful reads are served from, if any.
+    /// Defaults to no cache.
+    pub cache: Option<ResponseCache>,
+    /// The hooks called with every decoded response.
+    /// Defaults to none, add them with `DiscuitClientBuilder::hook`.
+    pub hooks: Vec<Arc<dyn DecodeHook>>,
+    /// The middleware every request passes through, in order.
+    /// Defaults to none, add them with `DiscuitClientBuilder::middleware`.
+    pub middleware: Vec<Arc<dyn Middleware>>,
+    /// The metrics requests are recorded in, if any.
+    /// Defaults to none, set them with `DiscuitClientBuilder::metrics`.
+    pub metrics: Option<Metrics>,
+    /// Whether writes are only validated and logged instead of sent.
+    /// Defaults to false, set it with `DiscuitClientBuilder::dry_run`.
+    pub dry_run: bool,
+    /// The session shared by all clones of the client.
+    session: Arc<RwLock<Session>>,
 }
 
 impl DiscuitClient {
-    /// Logger is a helper function to log messages based on the log level.
+    /// Create a new DiscuitClient with the given base URL and the default configuration.
+    /// Use `DiscuitClient::builder` to configure the client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client could not be created, e.g. because the TLS
+    /// backend failed to initialize.
+    pub fn new(base_url: &str) -> Self {
+        Self::builder(base_url)
+            .build()
+            .expect("Failed to build the HTTP client")
+    }
+
+    /// Create a new `DiscuitClientBuilder` for the given base URL.
+    pub fn builder(base_url: &str) -> DiscuitClientBuilder {
+        DiscuitClientBuilder::new(base_url)
+    }
+
+    /// Create a new DiscuitClient from its parts.
     /// For internal use only.
-    fn log(&self, level: LogLevel, message: &str) {
-        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_parts(
+        client: Client,
+        base_url: String,
+        user_agent: String,
+        log_level: LogLevel,
+        rate_limiter: RateLimiter,
+        max_retries: u32,
+        schema_drift: SchemaDrift,
+        cache: Option<ResponseCache>,
+        hooks: Vec<Arc<dyn DecodeHook>>,
+        middleware: Vec<Arc<dyn Middleware>>,
+        metrics: Option<Metrics>,
+        dry_run: bool,
+    ) -> Self {
+        Self {
+            client,
+            base_url,
+            user_agent,
+            log_level,
+            rate_limiter,
+            max_retries,
+            schema_drift,
+            cache,
+            hooks,
+            middleware,
+            metrics,
+            dry_run,
+            session: Arc::new(RwLock::new(Session::default())),
+        }
+    }
+
+    /// Set the rate limiter used by the client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discuit_rs::client::*;
+    /// use discuit_rs::rate_limit::{RateLimit, RateLimiter};
+    ///
+    /// // 5 reads per second with bursts of 10, one write every two seconds.
+    /// let client = DiscuitClient::new("https://discuit.net").with_rate_limiter(RateLimiter::new(
+    ///     RateLimit::new(5.0, 10),
+    ///     RateLimit::new(0.5, 1),
+    /// ));
+    /// ```
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Locks the session for reading.
+    /// For internal use only.
+    fn read_session(&self) -> RwLockReadGuard<'_, Session> {
+        // The session only holds plain data, so it is still usable if a thread
+        // panicked while holding the lock.
+        self.session.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the session for writing.
+    /// For internal use only.
+    fn write_session(&self) -> RwLockWriteGuard<'_, Session> {
+        self.session.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns a copy of the current session.
+    pub fn session(&self) -> Session {
+        self.read_session().clone()
+    }
+
+    /// Replace the current session, e.g. to restore a session saved earlier.
+    pub fn set_session(&self, session: Session) {
+        *self.write_session() = session;
+    }
+
+    /// Returns the CSRF token of the current session.
+    pub fn csrf_token(&self) -> String {
+        self.read_session().csrf_token.clone()
+    }
+
+    /// Returns the session ID of the current session.
+    pub fn session_id(&self) -> String {
+        self.read_session().session_id.clone()
+    }
+
+    /// Returns the User object of the authenticated user.
+    /// If the client is not authenticated, this is None.
+    pub fn user(&self) -> Option<User> {
+        self.read_session().user.clone()
+    }
+
+    /// Returns the value of the `Cookie` header for the current session.
+    /// For internal use only.
+    fn cookie_header(&self) -> String {
+        let session = self.read_session();
+        format!(
+            "csrftoken={}; SID={}",
+            session.csrf_token, session.session_id
+        )
+    }
 
-        if level >= self.log_level {
-            println!("{} [{}] {}", timestamp, level, message);
+    /// Resets the client to its initial state.
+    pub fn reset(&self) {
+        *self.write_session() = Session::default();
+        info!("Client reset.");
+    }
+
+    /// Creates a request to the given API path with the session headers set.
+    /// The headers are marked as sensitive so they are never logged.
+    /// For internal use only.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut csrf_token = HeaderValue::from_str(&self.csrf_token())
+            .unwrap_or_else(|_| HeaderValue::from_static(""));
+        csrf_token.set_sensitive(true);
+        let mut cookie = HeaderValue::from_str(&self.cookie_header())
+            .unwrap_or_else(|_| HeaderValue::from_static(""));
+        cookie.set_sensitive(true);
+
+        self.client
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("X-Csrf-Token", csrf_token)
+            .header(COOKIE, cookie)
+    }
+
+    /// Returns who the responses of the current session are cached for.
+    /// For internal use only.
+    fn cache_identity(&self) -> String {
+        self.read_session()
+            .user
+            .as_ref()
+            .map_or_else(|| "-".to_string(), |user| user.id.to_string())
+    }
+
+    /// Invalidates the cached responses of a resource, for writes whose path
+    /// doesn't name the resource they change.
+    /// For internal use only.
+    fn invalidate_cached(&self, path: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(path);
         }
     }
 
-    /// Create a new DiscuitClient with the given base URL.
-    pub fn new(base_url: &str) -> Self {
-        let base_url = base_url.trim_end_matches('/');
+    /// Decodes a response body, checking it for schema drift and passing the
+    /// decoded value to the hooks. Failures are recorded in the metrics.
+    /// For internal use only.
+    fn decode<T: DeserializeOwned + Serialize + 'static>(
+        &self,
+        text: &str,
+    ) -> Result<T, serde_json::Error> {
+        let value = self.schema_drift.decode(text).inspect_err(|_| {
+            if let Some(metrics) = &self.metrics {
+                metrics.record_decode_failure::<T>();
+            }
+        })?;
+        for hook in &self.hooks {
+            hook.decoded(&value);
+        }
+        Ok(value)
+    }
 
-        let client = ClientBuilder::new()
-            .user_agent("DiscuitClient")
-            .cookie_store(true)
-            .build()
-            .unwrap();
+    /// Decodes the body of a response, turning unsuccessful responses into `Error::Api`.
+    /// For internal use only.
+    async fn decode_response<T: DeserializeOwned + Serialize + 'static>(
+        &self,
+        response: Response,
+    ) -> Result<T, Error> {
+        let text = check_response(response).await?;
+        Ok(self.decode(&text)?)
+    }
 
-        Self {
-            client,
-            csrf_token: String::new(),
-            session_id: String::new(),
-            base_url: base_url.to_string(),
-            user_agent: "DiscuitClient".to_string(),
-            user: None,
-            log_level: LogLevel::Debug,
+    /// Sends a write, the one path all mutating calls take.
+    ///
+    /// In dry-run mode, the write is validated and logged instead, and the
+    /// result it would have returned is synthesized; see the `dry_run` module.
+    /// For internal use only.
+    async fn dispatch(&self, write: Write<'_>) -> Result<Dispatched, Error> {
+        if self.dry_run {
+            let user = write.validate(self.read_session().user.clone())?;
+            let result = dry_run::synthesize(self, &write, &user).await?;
+            info!(write = ?write, "Dry run, write not sent.");
+            return Ok(Dispatched::DryRun(result));
         }
+
+        let request = match write {
+            Write::CreatePost(post) => self.request(Method::POST, "/api/posts").json(post),
+            Write::DeletePost {
+                public_id,
+                delete_as,
+                delete_content,
+            } => self
+                .request(Method::DELETE, &format!("/api/posts/{}", public_id))
+                .query(&[
+                    ("deleteAs", delete_as.as_str()),
+                    ("deleteContent", &delete_content.to_string()),
+                ]),
+            Write::LockPost {
+                public_id,
+                lock_as,
+                locked,
+            } => self
+                .request(Method::PUT, &format!("/api/posts/{}", public_id))
+                .query(&[
+                    ("action", if locked { "lock" } else { "unlock" }),
+                    ("lockAs", lock_as.as_str()),
+                ]),
+            Write::PinPost {
+                public_id,
+                pinned,
+                site_wide,
+            } => self
+                .request(Method::PUT, &format!("/api/posts/{}", public_id))
+                .query(&[
+                    ("action", if pinned { "pin" } else { "unpin" }),
+                    ("siteWide", &site_wide.to_string()),
+                ]),
+            Write::VotePost { post_id, up, .. } => self
+                .request(Method::POST, "/api/_postVote")
+                .json(&PostVoteRequest {
+                    post_id: post_id.clone(),
+                    up,
+                }),
+            Write::CreateComment {
+                post_public_id,
+                parent_id,
+                body,
+            } => self
+                .request(
+                    Method::POST,
+                    &format!("/api/posts/{}/comments", post_public_id),
+                )
+                .json(&CreateCommentRequest {
+                    parent_comment_id: parent_id.cloned(),
+                    body: body.to_string(),
+                }),
+            Write::DeleteComment {
+                post_public_id,
+                comment_id,
+                delete_as,
+            } => self
+                .request(
+                    Method::DELETE,
+                    &format!("/api/posts/{}/comments/{}", post_public_id, comment_id),
+                )
+                .query(&[("deleteAs", delete_as.as_str())]),
+            Write::VoteComment { comment_id, up } => self
+                .request(Method::POST, "/api/_commentVote")
+                .json(&CommentVoteRequest {
+                    comment_id: comment_id.clone(),
+                    up,
+                }),
+            Write::MarkNotificationsSeen => self
+                .request(Method::POST, "/api/notifications")
+                .query(&[("action", "markAllAsSeen")]),
+        };
+        Ok(Dispatched::Sent(self.send(request).await?))
     }
 
-    /// Resets the client to its initial state.
-    pub fn reset(&mut self) {
-        self.log(LogLevel::Info, "Resetting client ...");
-        self.csrf_token = String::new();
-        self.session_id = String::new();
-        self.user = None;
-        self.log(LogLevel::Info, "Client reset.");
+    /// Dispatches a write and decodes its result.
+    /// For internal use only.
+    async fn write<T: DeserializeOwned + Serialize + 'static>(
+        &self,
+        write: Write<'_>,
+    ) -> Result<T, Error> {
+        match self.dispatch(write).await? {
+            Dispatched::Sent(response) => self.decode_response(response).await,
+            Dispatched::DryRun(result) => Ok(serde_json::from_value(result)?),
+        }
+    }
+
+    /// Sends a request through the middleware stack.
+    ///
+    /// Requests pass through the cache, if there is one, the logging, the
+    /// retries, the rate limiter and the metrics, if any, then through the
+    /// middleware added with `DiscuitClientBuilder::middleware`; see the
+    /// `middleware` module.
+    /// For internal use only.
+    async fn send(&self, builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+        self.send_with(builder, true).await
+    }
+
+    /// Sends a request through the middleware stack without the cache, for
+    /// requests that must reach the instance, e.g. to read its headers, and for
+    /// image downloads, which would crowd API responses out of the cache.
+    /// For internal use only.
+    async fn send_uncached(&self, builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+        self.send_with(builder, false).await
+    }
+
+    /// Sends a request through the middleware stack, through the cache only if
+    /// `cached` is true.
+    /// For internal use only.
+    async fn send_with(
+        &self,
+        builder: RequestBuilder,
+        cached: bool,
+    ) -> Result<Response, reqwest::Error> {
+        let request = builder.build()?;
+
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|_| cached)
+            .map(|cache| CacheLayer {
+                cache,
+                identity: self.cache_identity(),
+            });
+        let metrics = self.metrics.as_ref();
+        let retry = RetryLayer {
+            max_retries: self.max_retries,
+            metrics,
+        };
+        let rate_limit = RateLimitLayer {
+            rate_limiter: &self.rate_limiter,
+            metrics,
+        };
+        let metrics = metrics.map(|metrics| MetricsLayer { metrics });
+
+        let mut stack: Vec<&dyn Middleware> = Vec::with_capacity(5 + self.middleware.len());
+        if let Some(cache) = &cache {
+            stack.push(cache);
+        }
+        stack.extend([&LoggingLayer as &dyn Middleware, &retry, &rate_limit]);
+        if let Some(metrics) = &metrics {
+            stack.push(metrics);
+        }
+        stack.extend(self.middleware.iter().map(|middleware| &**middleware));
+
+        Next::new(&self.client, &stack).run(request).await
     }
 
     /// Initialize the client by fetching a CSRF token and a session ID.
     /// Returns `InitialResponse`.
-    pub async fn initialize(&mut self) -> Result<InitialResponse, reqwest::Error> {
-        self.log(LogLevel::Info, "Initializing client ...");
-        self.log(
-            LogLevel::Info,
-            &format!("GET {}/api/_initial", self.base_url),
-        );
+    pub async fn initialize(&self) -> Result<InitialResponse, Error> {
+        info!("Initializing client ...");
         let response = self
-            .client
-            .get(&format!("{}/api/_initial", self.base_url))
-            .send()
+            .send(self.request(Method::GET, "/api/_initial"))
             .await?;
 
-        let cookies = response.cookies();
-        for cookie in cookies {
+        for cookie in response.cookies() {
             if cookie.name() == "csrftoken" {
-                self.csrf_token = cookie.value().to_string();
-                self.log(LogLevel::Info, &format!("CSRF token: {}", self.csrf_token));
+                self.write_session().csrf_token = cookie.value().to_string();
+                debug!("Received CSRF token.");
             } else if cookie.name() == "SID" {
-                self.session_id = cookie.value().to_string();
-                self.log(LogLevel::Info, &format!("Session ID: {}", self.session_id));
+                self.write_session().session_id = cookie.value().to_string();
+                debug!("Received session ID.");
             }
         }
 
         let text = response.text().await?;
-        let initial_response: InitialResponse = serde_json::from_str(&text).unwrap();
-        self.log(
-            LogLevel::Debug,
-            &format!("Initial response: {:#?}", initial_response),
-        );
-        self.log(LogLevel::Info, "Client initialized.");
+        let initial_response: InitialResponse = self.decode(&text)?;
+        info!("Client initialized.");
         Ok(initial_response)
     }
 
+    /// Probe the instance for the features it supports, so unsupported ones
+    /// can be hidden instead of failing.
+    ///
+    /// This reads `/api/_initial` without changing the session, and if lists
+    /// can't be told apart from there, checks whether the lists endpoint of
+    /// the authenticated user exists.
+    pub async fn probe(&self) -> Result<InstanceCapabilities, Error> {
+        info!("Probing instance ...");
+        // A cached response wouldn't have the `Server` header, and could be
+        // from before the instance was updated.
+        let response = self
+            .send_uncached(self.request(Method::GET, "/api/_initial"))
+            .await?;
+
+        let server = response
+            .headers()
+            .get(SERVER)
+            .and_then(|server| server.to_str().ok())
+            .map(str::to_string);
+        let text = check_response(response).await?;
+        let initial: serde_json::Value = serde_json::from_str(&text)?;
+        let mut capabilities = InstanceCapabilities::from_initial(&self.base_url, &initial);
+        capabilities.server = server;
+
+        if capabilities.lists.is_none() {
+            let username = initial["user"]["username"]
+                .as_str()
+                .map(str::to_string)
+                .or_else(|| self.user().map(|user| user.username));
+            if let Some(username) = username {
+                let path = format!("/api/users/{}/lists", username);
+                capabilities.lists = Some(self.endpoint_exists(&path).await?);
+            }
+        }
+
+        info!("Instance probed.");
+        Ok(capabilities)
+    }
+
+    /// Returns whether the endpoint at the given path exists. A `404` or `405`
+    /// without an API error in its body means there is no such route.
+    /// For internal use only.
+    async fn endpoint_exists(&self, path: &str) -> Result<bool, Error> {
+        let response = self.send_uncached(self.request(Method::GET, path)).await?;
+        if !is_route_error(response.status()) {
+            return Ok(true);
+        }
+
+        let text = response.text().await?;
+        Ok(serde_json::from_str::<APIError>(&text).is_ok())
+    }
+
     /// Log in to the Discuit instance with the given username and password.
-    pub async fn login(
-        &mut self,
-        username: &str,
-        password: &str,
-    ) -> Result<UserResponse, reqwest::Error> {
-        self.log(LogLevel::Info, "Logging in ...");
-        self.log(
-            LogLevel::Info,
-            &format!("POST {}/api/_login", self.base_url),
-        );
+    pub async fn login(&self, username: &str, password: &str) -> Result<UserResponse, Error> {
+        info!(username, "Logging in ...");
         let response = self
-            .client
-            .post(&format!("{}/api/_login", self.base_url))
-            .header("X-Csrf-Token", &self.csrf_token)
-            .header(
-                "Cookie",
-                &format!("csrftoken={}; SID={}", self.csrf_token, self.session_id),
+            .send(
+                self.request(Method::POST, "/api/_login")
+                    .json(&LoginRequest {
+                        username: username.to_string(),
+                        password: password.to_string(),
+                    }),
             )
-            .json(&LoginRequest {
-                username: username.to_string(),
-                password: password.to_string(),
-            })
-            .send()
             .await?;
 
         let text = response.text().await?;
-        let login_response: UserResponse = serde_json::from_str(&text).unwrap();
-        self.log(
-            LogLevel::Debug,
-            &format!("Login response: {:#?}", login_response),
-        );
-        self.log(LogLevel::Info, "Logged in.");
-        self.user = match &login_response {
-            UserResponse::Error(_) => None,
-            UserResponse::User(user) => Some(user.clone()),
+        let login_response: UserResponse = self.decode(&text)?;
+        self.write_session().user = match &login_response {
+            UserResponse::Error(e) => {
+                warn!(status = e.status, message = %e.message, "Failed to log in.");
+                None
+            }
+            UserResponse::User(user) => {
+                info!("Logged in.");
+                Some(user.clone())
+            }
         };
 
         Ok(login_response)
@@ -148,115 +531,57 @@ impl DiscuitClient {
 
     /// Log out of the Discuit instance.
     /// Returns either `()` or a `reqwest::Error`.
-    pub async fn logout(&mut self) -> Result<(), reqwest::Error> {
-        self.log(LogLevel::Info, "Logging out ...");
-        if self.user.is_none() {
-            self.log(LogLevel::Info, "Not logged in.");
+    pub async fn logout(&self) -> Result<(), reqwest::Error> {
+        info!("Logging out ...");
+        if self.read_session().user.is_none() {
+            info!("Not logged in.");
             return Ok(());
         }
 
-        self.log(
-            LogLevel::Info,
-            &format!("POST {}/api/_login?action=logout", self.base_url),
-        );
-        let response = self
-            .client
-            .post(&format!("{}/api/_login?action=logout", self.base_url))
-            .header("X-Csrf-Token", &self.csrf_token)
-            .header(
-                "Cookie",
-                &format!("csrftoken={}; SID={}", self.csrf_token, self.session_id),
-            )
-            .send()
+        self.send(self.request(Method::POST, "/api/_login?action=logout"))
             .await?;
 
-        self.log(LogLevel::Info, "Logged out.");
-        self.log(
-            LogLevel::Debug,
-            &format!("Logout response: {:#?}", response),
-        );
+        info!("Logged out.");
         self.reset();
         Ok(())
     }
 
     /// Fetch the current user from the Discuit instance.
-    /// Returns either `User` or a `reqwest::Error`.
-    pub async fn get_user(&mut self) -> Result<User, reqwest::Error> {
-        self.log(LogLevel::Info, "Fetching user ...");
-        self.log(LogLevel::Info, &format!("GET {}/api/_user", self.base_url));
-        let response = self
-            .client
-            .get(&format!("{}/api/_user", self.base_url))
-            .header("X-Csrf-Token", &self.csrf_token)
-            .header(
-                "Cookie",
-                &format!("csrftoken={}; SID={}", self.csrf_token, self.session_id),
-            )
-            .send()
-            .await?;
+    /// Returns either `User` or an `Error`.
+    pub async fn get_user(&self) -> Result<User, Error> {
+        info!("Fetching user ...");
+        let response = self.send(self.request(Method::GET, "/api/_user")).await?;
 
         let text = response.text().await?;
-        let user: User = serde_json::from_str(&text).unwrap();
-        self.log(LogLevel::Debug, &format!("User: {:#?}", user));
-        self.log(LogLevel::Info, "User fetched.");
+        let user: User = self.decode(&text)?;
+        info!("User fetched.");
         Ok(user)
     }
 
     /// Fetch another user from the Discuit instance.
     /// Returns UserResponse
-    pub async fn get_user_by_username(
-        &mut self,
-        username: &str,
-    ) -> Result<UserResponse, reqwest::Error> {
-        self.log(LogLevel::Info, "Fetching user by username ...");
-        self.log(
-            LogLevel::Info,
-            &format!("GET {}/api/users/{}", self.base_url, username),
-        );
+    pub async fn get_user_by_username(&self, username: &str) -> Result<UserResponse, Error> {
+        info!(username, "Fetching user by username ...");
         let response = self
-            .client
-            .get(&format!("{}/api/users/{}", self.base_url, username))
-            .header("X-Csrf-Token", &self.csrf_token)
-            .header(
-                "Cookie",
-                &format!("csrftoken={}; SID={}", self.csrf_token, self.session_id),
-            )
-            .send()
+            .send(self.request(Method::GET, &format!("/api/users/{}", username)))
             .await?;
 
         let text = response.text().await?;
-        let user_response: UserResponse = serde_json::from_str(&text).unwrap();
-        self.log(
-            LogLevel::Debug,
-            &format!("User by username: {:#?}", user_response),
-        );
-        self.log(LogLevel::Info, "User by username fetched.");
+        let user_response: UserResponse = self.decode(&text)?;
+        info!("User by username fetched.");
         Ok(user_response)
     }
 
     /// Fetch a user's feed.
-    pub async fn get_feed(&mut self, username: &str) -> Result<FeedResponse, reqwest::Error> {
-        self.log(LogLevel::Info, "Fetching feed ...");
-        self.log(
-            LogLevel::Info,
-            &format!("GET {}/api/users/{}/feed", self.base_url, username),
-        );
+    pub async fn get_feed(&self, username: &str) -> Result<FeedResponse, Error> {
+        info!(username, "Fetching feed ...");
         let response = self
-            .client
-            .get(&format!("{}/api/users/{}/feed", self.base_url, username))
-            .header("X-Csrf-Token", &self.csrf_token)
-            .header(
-                "Cookie",
-                &format!("csrftoken={}; SID={}", self.csrf_token, self.session_id),
-            )
-            .send()
-            .await
-            .unwrap();
+            .send(self.request(Method::GET, &format!("/api/users/{}/feed", username)))
+            .await?;
 
-        let text = response.text().await.unwrap();
-        let feed: FeedResponse = serde_json::from_str(&text).unwrap();
-        self.log(LogLevel::Debug, &format!("Feed: {:#?}", feed));
-        self.log(LogLevel::Info, "Feed fetched.");
+        let text = response.text().await?;
+        let feed: FeedResponse = self.decode(&text)?;
+        info!("Feed fetched.");
 
         Ok(feed)
     }
@@ -270,7 +595,7 @@ impl DiscuitClient {
     /// # #[tokio::main]
     /// # async fn main() {
     /// let base_url = "https://discuit.net";
-    /// let mut client = DiscuitClient::new(base_url);
+    /// let client = DiscuitClient::new(base_url);
     ///
     /// // Fetch all posts
     /// let site_posts = client.get_posts(None, None).await.unwrap();
@@ -280,85 +605,765 @@ impl DiscuitClient {
     /// # }
     /// ```
     pub async fn get_posts(
-        &mut self,
+        &self,
         sort: Option<&str>,
         community: Option<&str>,
     ) -> Result<PostFeedResponse, Box<dyn std::error::Error>> {
-        self.log(LogLevel::Info, "Fetching posts ...");
+        Ok(self.get_posts_page(sort, community, None).await?)
+    }
 
-        let mut url =
-            Url::parse(&format!("{}/api/posts", self.base_url)).expect("Failed to parse base URL");
+    /// Fetch a page of community or sitewide posts.
+    /// Pass the `next` cursor of a previous response to fetch the next page.
+    pub async fn get_posts_page(
+        &self,
+        sort: Option<&str>,
+        community: Option<&str>,
+        next: Option<&str>,
+    ) -> Result<PostFeedResponse, Error> {
+        info!(sort, community, next, "Fetching posts ...");
 
         let mut query_params = HashMap::new();
-        if let Some(sort) = sort {
-            query_params.insert("sort", sort);
-        } else {
-            query_params.insert("sort", "hot");
-        }
+        query_params.insert("sort", sort.unwrap_or("hot"));
         if let Some(community) = community {
             query_params.insert("community", community);
         }
+        if let Some(next) = next {
+            query_params.insert("next", next);
+        }
 
-        url.query_pairs_mut().extend_pairs(query_params.into_iter());
+        let response = self
+            .send(self.request(Method::GET, "/api/posts").query(&query_params))
+            .await?;
 
-        self.log(LogLevel::Info, &format!("GET {}", url));
+        let posts = self.decode_response(response).await?;
+        info!("Posts fetched.");
+        Ok(posts)
+    }
 
+    /// Fetch a single post by its public ID, including its community.
+    pub async fn get_post(&self, public_id: &PublicPostId) -> Result<Post, Error> {
+        info!(%public_id, "Fetching post ...");
         let response = self
-            .client
-            .get(url)
-            .header("X-Csrf-Token", &self.csrf_token)
-            .header(
-                "Cookie",
-                &format!("csrftoken={}; SID={}", self.csrf_token, self.session_id),
+            .send(
+                self.request(Method::GET, &format!("/api/posts/{}", public_id))
+                    .query(&[("fetchCommunity", "true")]),
             )
-            .send()
             .await?;
 
-        if response.status().is_success() {
-            let text = response.text().await?;
-            let posts: PostFeedResponse = serde_json::from_str(&text).unwrap();
-            self.log(LogLevel::Debug, &format!("Posts: {:#?}", posts));
-            self.log(LogLevel::Info, "Posts fetched.");
-            Ok(posts)
+        let post = self.decode_response(response).await?;
+        info!("Post fetched.");
+        Ok(post)
+    }
+
+    /// Create a post.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use discuit_rs::client::*;
+    /// # use discuit_rs::structs::api_requests::CreatePostRequest;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), discuit_rs::error::Error> {
+    /// let client = DiscuitClient::new("https://discuit.net");
+    /// // ... initialize and log in ...
+    /// let post = client
+    ///     .create_post(&CreatePostRequest::text("general", "Hello", "Hello, world!"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_post(&self, post: &CreatePostRequest) -> Result<Post, Error> {
+        info!(community = %post.community, "Creating post ...");
+        let post: Post = self.write(Write::CreatePost(post)).await?;
+        info!(public_id = %post.public_id, "Post created.");
+        Ok(post)
+    }
+
+    /// Delete a post.
+    /// `delete_as` is the capacity in which the post is deleted. If `delete_content` is true, the body of the post and
+    /// all associated links or images are deleted as well.
+    pub async fn delete_post(
+        &self,
+        public_id: &PublicPostId,
+        delete_as: &UserGroup,
+        delete_content: bool,
+    ) -> Result<Post, Error> {
+        info!(%public_id, %delete_as, "Deleting post ...");
+        let post = self
+            .write(Write::DeletePost {
+                public_id,
+                delete_as,
+                delete_content,
+            })
+            .await?;
+        info!("Post deleted.");
+        Ok(post)
+    }
+
+    /// Lock or unlock a post.
+    /// `lock_as` is the capacity in which the post is locked, e.g. as a moderator.
+    pub async fn set_post_locked(
+        &self,
+        public_id: &PublicPostId,
+        lock_as: &UserGroup,
+        locked: bool,
+    ) -> Result<Post, Error> {
+        let action = if locked { "lock" } else { "unlock" };
+        info!(%public_id, %lock_as, action, "Updating post ...");
+        let post = self
+            .write(Write::LockPost {
+                public_id,
+                lock_as,
+                locked,
+            })
+            .await?;
+        info!("Post updated.");
+        Ok(post)
+    }
+
+    /// Pin or unpin a post in its community, or site-wide if `site_wide` is true.
+    pub async fn set_post_pinned(
+        &self,
+        public_id: &PublicPostId,
+        pinned: bool,
+        site_wide: bool,
+    ) -> Result<Post, Error> {
+        let action = if pinned { "pin" } else { "unpin" };
+        info!(%public_id, action, site_wide, "Updating post ...");
+        let post = self
+            .write(Write::PinPost {
+                public_id,
+                pinned,
+                site_wide,
+            })
+            .await?;
+        info!("Post updated.");
+        Ok(post)
+    }
+
+    /// Vote on a post, e.g. one returned by `get_post`.
+    pub async fn vote_post(&self, post: &Post, up: bool) -> Result<Post, Error> {
+        info!(post_id = %post.id, up, "Voting on post ...");
+        let voted: Post = self
+            .write(Write::VotePost {
+                post_id: &post.id,
+                public_id: &post.public_id,
+                up,
+            })
+            .await?;
+        // Dry runs change nothing, so the cached responses stay valid.
+        if !self.dry_run {
+            self.invalidate_cached(&format!("/api/posts/{}", post.public_id));
+        }
+        info!("Voted on post.");
+        Ok(voted)
+    }
+
+    /// Add a comment to a post. If `parent_id` is set, the comment is a reply
+    /// to the comment with that ID.
+    pub async fn create_comment(
+        &self,
+        post_public_id: &PublicPostId,
+        parent_id: Option<&CommentId>,
+        body: &str,
+    ) -> Result<Comment, Error> {
+        info!(%post_public_id, parent_id = ?parent_id.map(CommentId::as_str), "Creating comment ...");
+        let comment: Comment = self
+            .write(Write::CreateComment {
+                post_public_id,
+                parent_id,
+                body,
+            })
+            .await?;
+        info!(comment_id = %comment.id, "Comment created.");
+        Ok(comment)
+    }
+
+    /// Fetch a page of the comments of a post.
+    /// Pass the `next` cursor of a previous response to fetch the next page.
+    pub async fn get_post_comments(
+        &self,
+        post_public_id: &PublicPostId,
+        next: Option<&str>,
+    ) -> Result<CommentsResponse, Error> {
+        info!(%post_public_id, next, "Fetching comments ...");
+        let mut request = self.request(
+            Method::GET,
+            &format!("/api/posts/{}/comments", post_public_id),
+        );
+        if let Some(next) = next {
+            request = request.query(&[("next", next)]);
+        }
+        let response = self.send(request).await?;
+
+        let comments = self.decode_response(response).await?;
+        info!("Comments fetched.");
+        Ok(comments)
+    }
+
+    /// Fetch a comment by its ID.
+    pub async fn get_comment(&self, comment_id: &CommentId) -> Result<Comment, Error> {
+        info!(%comment_id, "Fetching comment ...");
+        let response = self
+            .send(self.request(Method::GET, &format!("/api/comments/{}", comment_id)))
+            .await?;
+
+        let comment = self.decode_response(response).await?;
+        info!("Comment fetched.");
+        Ok(comment)
+    }
+
+    /// Delete a comment.
+    /// `delete_as` is the capacity in which the comment is deleted.
+    pub async fn delete_comment(
+        &self,
+        post_public_id: &PublicPostId,
+        comment_id: &CommentId,
+        delete_as: &UserGroup,
+    ) -> Result<Comment, Error> {
+        info!(%post_public_id, %comment_id, %delete_as, "Deleting comment ...");
+        let comment = self
+            .write(Write::DeleteComment {
+                post_public_id,
+                comment_id,
+                delete_as,
+            })
+            .await?;
+        info!("Comment deleted.");
+        Ok(comment)
+    }
+
+    /// Vote on a comment.
+    pub async fn vote_comment(&self, comment_id: &CommentId, up: bool) -> Result<Comment, Error> {
+        info!(%comment_id, up, "Voting on comment ...");
+        let comment: Comment = self.write(Write::VoteComment { comment_id, up }).await?;
+        if !self.dry_run {
+            self.invalidate_cached(&format!("/api/posts/{}", comment.post_public_id));
+        }
+        info!("Voted on comment.");
+        Ok(comment)
+    }
+
+    /// Fetch a community by its name.
+    pub async fn get_community_by_name(&self, name: &str) -> Result<Community, Error> {
+        info!(name, "Fetching community ...");
+        let response = self
+            .send(
+                self.request(Method::GET, &format!("/api/communities/{}", name))
+                    .query(&[("byName", "true")]),
+            )
+            .await?;
+
+        let community = self.decode_response(response).await?;
+        info!("Community fetched.");
+        Ok(community)
+    }
+
+    /// Fetch the communities of the instance.
+    pub async fn get_communities(&self) -> Result<Vec<Community>, Error> {
+        info!("Fetching communities ...");
+        let response = self
+            .send(self.request(Method::GET, "/api/communities"))
+            .await?;
+
+        let communities: Option<Vec<Community>> = self.decode_response(response).await?;
+        info!("Communities fetched.");
+        Ok(communities.unwrap_or_default())
+    }
+
+    /// Search for posts whose title or body contains the words of the query,
+    /// optionally in one community with `Search::community`.
+    /// See the `search` module for how instances without search are handled.
+    pub fn search_posts(&self, query: &str) -> Search<Post> {
+        Search::new(self.clone(), query)
+    }
+
+    /// Search for communities whose name starts with the given prefix, e.g.
+    /// for autocompletion.
+    pub fn search_communities(&self, prefix: &str) -> Search<Community> {
+        Search::new(self.clone(), prefix)
+    }
+
+    /// Search for users matching the query.
+    pub fn search_users(&self, query: &str) -> Search<User> {
+        Search::new(self.clone(), query)
+    }
+
+    /// Fetch a page of results from `/api/search`.
+    /// Returns `None` if the instance has no search endpoint.
+    /// For internal use only.
+    pub(crate) async fn search_page<T: Searchable>(
+        &self,
+        query: &str,
+        community: Option<&str>,
+        next: Option<&str>,
+    ) -> Result<Option<SearchResponse<T>>, Error> {
+        info!(query, kind = T::KIND, community, next, "Searching ...");
+
+        let mut query_params = vec![("q", query), ("type", T::KIND)];
+        if let Some(community) = community {
+            query_params.push(("community", community));
+        }
+        if let Some(next) = next {
+            query_params.push(("next", next));
+        }
+
+        let response = self
+            .send(
+                self.request(Method::GET, "/api/search")
+                    .query(&query_params),
+            )
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if is_route_error(status) && serde_json::from_str::<APIError>(&text).is_err() {
+            debug!("The instance has no search endpoint.");
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(api_error(status, text));
+        }
+
+        let results = self.decode(&text)?;
+        info!("Search results fetched.");
+        Ok(Some(results))
+    }
+
+    /// Fetch a page of the reports of a community. Only available to moderators.
+    /// Pages start at 1.
+    pub async fn get_reports(
+        &self,
+        community_id: &CommunityId,
+        page: u32,
+    ) -> Result<ReportsResponse, Error> {
+        info!(%community_id, page, "Fetching reports ...");
+        let response = self
+            .send(
+                self.request(
+                    Method::GET,
+                    &format!("/api/communities/{}/reports", community_id),
+                )
+                .query(&[("page", page)]),
+            )
+            .await?;
+
+        let reports = self.decode_response(response).await?;
+        info!("Reports fetched.");
+        Ok(reports)
+    }
+
+    /// Download the contents of an image, e.g. the image of a post.
+    pub async fn download_image(&self, image: &Image) -> Result<Vec<u8>, Error> {
+        info!(image_id = %image.id, "Downloading image ...");
+        // Image URLs are usually relative to the instance, but may point elsewhere.
+        let request = if image.url.starts_with("http://") || image.url.starts_with("https://") {
+            self.client.get(&image.url)
         } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            self.log(
-                LogLevel::Info,
-                &format!("Failed to fetch posts: {} - {}", status, error_text),
-            );
-            Err(format!("Failed to fetch posts: {} - {}", status, error_text).into())
+            self.request(Method::GET, &image.url)
+        };
+        let response = self.send_uncached(request).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(api_error(status, response.text().await?));
+        }
+        let bytes = response.bytes().await?;
+        info!("Image downloaded.");
+        Ok(bytes.to_vec())
+    }
+
+    /// Fetch the notifications of the authenticated user.
+    /// Pass the `next` cursor of a previous response to fetch the next page.
+    pub async fn get_notifications(
+        &self,
+        next: Option<&str>,
+    ) -> Result<NotificationsResponse, Error> {
+        info!(next, "Fetching notifications ...");
+        let mut request = self.request(Method::GET, "/api/notifications");
+        if let Some(next) = next {
+            request = request.query(&[("next", next)]);
+        }
+        let response = self.send(request).await?;
+
+        let notifications = self.decode_response(response).await?;
+        info!("Notifications fetched.");
+        Ok(notifications)
+    }
+
+    /// Mark all notifications of the authenticated user as seen.
+    pub async fn mark_all_notifications_seen(&self) -> Result<(), Error> {
+        info!("Marking notifications as seen ...");
+        if let Dispatched::Sent(response) = self.dispatch(Write::MarkNotificationsSeen).await? {
+            check_response(response).await?;
         }
+        info!("Notifications marked as seen.");
+        Ok(())
+    }
+}
+
+/// Reads the body of a response, turning unsuccessful responses into `Error::Api`.
+/// For internal use only.
+async fn check_response(response: Response) -> Result<String, Error> {
+    let status = response.status();
+    let text = response.text().await?;
+    if status.is_success() {
+        return Ok(text);
     }
+
+    Err(api_error(status, text))
+}
+
+/// Returns true if the status is one a router answers with for a path or
+/// method it doesn't know; an API error in the body tells an endpoint's own
+/// `404` apart from a missing route.
+/// For internal use only.
+fn is_route_error(status: StatusCode) -> bool {
+    status == StatusCode::NOT_FOUND || status == StatusCode::METHOD_NOT_ALLOWED
+}
+
+/// Turns the body of an unsuccessful response into `Error::Api`, synthesizing
+/// an `APIError` if the body isn't one.
+/// For internal use only.
+fn api_error(status: StatusCode, text: String) -> Error {
+    let error = serde_json::from_str(&text).unwrap_or_else(|_| APIError {
+        status: status.as_u16() as i32,
+        code: None,
+        message: text,
+    });
+    warn!(status = error.status, code = ?error.code, message = %error.message, "API error");
+    Error::Api(error)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cache::MemoryCache;
+    use crate::mock_server::{MockResponse, MockServer};
+    use crate::schema::SchemaMode;
     use rand::random;
+    use std::time::Duration;
 
     fn get_env_var(name: &str) -> Result<String, String> {
         std::env::var(name).map_err(|_| format!("Environment variable {} is not set", name))
     }
 
+    #[test]
+    fn test_client_is_shareable() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<DiscuitClient>();
+    }
+
+    #[test]
+    fn test_clones_share_session() {
+        let client = DiscuitClient::new("https://discuit.net");
+        let clone = client.clone();
+
+        client.set_session(Session {
+            csrf_token: "token".to_string(),
+            session_id: "sid".to_string(),
+            user: None,
+        });
+        assert_eq!(clone.csrf_token(), "token");
+        assert_eq!(clone.cookie_header(), "csrftoken=token; SID=sid");
+
+        clone.reset();
+        assert!(client.session_id().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sends_session_headers() {
+        let server = MockServer::start(|_| MockResponse::json(200, "{}")).await;
+        let client = DiscuitClient::new(&server.url);
+        client.set_session(Session {
+            csrf_token: "token".to_string(),
+            session_id: "sid".to_string(),
+            user: None,
+        });
+
+        client
+            .send(client.request(Method::GET, "/api/_user"))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/api/_user");
+        assert_eq!(requests[0].header("X-Csrf-Token"), Some("token"));
+        assert_eq!(
+            requests[0].header("Cookie"),
+            Some("csrftoken=token; SID=sid")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_login_keeps_user_unset() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                401,
+                r#"{"status": 401, "code": "invalid_credentials", "message": "Invalid credentials."}"#,
+            )
+        })
+        .await;
+        let client = DiscuitClient::new(&server.url);
+
+        let response = client.login("user", "hunter2").await.unwrap();
+
+        assert!(matches!(response, UserResponse::Error(_)));
+        assert!(client.user().is_none());
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].body,
+            r#"{"username":"user","password":"hunter2"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_errors() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                404,
+                r#"{"status": 404, "code": "post_not_found", "message": "Post not found."}"#,
+            )
+        })
+        .await;
+        let client = DiscuitClient::new(&server.url);
+        let mut post = crate::fixtures::post("a", "general", "alice", "2024-06-01T10:00:00Z");
+        post["id"] = serde_json::json!("1234");
+
+        let error = client
+            .vote_post(&serde_json::from_value(post).unwrap(), true)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.api_error().and_then(|e| e.code.as_deref()),
+            Some("post_not_found")
+        );
+        assert_eq!(server.requests()[0].body, r#"{"postId":"1234","up":true}"#);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_responses_are_errors() {
+        let server = MockServer::start(|_| MockResponse::json(200, "<html>")).await;
+        let client = DiscuitClient::new(&server.url);
+
+        assert!(matches!(client.initialize().await, Err(Error::Decode(_))));
+        assert!(matches!(
+            client.login("user", "hunter2").await,
+            Err(Error::Decode(_))
+        ));
+        assert!(matches!(client.get_user().await, Err(Error::Decode(_))));
+        assert!(matches!(
+            client.get_user_by_username("user").await,
+            Err(Error::Decode(_))
+        ));
+        assert!(matches!(
+            client.get_feed("user").await,
+            Err(Error::Decode(_))
+        ));
+        assert!(client.user().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_requests() {
+        let server = MockServer::start(|_| MockResponse::json(200, "{}")).await;
+        let client = DiscuitClient::new(&server.url);
+
+        client.mark_all_notifications_seen().await.unwrap();
+        let error = client
+            .create_post(&CreatePostRequest::text(
+                "general",
+                "Hello",
+                "Hello, world!",
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Decode(_)));
+        client
+            .set_post_locked(&PublicPostId::from("a"), &UserGroup::Admins, true)
+            .await
+            .unwrap_err();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/notifications?action=markAllAsSeen");
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].path, "/api/posts");
+        assert_eq!(
+            requests[1].body,
+            r#"{"type":"text","title":"Hello","body":"Hello, world!","community":"general"}"#
+        );
+        assert_eq!(requests[2].method, "PUT");
+        assert_eq!(requests[2].path, "/api/posts/a?action=lock&lockAs=admins");
+    }
+
+    #[tokio::test]
+    async fn test_schema_drift() {
+        let mut comment: serde_json::Value =
+            serde_json::from_str(include_str!("structs/payloads/comment.json")).unwrap();
+        comment["reactions"] = serde_json::json!({"heart": 2});
+        let body = comment.to_string();
+        let server = MockServer::start(move |_| MockResponse::json(200, &body)).await;
+        let id = CommentId::from("17e2b9d0c4a8f6e1");
+
+        let client = DiscuitClient::builder(&server.url)
+            .schema_mode(SchemaMode::Capture)
+            .build()
+            .unwrap();
+        client.vote_comment(&id, true).await.unwrap();
+        let report = client.schema_drift.report();
+        assert_eq!(report.fields[0].type_name, "Comment");
+        assert_eq!(report.fields[0].path, "reactions");
+
+        let client = DiscuitClient::builder(&server.url)
+            .schema_mode(SchemaMode::Strict)
+            .build()
+            .unwrap();
+        let error = client.vote_comment(&id, true).await.unwrap_err();
+        assert!(matches!(error, Error::Decode(_)));
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/api/_initial" => {
+                MockResponse::json(200, include_str!("structs/payloads/initial.json"))
+                    .header("Server", "discuit")
+                    .header("Set-Cookie", "SID=probe; Path=/")
+            }
+            _ => MockResponse::json(404, "404 page not found"),
+        })
+        .await;
+        let client = DiscuitClient::new(&server.url);
+
+        let capabilities = client.probe().await.unwrap();
+
+        assert_eq!(capabilities.server.as_deref(), Some("discuit"));
+        assert!(capabilities.push_notifications);
+        assert_eq!(capabilities.lists, Some(false));
+        assert_eq!(server.requests()[1].path, "/api/users/previnder/lists");
+        // Probing doesn't start a session.
+        assert!(client.session_id().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_probe_bypasses_cache() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(200, include_str!("structs/payloads/initial.json"))
+                .header("Server", "discuit")
+        })
+        .await;
+        let client = DiscuitClient::builder(&server.url)
+            .cache(ResponseCache::new(MemoryCache::new(10)))
+            .build()
+            .unwrap();
+
+        client.initialize().await.unwrap();
+        let capabilities = client.probe().await.unwrap();
+
+        assert_eq!(capabilities.server.as_deref(), Some("discuit"));
+        assert_eq!(server.requests()[1].path, "/api/_initial");
+    }
+
+    #[tokio::test]
+    async fn test_download_image_bypasses_cache() {
+        let server = MockServer::start(|_| MockResponse::json(200, "image")).await;
+        let client = DiscuitClient::builder(&server.url)
+            .cache(ResponseCache::new(MemoryCache::new(10)))
+            .build()
+            .unwrap();
+        let image: Image = serde_json::from_value(crate::fixtures::image("a")).unwrap();
+
+        assert_eq!(client.download_image(&image).await.unwrap(), b"image");
+        assert_eq!(client.download_image(&image).await.unwrap(), b"image");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let post = include_str!("structs/payloads/post.json");
+        let server = MockServer::start(move |request| {
+            if request.header("If-None-Match") == Some("\"v1\"") {
+                MockResponse::json(304, "")
+            } else {
+                MockResponse::json(200, post).header("ETag", "\"v1\"")
+            }
+        })
+        .await;
+        let public_id = PublicPostId::from("Gq4XzLbA");
+
+        let client = DiscuitClient::builder(&server.url)
+            .cache(ResponseCache::new(MemoryCache::new(10)))
+            .build()
+            .unwrap();
+        client.get_post(&public_id).await.unwrap();
+        client.get_post(&public_id).await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+
+        // Voting drops the cached responses of the post.
+        let post: Post = serde_json::from_str(post).unwrap();
+        client.vote_post(&post, true).await.unwrap();
+        client.get_post(&public_id).await.unwrap();
+        assert_eq!(server.requests().len(), 3);
+
+        let client = DiscuitClient::builder(&server.url)
+            .cache(ResponseCache::new(MemoryCache::new(10)).default_ttl(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+        client.get_post(&public_id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let post = client.get_post(&public_id).await.unwrap();
+        assert_eq!(post.public_id, public_id);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[4].header("If-None-Match"), Some("\"v1\""));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_reads() {
+        let server = MockServer::start(|request| {
+            if request.method == "GET" {
+                MockResponse::json(503, "{}")
+            } else {
+                MockResponse::json(429, "{}").header("Retry-After", "1")
+            }
+        })
+        .await;
+        let client = DiscuitClient::builder(&server.url)
+            .max_retries(2)
+            .build()
+            .unwrap();
+
+        let response = client
+            .send(client.request(Method::GET, "/api/posts"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.requests().len(), 3);
+
+        let response = client
+            .send(client.request(Method::POST, "/api/_postVote"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(server.requests().len(), 4);
+    }
+
     #[tokio::test]
     async fn test_initialize() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         let response = client.initialize().await;
 
         println!("{:?}", response);
 
-        assert!(!client.csrf_token.is_empty());
-        assert!(!client.session_id.is_empty());
+        assert!(!client.csrf_token().is_empty());
+        assert!(!client.session_id().is_empty());
         assert!(response.is_ok());
     }
 
     #[tokio::test]
     async fn test_login() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         client.initialize().await.unwrap();
 
         let username =
@@ -369,14 +1374,14 @@ mod tests {
         client.logout().await.unwrap();
 
         // Ensure that the client is logged out
-        assert!(client.user.is_none());
-        assert!(client.csrf_token.is_empty());
-        assert!(client.session_id.is_empty());
+        assert!(client.user().is_none());
+        assert!(client.csrf_token().is_empty());
+        assert!(client.session_id().is_empty());
     }
 
     #[tokio::test]
     async fn test_get_user() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         client.initialize().await.unwrap();
 
         let username =
@@ -388,9 +1393,9 @@ mod tests {
         client.logout().await.unwrap();
 
         // Ensure that the client is logged out
-        assert!(client.user.is_none());
-        assert!(client.csrf_token.is_empty());
-        assert!(client.session_id.is_empty());
+        assert!(client.user().is_none());
+        assert!(client.csrf_token().is_empty());
+        assert!(client.session_id().is_empty());
 
         // Ensure that the user is fetched
         assert_eq!(user.username, username);
@@ -398,7 +1403,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_user_by_username() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         client.initialize().await.unwrap();
 
         let username =
@@ -415,7 +1420,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_user_by_username_nonexistent() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         client.initialize().await.unwrap();
 
         let username = random::<u64>().to_string();
@@ -438,7 +1443,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_feed() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         client.initialize().await.unwrap();
 
         let username =
@@ -462,7 +1467,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_feed_nonexistent() {
-        let mut client = DiscuitClient::new("https://discuit.net");
+        let client = DiscuitClient::new("https://discuit.net");
         client.initialize().await.unwrap();
 
         let username = random::<u64>().to_string();
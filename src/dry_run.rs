@@ -0,0 +1,670 @@
+//! This module contains the write dispatch of `DiscuitClient` and the
+//! results it synthesizes in dry-run mode.
+//!
+//! Every mutating call of the client (posting, commenting, voting, deleting,
+//! moderating and marking notifications seen) is described by a `Write` and sent
+//! through `DiscuitClient::dispatch`. With `DiscuitClientBuilder::dry_run`,
+//! writes are validated and logged but not sent, and the call returns a
+//! result synthesized from what the write would have done. Reads still go to
+//! the server, and writes other than creating posts read the post or comment
+//! they change to synthesize their result, so a dry run needs a logged-in
+//! session like a real one.
+//!
+//! Synthesized results are not passed to the decode hooks, and their IDs
+//! are `"dry-run"` unless the write names an existing entity.
+//! For internal use only.
+
+use crate::client::DiscuitClient;
+use crate::error::Error;
+use crate::structs::api_requests::CreatePostRequest;
+use crate::structs::api_responses::APIError;
+use crate::structs::api_types::*;
+use chrono::Utc;
+use serde_json::{json, Value};
+
+/// The ID of the entities created in dry-run mode.
+const DRY_RUN_ID: &str = "dry-run";
+
+/// `Write` is a mutating call of the client.
+/// For internal use only.
+#[derive(Debug)]
+pub(crate) enum Write<'a> {
+    CreatePost(&'a CreatePostRequest),
+    DeletePost {
+        public_id: &'a PublicPostId,
+        delete_as: &'a UserGroup,
+        delete_content: bool,
+    },
+    LockPost {
+        public_id: &'a PublicPostId,
+        lock_as: &'a UserGroup,
+        locked: bool,
+    },
+    PinPost {
+        public_id: &'a PublicPostId,
+        pinned: bool,
+        site_wide: bool,
+    },
+    VotePost {
+        post_id: &'a PostId,
+        public_id: &'a PublicPostId,
+        up: bool,
+    },
+    CreateComment {
+        post_public_id: &'a PublicPostId,
+        parent_id: Option<&'a CommentId>,
+        body: &'a str,
+    },
+    DeleteComment {
+        post_public_id: &'a PublicPostId,
+        comment_id: &'a CommentId,
+        delete_as: &'a UserGroup,
+    },
+    VoteComment {
+        comment_id: &'a CommentId,
+        up: bool,
+    },
+    MarkNotificationsSeen,
+}
+
+/// `Dispatched` is the outcome of a `Write`.
+/// For internal use only.
+pub(crate) enum Dispatched {
+    /// The write was sent and got this response.
+    Sent(reqwest::Response),
+    /// The write was not sent; this is the result it would have returned.
+    DryRun(Value),
+}
+
+impl Write<'_> {
+    /// Checks the write for what the server would reject without looking at
+    /// any other data, given the logged-in user, and returns that user.
+    pub(crate) fn validate(&self, user: Option<User>) -> Result<User, Error> {
+        let Some(user) = user else {
+            return Err(invalid(401, "Not logged in."));
+        };
+
+        let valid = match self {
+            Write::CreatePost(post) => {
+                let title = post.title.trim().chars().count();
+                if title <= 3 || title > 255 {
+                    return Err(invalid(400, "The title must be 4 to 255 characters."));
+                }
+                if post.community.trim().is_empty() {
+                    return Err(invalid(400, "The community is required."));
+                }
+                match post.post_type {
+                    PostType::Text if post.body.is_none() => {
+                        Err(invalid(400, "Text posts require a body."))
+                    }
+                    PostType::Link => match post.url.as_deref().map(reqwest::Url::parse) {
+                        Some(Ok(_)) => Ok(()),
+                        _ => Err(invalid(400, "Link posts require a valid URL.")),
+                    },
+                    PostType::Image if post.image_id.is_none() => {
+                        Err(invalid(400, "Image posts require an image."))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Write::DeletePost { delete_as, .. } | Write::DeleteComment { delete_as, .. }
+                if **delete_as == UserGroup::Admins && !user.is_admin =>
+            {
+                Err(invalid(403, "Only admins can delete as admins."))
+            }
+            Write::LockPost { lock_as, .. } if **lock_as == UserGroup::Admins && !user.is_admin => {
+                Err(invalid(403, "Only admins can lock as admins."))
+            }
+            Write::PinPost {
+                site_wide: true, ..
+            } if !user.is_admin => Err(invalid(403, "Only admins can pin posts site-wide.")),
+            Write::CreateComment { body, .. } if body.trim().is_empty() => {
+                Err(invalid(400, "The comment body is empty."))
+            }
+            _ => Ok(()),
+        };
+        valid.map(|_| user)
+    }
+}
+
+/// Returns the result a write would have returned, reading the post or
+/// comment it changes from the server where needed.
+pub(crate) async fn synthesize(
+    client: &DiscuitClient,
+    write: &Write<'_>,
+    user: &User,
+) -> Result<Value, Error> {
+    let now = Utc::now();
+    let value = match write {
+        Write::CreatePost(request) => {
+            let community = client.get_community_by_name(&request.community).await?;
+            let link = request.url.as_deref().map(|url| {
+                let hostname = reqwest::Url::parse(url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                    .unwrap_or_default();
+                json!({ "url": url, "hostname": hostname, "image": null })
+            });
+            let mut post = placeholder_post(&PostId::from(DRY_RUN_ID));
+            post["publicId"] = json!(DRY_RUN_ID);
+            post["type"] = json!(request.post_type);
+            post["title"] = json!(request.title.trim());
+            post["body"] = json!(request.body);
+            post["link"] = json!(link);
+            post["userId"] = json!(user.id);
+            post["username"] = json!(user.username);
+            post["user"] = json!(user);
+            post["communityId"] = json!(community.id);
+            post["communityName"] = json!(community.name);
+            post["userVoted"] = json!(true);
+            post["userUpvoted"] = json!(true);
+            if let Some(image) = &community.pro_pic {
+                post["communityProPic"] = json!(image);
+            }
+            if let Some(image) = &community.banner_image {
+                post["communityBannerImage"] = json!(image);
+            }
+            post["community"] = json!(community);
+            post
+        }
+        Write::DeletePost {
+            public_id,
+            delete_as,
+            delete_content,
+        } => {
+            let mut post = client.get_post(public_id).await?;
+            check_acts_as(&post.community, &post.user_id, user, delete_as)?;
+            post.deleted = true;
+            post.deleted_at = Some(now);
+            post.deleted_by = Some(user.id.clone());
+            post.deleted_as = Some((*delete_as).clone());
+            if *delete_content {
+                post.deleted_content = true;
+                post.deleted_content_as = Some((*delete_as).clone());
+                post.body = None;
+                post.image = None;
+                post.link = None;
+            }
+            json!(post)
+        }
+        Write::LockPost {
+            public_id,
+            lock_as,
+            locked,
+        } => {
+            let mut post = client.get_post(public_id).await?;
+            check_acts_as(&post.community, &post.user_id, user, lock_as)?;
+            post.locked = *locked;
+            if *locked {
+                post.locked_by = Some(user.id.clone());
+                post.locked_by_group = Some((*lock_as).clone());
+                post.locked_at = Some(now);
+            } else {
+                post.locked_by = None;
+                post.locked_by_group = None;
+                post.locked_at = None;
+            }
+            json!(post)
+        }
+        Write::PinPost {
+            public_id,
+            pinned,
+            site_wide,
+        } => {
+            let mut post = client.get_post(public_id).await?;
+            if *site_wide {
+                post.is_pinned_site = *pinned;
+            } else {
+                check_acts_as(&post.community, &post.user_id, user, &UserGroup::Mods)?;
+                post.is_pinned = *pinned;
+            }
+            json!(post)
+        }
+        Write::VotePost { public_id, up, .. } => {
+            let mut post = client.get_post(public_id).await?;
+            let (upvotes, downvotes) = vote(
+                post.upvotes,
+                post.downvotes,
+                post.user_voted
+                    .unwrap_or(false)
+                    .then_some(post.user_upvoted == Some(true)),
+                *up,
+            );
+            post.upvotes = upvotes;
+            post.downvotes = downvotes;
+            post.user_voted = Some(true);
+            post.user_upvoted = Some(*up);
+            json!(post)
+        }
+        Write::CreateComment {
+            post_public_id,
+            parent_id,
+            body,
+        } => {
+            let post = client.get_post(post_public_id).await?;
+            if post.locked {
+                return Err(invalid(403, "The post is locked."));
+            }
+            let mut comment = placeholder_comment(&CommentId::from(DRY_RUN_ID));
+            comment["postId"] = json!(post.id);
+            comment["postPublicId"] = json!(post.public_id);
+            comment["postTitle"] = json!(post.title);
+            comment["communityId"] = json!(post.community_id);
+            comment["communityName"] = json!(post.community_name);
+            comment["userId"] = json!(user.id);
+            comment["username"] = json!(user.username);
+            comment["author"] = json!(user);
+            comment["body"] = json!(body);
+            comment["upvotes"] = json!(1);
+            comment["userVoted"] = json!(true);
+            comment["userVotedUp"] = json!(true);
+            if let Some(parent_id) = parent_id {
+                comment["parentId"] = json!(parent_id);
+                comment["ancestors"] = json!([parent_id]);
+                comment["depth"] = json!(1);
+            }
+            comment
+        }
+        Write::DeleteComment {
+            post_public_id,
+            comment_id,
+            delete_as,
+        } => {
+            let post = client.get_post(post_public_id).await?;
+            let mut comment = client.get_comment(comment_id).await?;
+            let author = comment.user_id.clone().unwrap_or_else(|| UserId::from(""));
+            check_acts_as(&post.community, &author, user, delete_as)?;
+            comment.body = "[deleted]".to_string();
+            comment.deleted = true;
+            comment.deleted_at = Some(now);
+            comment.deleted_as = Some((*delete_as).clone());
+            json!(comment)
+        }
+        Write::VoteComment { comment_id, up } => {
+            let mut comment = client.get_comment(comment_id).await?;
+            let (upvotes, downvotes) = vote(
+                comment.upvotes,
+                comment.downvotes,
+                comment
+                    .user_voted
+                    .unwrap_or(false)
+                    .then_some(comment.user_voted_up == Some(true)),
+                *up,
+            );
+            comment.upvotes = upvotes;
+            comment.downvotes = downvotes;
+            comment.user_voted = Some(true);
+            comment.user_voted_up = Some(*up);
+            json!(comment)
+        }
+        Write::MarkNotificationsSeen => Value::Null,
+    };
+    Ok(value)
+}
+
+/// Fails unless the user may act as `group` on a post by `author` in the
+/// community.
+fn check_acts_as(
+    community: &Community,
+    author: &UserId,
+    user: &User,
+    group: &UserGroup,
+) -> Result<(), Error> {
+    match group {
+        UserGroup::Normal if *author != user.id => Err(invalid(403, "Not the author of the post.")),
+        UserGroup::Mods if !user.is_admin && community.user_mod != Some(true) => {
+            Err(invalid(403, "Not a moderator of the community."))
+        }
+        UserGroup::Admins | UserGroup::Unknown(_) if !user.is_admin => {
+            Err(invalid(403, "Not an admin."))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the upvotes and downvotes after a vote that replaces the earlier
+/// vote of the user, if any; `previous` is true if that was an upvote.
+fn vote(upvotes: i32, downvotes: i32, previous: Option<bool>, up: bool) -> (i32, i32) {
+    let (mut upvotes, mut downvotes) = match previous {
+        Some(true) => (upvotes - 1, downvotes),
+        Some(false) => (upvotes, downvotes - 1),
+        None => (upvotes, downvotes),
+    };
+    if up {
+        upvotes += 1;
+    } else {
+        downvotes += 1;
+    }
+    (upvotes.max(0), downvotes.max(0))
+}
+
+/// Returns the error the server would have returned for an invalid write.
+fn invalid(status: i32, message: &str) -> Error {
+    Error::Api(APIError {
+        status,
+        code: None,
+        message: format!("Dry run: {}", message),
+    })
+}
+
+/// Returns a post of which only the ID is known.
+fn placeholder_post(id: &PostId) -> Value {
+    let now = Utc::now();
+    let mut post = json!({
+        "id": id,
+        "type": "text",
+        "publicId": "",
+        "userId": "",
+        "username": "",
+        "userGhostId": null,
+        "userGroup": "normal",
+        "userDeleted": false,
+        "isPinned": false,
+        "isPinnedSite": false,
+        "communityId": "",
+        "communityName": "",
+        "communityProPic": placeholder_image(),
+        "communityBannerImage": placeholder_image(),
+        "title": "",
+        "body": null,
+        "image": null,
+        "link": null,
+        "locked": false,
+        "lockedBy": null,
+        "lockedByGroup": null,
+        "lockedAt": null,
+        "upvotes": 0,
+        "downvotes": 0,
+        "hotness": 0
+    });
+    // Split in two, as a single `json!` call exceeds the macro recursion limit.
+    let rest = json!({
+        "createdAt": now,
+        "editedAt": null,
+        "lastActivityAt": now,
+        "deleted": false,
+        "deletedAt": null,
+        "deletedBy": null,
+        "deletedAs": null,
+        "deletedContent": false,
+        "deletedContentAs": null,
+        "noComments": 0,
+        "comments": null,
+        "commentsNext": null,
+        "userVoted": null,
+        "userUpvoted": null,
+        "isAuthorMuted": false,
+        "isCommunityMuted": false,
+        "community": placeholder_community(),
+        "user": null
+    });
+    if let (Some(post), Value::Object(rest)) = (post.as_object_mut(), rest) {
+        post.extend(rest);
+    }
+    post
+}
+
+/// Returns a comment of which only the ID is known.
+fn placeholder_comment(id: &CommentId) -> Value {
+    json!({
+        "id": id,
+        "postId": "",
+        "postPublicId": "",
+        "communityId": "",
+        "communityName": "",
+        "userId": null,
+        "username": "",
+        "userGhostId": null,
+        "userGroup": "normal",
+        "userDeleted": false,
+        "parentId": null,
+        "depth": 0,
+        "noReplies": 0,
+        "noDirectReplies": 0,
+        "ancestors": null,
+        "body": "",
+        "upvotes": 0,
+        "downvotes": 0,
+        "createdAt": Utc::now(),
+        "editedAt": null,
+        "contentStripped": null,
+        "deleted": false,
+        "deletedAt": null,
+        "deletedAs": null,
+        "author": placeholder_user(),
+        "isAuthorMuted": null,
+        "userVoted": null,
+        "userVotedUp": null,
+        "postTitle": null,
+        "postDeleted": false,
+        "postDeletedAs": null
+    })
+}
+
+fn placeholder_user() -> Value {
+    json!({
+        "id": "",
+        "username": "",
+        "email": null,
+        "emailConfirmedAt": null,
+        "aboutMe": null,
+        "points": 0,
+        "isAdmin": false,
+        "proPic": null,
+        "badges": [],
+        "noPosts": 0,
+        "noComments": 0,
+        "createdAt": Utc::now(),
+        "deleted": false,
+        "deletedAt": null,
+        "upvoteNotificationsOff": false,
+        "replyNotificationsOff": false,
+        "homeFeed": "all",
+        "rememberFeedSort": false,
+        "embedsOff": false,
+        "hideUserProfilePictures": false,
+        "bannedAt": null,
+        "isBanned": false,
+        "notificationsNewCount": 0,
+        "moddingList": null
+    })
+}
+
+fn placeholder_community() -> Value {
+    json!({
+        "id": "",
+        "userId": "",
+        "name": "",
+        "nsfw": false,
+        "about": null,
+        "noMembers": 0,
+        "proPic": null,
+        "bannerImage": null,
+        "createdAt": Utc::now(),
+        "deletedAt": null,
+        "isDefault": null,
+        "userJoined": null,
+        "userMod": null,
+        "mods": null,
+        "rules": null,
+        "reportsDetails": null
+    })
+}
+
+fn placeholder_image() -> Value {
+    json!({
+        "id": "",
+        "format": "",
+        "mimetype": "",
+        "width": 0,
+        "height": 0,
+        "size": 0,
+        "averageColor": "",
+        "url": "",
+        "copies": []
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+    use crate::structs::internal_types::Session;
+
+    fn user(username: &str) -> User {
+        serde_json::from_value(fixtures::user(username)).unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let write = Write::CreateComment {
+            post_public_id: &PublicPostId::from("a"),
+            parent_id: None,
+            body: "Hello",
+        };
+        let Err(Error::Api(e)) = write.validate(None) else {
+            panic!("writes without a user are invalid");
+        };
+        assert_eq!(e.status, 401);
+        assert!(write.validate(Some(user("alice"))).is_ok());
+
+        let post = CreatePostRequest::text("general", "Hi", "Hello");
+        let Err(Error::Api(e)) = Write::CreatePost(&post).validate(Some(user("alice"))) else {
+            panic!("short titles are invalid");
+        };
+        assert_eq!(e.status, 400);
+        let post = CreatePostRequest::link("general", "A link", "not a url");
+        assert!(Write::CreatePost(&post)
+            .validate(Some(user("alice")))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let server = MockServer::start(|request| {
+            if request.path.starts_with("/api/comments/") {
+                let comment = fixtures::comment("c1", "a", "bob", "Hi", "2024-06-01T11:00:00Z");
+                return MockResponse::json(200, &comment.to_string());
+            }
+            let mut post = fixtures::post("a", "general", "bob", "2024-06-01T10:00:00Z");
+            post["userVoted"] = json!(true);
+            post["userUpvoted"] = json!(true);
+            MockResponse::json(200, &post.to_string())
+        })
+        .await;
+        let client = DiscuitClient::builder(&server.url)
+            .dry_run(true)
+            .build()
+            .unwrap();
+        client.set_session(Session {
+            csrf_token: "token".to_string(),
+            session_id: "session".to_string(),
+            user: Some(user("alice")),
+        });
+
+        // Votes are synthesized from the post as it is, replacing the upvote
+        // of the user.
+        let post = client.get_post(&PublicPostId::from("a")).await.unwrap();
+        let voted = client.vote_post(&post, false).await.unwrap();
+        assert_eq!(voted.public_id, post.public_id);
+        assert_eq!(voted.title, post.title);
+        assert_eq!(voted.upvotes, post.upvotes - 1);
+        assert_eq!(voted.downvotes, post.downvotes + 1);
+        assert_eq!(voted.user_upvoted, Some(false));
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| request.method == "GET"));
+
+        let comment = client
+            .vote_comment(&CommentId::from("c1"), true)
+            .await
+            .unwrap();
+        assert_eq!(comment.post_public_id, PublicPostId::from("a"));
+        assert_eq!(comment.body, "Hi");
+        assert_eq!((comment.upvotes, comment.user_voted_up), (4, Some(true)));
+
+        let comment = client
+            .create_comment(&PublicPostId::from("a"), None, "Hello")
+            .await
+            .unwrap();
+        assert_eq!(comment.post_public_id, PublicPostId::from("a"));
+        assert_eq!(comment.username, "alice");
+        assert_eq!(comment.body, "Hello");
+
+        // Alice neither moderates the community nor wrote the post.
+        let locked = client
+            .set_post_locked(&PublicPostId::from("a"), &UserGroup::Mods, true)
+            .await;
+        assert!(matches!(locked, Err(Error::Api(e)) if e.status == 403));
+        let deleted = client
+            .delete_post(&PublicPostId::from("a"), &UserGroup::Normal, false)
+            .await;
+        assert!(matches!(deleted, Err(Error::Api(e)) if e.status == 403));
+        let deleted = client
+            .delete_comment(
+                &PublicPostId::from("a"),
+                &CommentId::from("c1"),
+                &UserGroup::Normal,
+            )
+            .await;
+        assert!(matches!(deleted, Err(Error::Api(e)) if e.status == 403));
+
+        client.mark_all_notifications_seen().await.unwrap();
+
+        // Admins can lock any post as admins.
+        let mut admin = user("root");
+        admin.is_admin = true;
+        client.set_session(Session {
+            user: Some(admin),
+            ..client.session()
+        });
+        let post = client
+            .set_post_locked(&PublicPostId::from("a"), &UserGroup::Admins, true)
+            .await
+            .unwrap();
+        assert!(post.locked);
+        assert_eq!(post.locked_by_group, Some(UserGroup::Admins));
+        let comment = client
+            .delete_comment(
+                &PublicPostId::from("a"),
+                &CommentId::from("c1"),
+                &UserGroup::Admins,
+            )
+            .await
+            .unwrap();
+        assert!(comment.deleted);
+        assert_eq!(comment.deleted_as, Some(UserGroup::Admins));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 11);
+        assert!(requests.iter().all(|request| request.method == "GET"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_keeps_cache() {
+        use crate::cache::{MemoryCache, ResponseCache};
+
+        let server = MockServer::start(|_| {
+            let post = fixtures::post("a", "general", "bob", "2024-06-01T10:00:00Z");
+            MockResponse::json(200, &post.to_string())
+        })
+        .await;
+        let client = DiscuitClient::builder(&server.url)
+            .dry_run(true)
+            .cache(ResponseCache::new(MemoryCache::new(10)))
+            .build()
+            .unwrap();
+        client.set_session(Session {
+            csrf_token: "token".to_string(),
+            session_id: "session".to_string(),
+            user: Some(user("alice")),
+        });
+
+        let post = client.get_post(&PublicPostId::from("a")).await.unwrap();
+        client.vote_post(&post, true).await.unwrap();
+        client.get_post(&PublicPostId::from("a")).await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,63 @@
+//! This module contains the error type returned by `DiscuitClient` methods.
+
+use crate::structs::api_responses::APIError;
+use std::fmt;
+
+/// `Error` represents everything that can go wrong when calling the Discuit API.
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or the response could not be read.
+    Http(reqwest::Error),
+    /// The response body could not be decoded.
+    Decode(serde_json::Error),
+    /// The Discuit API returned an error.
+    Api(APIError),
+}
+
+impl Error {
+    /// Returns the API error, if the Discuit API returned one.
+    pub fn api_error(&self) -> Option<&APIError> {
+        match self {
+            Error::Api(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "HTTP error: {}", e),
+            Error::Decode(e) => write!(f, "Failed to decode response: {}", e),
+            Error::Api(e) => write!(f, "API error: {} - {}", e.status, e.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Api(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<APIError> for Error {
+    fn from(e: APIError) -> Self {
+        Error::Api(e)
+    }
+}
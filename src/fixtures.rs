@@ -0,0 +1,159 @@
+//! This module contains JSON fixtures of API objects used by tests. They are
+//! built from the payloads of the `structs/payloads` directory, with the fields
+//! tests care about replaced. For internal use only.
+
+use serde_json::{json, Value};
+
+/// Parses a payload of the `structs/payloads` directory.
+fn payload(json: &str) -> Value {
+    serde_json::from_str(json).expect("payload should parse")
+}
+
+/// Replaces the given fields of an object.
+fn with(mut value: Value, fields: Value) -> Value {
+    if let (Some(object), Value::Object(fields)) = (value.as_object_mut(), fields) {
+        object.extend(fields);
+    }
+    value
+}
+
+/// Returns a user object with the given username, based on the user payload.
+pub(crate) fn user(username: &str) -> Value {
+    with(
+        payload(include_str!("structs/payloads/user.json")),
+        json!({
+            "id": format!("user-{}", username),
+            "username": username,
+            "aboutMe": null,
+            "isAdmin": false,
+            "proPic": null,
+            "badges": []
+        }),
+    )
+}
+
+/// Returns an image object with the given ID, based on the profile picture of
+/// the user payload.
+pub(crate) fn image(id: &str) -> Value {
+    let user = payload(include_str!("structs/payloads/user.json"));
+    with(
+        user["proPic"].clone(),
+        json!({
+            "id": id,
+            "size": 1024,
+            "url": format!("/images/{}.jpeg", id)
+        }),
+    )
+}
+
+/// Returns a community object with the given name, based on the community
+/// payload.
+pub(crate) fn community(name: &str) -> Value {
+    with(
+        payload(include_str!("structs/payloads/community.json")),
+        json!({
+            "id": format!("community-{}", name),
+            "name": name
+        }),
+    )
+}
+
+/// Returns a text post object without comments, based on the post payload.
+pub(crate) fn post(
+    public_id: &str,
+    community_name: &str,
+    username: &str,
+    created_at: &str,
+) -> Value {
+    let post = with(
+        payload(include_str!("structs/payloads/post.json")),
+        json!({
+            "id": format!("post-{}", public_id),
+            "type": "text",
+            "publicId": public_id,
+            "userId": format!("user-{}", username),
+            "username": username,
+            "userGroup": "normal",
+            "isPinnedSite": false,
+            "communityId": format!("community-{}", community_name),
+            "communityName": community_name,
+            "title": format!("Post {}", public_id),
+            "body": "Hello, world!",
+            "link": null,
+            "locked": false,
+            "lockedBy": null,
+            "lockedByGroup": null,
+            "lockedAt": null,
+            "upvotes": 1,
+            "downvotes": 0
+        }),
+    );
+    // Split in two, as a single `json!` call exceeds the macro recursion limit.
+    with(
+        post,
+        json!({
+            "createdAt": created_at,
+            "editedAt": null,
+            "lastActivityAt": created_at,
+            "noComments": 0,
+            "comments": null,
+            "userVoted": null,
+            "userUpvoted": null,
+            "community": community(community_name),
+            "user": null
+        }),
+    )
+}
+
+/// Returns a top-level comment object on the post with the given public ID,
+/// based on the comment payload.
+pub(crate) fn comment(
+    id: &str,
+    post_public_id: &str,
+    username: &str,
+    body: &str,
+    created_at: &str,
+) -> Value {
+    with(
+        payload(include_str!("structs/payloads/comment.json")),
+        json!({
+            "id": id,
+            "postId": format!("post-{}", post_public_id),
+            "postPublicId": post_public_id,
+            "communityId": "community-general",
+            "communityName": "general",
+            "userId": format!("user-{}", username),
+            "username": username,
+            "parentId": null,
+            "depth": 0,
+            "ancestors": null,
+            "body": body,
+            "createdAt": created_at,
+            "editedAt": null,
+            "author": user(username),
+            "postTitle": null
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::api_types::{Comment, Community, Post, User};
+
+    #[test]
+    fn test_fixtures_decode() {
+        serde_json::from_value::<User>(user("alice")).unwrap();
+        serde_json::from_value::<Community>(community("general")).unwrap();
+        serde_json::from_value::<Post>(post("a", "general", "alice", "2024-06-01T10:00:00Z"))
+            .unwrap();
+        serde_json::from_value::<Comment>(comment(
+            "c1",
+            "a",
+            "alice",
+            "Hello",
+            "2024-06-01T10:00:00Z",
+        ))
+        .unwrap();
+    }
+}
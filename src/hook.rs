@@ -0,0 +1,45 @@
+//! This module contains `DecodeHook`, which is called with every response the
+//! client decodes.
+//!
+//! Hooks see the decoded values before they are returned to the caller, e.g.
+//! to persist the posts and comments an app fetches without going through
+//! every call site. Values are passed as `&dyn Any`; use `downcast_ref` to
+//! get at the types a hook is interested in.
+//!
+//! # Example
+//!
+//! ```
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::hook::DecodeHook;
+//! use discuit_rs::structs::api_responses::PostFeedResponse;
+//! use std::any::Any;
+//!
+//! #[derive(Debug)]
+//! struct CountPosts;
+//!
+//! impl DecodeHook for CountPosts {
+//!     fn decoded(&self, value: &dyn Any) {
+//!         if let Some(page) = value.downcast_ref::<PostFeedResponse>() {
+//!             println!("fetched {} posts", page.posts.len());
+//!         }
+//!     }
+//! }
+//!
+//! let client = DiscuitClient::builder("https://discuit.net")
+//!     .hook(CountPosts)
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use std::any::Any;
+use std::fmt;
+
+/// `DecodeHook` is called with every response body the client decodes
+/// successfully, e.g. a `Post`, a `PostFeedResponse` or a `UserResponse`.
+///
+/// Hooks are called synchronously from within the request, so they should be
+/// quick; errors can't be returned to the caller and should be logged instead.
+pub trait DecodeHook: Send + Sync + fmt::Debug {
+    /// Called with a decoded value.
+    fn decoded(&self, value: &dyn Any);
+}
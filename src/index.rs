@@ -0,0 +1,745 @@
+//! This module contains `LocalIndex`, a full-text index of posts and comments
+//! stored on disk. Requires the `index` feature.
+//!
+//! Posts and comments are added to the index as they're fetched, e.g. from
+//! the pages of `DiscuitClient::get_posts_page` or by an `Archiver`, and can
+//! then be searched offline. A `LocalIndex` is also a `DecodeHook`, so adding
+//! it to a client with `DiscuitClientBuilder::hook` indexes every post and
+//! comment the client fetches, committing them in batches; see
+//! `LocalIndex::commit_every`. Queries are keywords, which all have to match,
+//! and `"quoted phrases"`, filtered by community, author, date and score.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::index::{IndexQuery, Indexed, LocalIndex};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = DiscuitClient::new("https://discuit.net");
+//! let index = LocalIndex::open("index")?;
+//!
+//! let page = client.get_posts_page(Some("new"), Some("general"), None).await?;
+//! index.add_posts(&page.posts)?;
+//! index.commit()?;
+//!
+//! let query = IndexQuery::new("\"dark mode\" bug").community("general");
+//! for hit in index.search(&query)? {
+//!     if let Indexed::Post(post) = hit.item {
+//!         println!("{:.2} {}", hit.score, post.title);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::hook::DecodeHook;
+use crate::structs::api_responses::*;
+use crate::structs::api_types::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tantivy::collector::TopDocs;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    DateOptions, DateTimePrecision, Field, IndexRecordOption, Schema, Value, INDEXED, STORED,
+    STRING, TEXT,
+};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use tracing::{debug, info, warn};
+
+/// The memory the index writer may use before flushing to disk.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// The number of hits returned by a query when no limit is configured.
+const DEFAULT_LIMIT: usize = 20;
+
+/// The number of items added by `add_response` after which they're committed,
+/// unless configured otherwise.
+const DEFAULT_COMMIT_ITEMS: usize = 1000;
+
+/// The time after a commit after which `add_response` commits again, unless
+/// configured otherwise.
+const DEFAULT_COMMIT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// `IndexError` represents everything that can go wrong with a `LocalIndex`.
+#[derive(Debug)]
+pub enum IndexError {
+    /// Reading or writing the index failed.
+    Index(tantivy::TantivyError),
+    /// The query couldn't be parsed.
+    Query(tantivy::query::QueryParserError),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Index(e) => write!(f, "Index error: {}", e),
+            IndexError::Query(e) => write!(f, "Invalid query: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IndexError::Index(e) => Some(e),
+            IndexError::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<tantivy::TantivyError> for IndexError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        IndexError::Index(e)
+    }
+}
+
+impl From<tantivy::query::QueryParserError> for IndexError {
+    fn from(e: tantivy::query::QueryParserError) -> Self {
+        IndexError::Query(e)
+    }
+}
+
+impl From<serde_json::Error> for IndexError {
+    fn from(e: serde_json::Error) -> Self {
+        IndexError::Index(e.into())
+    }
+}
+
+impl From<std::io::Error> for IndexError {
+    fn from(e: std::io::Error) -> Self {
+        IndexError::Index(e.into())
+    }
+}
+
+/// `Indexed` is a post or comment stored in the index.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "data", rename_all = "lowercase")]
+#[allow(clippy::large_enum_variant)]
+pub enum Indexed {
+    /// A post.
+    Post(Post),
+    /// A comment.
+    Comment(Comment),
+}
+
+/// `IndexedKind` restricts a query to posts or comments.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum IndexedKind {
+    /// Only posts.
+    Post,
+    /// Only comments.
+    Comment,
+}
+
+impl IndexedKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            IndexedKind::Post => "post",
+            IndexedKind::Comment => "comment",
+        }
+    }
+}
+
+/// `IndexHit` is a result of a query, with its relevance.
+#[derive(Clone, PartialEq, Debug)]
+pub struct IndexHit {
+    /// How well the item matches the keywords of the query; higher is better.
+    pub score: f32,
+    /// The matching post or comment, as it was added to the index.
+    pub item: Indexed,
+}
+
+/// `IndexQuery` is a query of a `LocalIndex`.
+#[derive(Clone, Debug)]
+pub struct IndexQuery {
+    text: String,
+    community: Option<String>,
+    author: Option<String>,
+    kind: Option<IndexedKind>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    min_score: Option<i64>,
+    limit: usize,
+}
+
+impl IndexQuery {
+    /// Create a new query matching the given text against the titles and
+    /// bodies. All keywords have to match, `"quoted phrases"` have to match in
+    /// order, and an empty text matches everything.
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            community: None,
+            author: None,
+            kind: None,
+            since: None,
+            until: None,
+            min_score: None,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    /// Only match posts and comments in the community with the given name.
+    pub fn community(mut self, name: &str) -> Self {
+        self.community = Some(name.to_lowercase());
+        self
+    }
+
+    /// Only match posts and comments by the user with the given username.
+    pub fn author(mut self, username: &str) -> Self {
+        self.author = Some(username.to_lowercase());
+        self
+    }
+
+    /// Only match posts or only match comments.
+    pub fn kind(mut self, kind: IndexedKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Only match posts and comments created at or after the given time.
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only match posts and comments created before the given time.
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Only match posts and comments with at least the given score, i.e.
+    /// upvotes minus downvotes.
+    pub fn min_score(mut self, min_score: i64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Set the maximum number of hits. Defaults to 20.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+}
+
+/// The fields of the index.
+#[derive(Clone, Copy, Debug)]
+struct Fields {
+    /// `post:{id}` or `comment:{id}`, replacing earlier versions of an item.
+    key: Field,
+    kind: Field,
+    community: Field,
+    author: Field,
+    title: Field,
+    body: Field,
+    created_at: Field,
+    score: Field,
+    /// The item as JSON, returned by queries.
+    document: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Fields) {
+        let mut builder = Schema::builder();
+        let date = DateOptions::default()
+            .set_indexed()
+            .set_fast()
+            .set_precision(DateTimePrecision::Seconds);
+        let fields = Fields {
+            key: builder.add_text_field("key", STRING),
+            kind: builder.add_text_field("kind", STRING),
+            community: builder.add_text_field("community", STRING),
+            author: builder.add_text_field("author", STRING),
+            title: builder.add_text_field("title", TEXT),
+            body: builder.add_text_field("body", TEXT),
+            created_at: builder.add_date_field("created_at", date),
+            score: builder.add_i64_field("score", INDEXED),
+            document: builder.add_text_field("document", STORED),
+        };
+        (builder.build(), fields)
+    }
+}
+
+/// `LocalIndex` is a full-text index of posts and comments in a directory.
+///
+/// Added items become searchable, and are persisted, once `commit` is called.
+/// Adding an item that is already in the index replaces it, so items can be
+/// added again whenever they're refetched.
+///
+/// Cloning the index is cheap; clones share the same writer and reader.
+#[derive(Clone)]
+pub struct LocalIndex {
+    dir: PathBuf,
+    index: Index,
+    reader: IndexReader,
+    writer: Arc<Mutex<Writer>>,
+    fields: Fields,
+    commit_items: usize,
+    commit_interval: Duration,
+}
+
+/// The writer of an index and what was added since the last commit.
+struct Writer {
+    writer: IndexWriter,
+    /// The number of items added since the last commit.
+    uncommitted: usize,
+    committed_at: Instant,
+}
+
+impl fmt::Debug for LocalIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalIndex")
+            .field("dir", &self.dir)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LocalIndex {
+    /// Open the index in the given directory, creating it if it doesn't exist.
+    /// Only one `LocalIndex` can have a directory open at a time.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, IndexError> {
+        let dir = dir.into();
+        let (schema, fields) = Fields::schema();
+        std::fs::create_dir_all(&dir)?;
+        let index = if dir.join("meta.json").exists() {
+            Index::open_in_dir(&dir)?
+        } else {
+            info!(dir = %dir.display(), "Creating index ...");
+            Index::create_in_dir(&dir, schema)?
+        };
+
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let writer = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+        Ok(Self {
+            dir,
+            index,
+            reader,
+            writer: Arc::new(Mutex::new(Writer {
+                writer,
+                uncommitted: 0,
+                committed_at: Instant::now(),
+            })),
+            fields,
+            commit_items: DEFAULT_COMMIT_ITEMS,
+            commit_interval: DEFAULT_COMMIT_INTERVAL,
+        })
+    }
+
+    /// Set when `add_response`, and so the index as a `DecodeHook`, commits:
+    /// once the given number of items were added since the last commit, or
+    /// once the given time has passed since it. Defaults to 1000 items or 30
+    /// seconds.
+    ///
+    /// Items added after the last batch are only committed with the next
+    /// response, so call `commit` before the program exits.
+    pub fn commit_every(mut self, items: usize, interval: Duration) -> Self {
+        self.commit_items = items.max(1);
+        self.commit_interval = interval;
+        self
+    }
+
+    /// Returns the directory of the index.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Add a post, replacing an earlier version of it.
+    pub fn add_post(&self, post: &Post) -> Result<(), IndexError> {
+        let fields = &self.fields;
+        let key = format!("post:{}", post.id);
+        let document = serde_json::to_string(&Indexed::Post(post.clone()))?;
+        self.replace(
+            &key,
+            doc!(
+                fields.key => key.as_str(),
+                fields.kind => IndexedKind::Post.as_str(),
+                fields.community => post.community_name.to_lowercase(),
+                fields.author => post.username.to_lowercase(),
+                fields.title => post.title.as_str(),
+                fields.body => post.body.as_deref().unwrap_or_default(),
+                fields.created_at => date(post.created_at),
+                fields.score => i64::from(post.upvotes) - i64::from(post.downvotes),
+                fields.document => document,
+            ),
+        )
+    }
+
+    /// Add a comment, replacing an earlier version of it.
+    pub fn add_comment(&self, comment: &Comment) -> Result<(), IndexError> {
+        let fields = &self.fields;
+        let key = format!("comment:{}", comment.id);
+        let document = serde_json::to_string(&Indexed::Comment(comment.clone()))?;
+        self.replace(
+            &key,
+            doc!(
+                fields.key => key.as_str(),
+                fields.kind => IndexedKind::Comment.as_str(),
+                fields.community => comment.community_name.to_lowercase(),
+                fields.author => comment.username.to_lowercase(),
+                fields.body => comment.body.as_str(),
+                fields.created_at => date(comment.created_at),
+                fields.score => i64::from(comment.upvotes) - i64::from(comment.downvotes),
+                fields.document => document,
+            ),
+        )
+    }
+
+    /// Add several posts, e.g. a page of `get_posts_page`.
+    pub fn add_posts<'a>(
+        &self,
+        posts: impl IntoIterator<Item = &'a Post>,
+    ) -> Result<(), IndexError> {
+        posts.into_iter().try_for_each(|post| self.add_post(post))
+    }
+
+    /// Add several comments, e.g. a page of `get_post_comments`.
+    pub fn add_comments<'a>(
+        &self,
+        comments: impl IntoIterator<Item = &'a Comment>,
+    ) -> Result<(), IndexError> {
+        comments
+            .into_iter()
+            .try_for_each(|comment| self.add_comment(comment))
+    }
+
+    /// Add the posts and comments of a response the client decoded, e.g. a
+    /// `PostFeedResponse` or a `CommentsResponse`, committing them once a
+    /// batch is due; see `commit_every`.
+    /// Returns false if the response has no posts or comments.
+    pub fn add_response(&self, value: &dyn Any) -> Result<bool, IndexError> {
+        let mut posts: Vec<&Post> = Vec::new();
+        let mut comments: Vec<&Comment> = Vec::new();
+        if let Some(post) = value.downcast_ref::<Post>() {
+            posts.push(post);
+        } else if let Some(page) = value.downcast_ref::<PostFeedResponse>() {
+            posts.extend(&page.posts);
+        } else if let Some(comment) = value.downcast_ref::<Comment>() {
+            comments.push(comment);
+        } else if let Some(page) = value.downcast_ref::<CommentsResponse>() {
+            comments.extend(page.comments.iter().flatten());
+        } else if let Some(FeedResponse::Feed { feed, .. }) = value.downcast_ref::<FeedResponse>() {
+            for item in feed {
+                match item {
+                    FeedItem::Post(post) => posts.push(post),
+                    FeedItem::Comment(comment) => comments.push(comment),
+                }
+            }
+        } else if let Some(page) = value.downcast_ref::<SearchResponse<Post>>() {
+            posts.extend(page.results.iter().flatten());
+        }
+        // Posts fetched on their own come with their comments.
+        comments.extend(posts.iter().flat_map(|post| post.comments.iter().flatten()));
+        if posts.is_empty() && comments.is_empty() {
+            return Ok(false);
+        }
+
+        self.add_posts(posts)?;
+        self.add_comments(comments)?;
+        let due = {
+            let writer = self.lock();
+            writer.uncommitted >= self.commit_items
+                || writer.committed_at.elapsed() >= self.commit_interval
+        };
+        if due {
+            self.commit()?;
+        }
+        Ok(true)
+    }
+
+    /// Locks the writer.
+    /// For internal use only.
+    fn lock(&self) -> MutexGuard<'_, Writer> {
+        // A panic while adding a document leaves at most that document
+        // half-written, and it's discarded with the rest of an unsuccessful
+        // commit, so the writer is still usable.
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Deletes the document with the given key and adds its new version.
+    /// For internal use only.
+    fn replace(&self, key: &str, document: TantivyDocument) -> Result<(), IndexError> {
+        let mut writer = self.lock();
+        writer
+            .writer
+            .delete_term(Term::from_field_text(self.fields.key, key));
+        writer.writer.add_document(document)?;
+        writer.uncommitted += 1;
+        Ok(())
+    }
+
+    /// Persist the items added since the last commit and make them searchable.
+    pub fn commit(&self) -> Result<(), IndexError> {
+        {
+            let mut writer = self.lock();
+            writer.writer.commit()?;
+            writer.uncommitted = 0;
+            writer.committed_at = Instant::now();
+        }
+        self.reader.reload()?;
+        debug!(documents = self.len(), "Index committed.");
+        Ok(())
+    }
+
+    /// Returns the number of committed posts and comments.
+    pub fn len(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Returns true if no posts or comments were committed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Search the committed posts and comments, best matches first.
+    pub fn search(&self, query: &IndexQuery) -> Result<Vec<IndexHit>, IndexError> {
+        let fields = &self.fields;
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+        if query.text.trim().is_empty() {
+            clauses.push((Occur::Must, Box::new(AllQuery)));
+        } else {
+            let mut parser = QueryParser::for_index(&self.index, vec![fields.title, fields.body]);
+            parser.set_conjunction_by_default();
+            clauses.push((Occur::Must, parser.parse_query(&query.text)?));
+        }
+
+        let terms = [
+            (fields.community, query.community.as_deref()),
+            (fields.author, query.author.as_deref()),
+            (fields.kind, query.kind.as_ref().map(IndexedKind::as_str)),
+        ];
+        for (field, value) in terms {
+            if let Some(value) = value {
+                let term = Term::from_field_text(field, value);
+                let term_query = TermQuery::new(term, IndexRecordOption::Basic);
+                clauses.push((Occur::Must, Box::new(term_query)));
+            }
+        }
+
+        if query.since.is_some() || query.until.is_some() {
+            let bound = |time: Option<DateTime<Utc>>| {
+                time.map(|time| Term::from_field_date_for_search(fields.created_at, date(time)))
+            };
+            let since = bound(query.since).map_or(Bound::Unbounded, Bound::Included);
+            let until = bound(query.until).map_or(Bound::Unbounded, Bound::Excluded);
+            clauses.push((Occur::Must, Box::new(RangeQuery::new(since, until))));
+        }
+        if let Some(min_score) = query.min_score {
+            let min_score = Term::from_field_i64(fields.score, min_score);
+            let range = RangeQuery::new(Bound::Included(min_score), Bound::Unbounded);
+            clauses.push((Occur::Must, Box::new(range)));
+        }
+
+        let searcher = self.reader.searcher();
+        let top_docs = searcher.search(
+            &BooleanQuery::new(clauses),
+            &TopDocs::with_limit(query.limit),
+        )?;
+        top_docs
+            .into_iter()
+            .map(|(score, address)| {
+                let document: TantivyDocument = searcher.doc(address)?;
+                let json = document
+                    .get_first(fields.document)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default();
+                Ok(IndexHit {
+                    score,
+                    item: serde_json::from_str(json)?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl DecodeHook for LocalIndex {
+    fn decoded(&self, value: &dyn Any) {
+        match self.add_response(value) {
+            Ok(true) => debug!("Response indexed."),
+            Ok(false) => {}
+            Err(e) => warn!(error = %e, "Failed to index response"),
+        }
+    }
+}
+
+/// Converts a timestamp to the date type of the index.
+/// For internal use only.
+fn date(time: DateTime<Utc>) -> tantivy::DateTime {
+    tantivy::DateTime::from_timestamp_secs(time.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use serde_json::json;
+
+    fn post(public_id: &str, community: &str, title: &str, created_at: &str, upvotes: i32) -> Post {
+        let mut post = fixtures::post(public_id, community, "alice", created_at);
+        post["title"] = json!(title);
+        post["upvotes"] = json!(upvotes);
+        serde_json::from_value(post).unwrap()
+    }
+
+    fn ids(hits: Vec<IndexHit>) -> Vec<String> {
+        hits.into_iter()
+            .map(|hit| match hit.item {
+                Indexed::Post(post) => post.public_id.to_string(),
+                Indexed::Comment(comment) => comment.id.to_string(),
+            })
+            .collect()
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("discuit-index-{}", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn test_queries_and_filters() {
+        let dir = temp_dir();
+        let index = LocalIndex::open(&dir).unwrap();
+        index
+            .add_posts(&[
+                post(
+                    "a",
+                    "general",
+                    "Dark mode is broken",
+                    "2024-05-01T00:00:00Z",
+                    5,
+                ),
+                post(
+                    "b",
+                    "general",
+                    "Mode switch: dark",
+                    "2024-05-02T00:00:00Z",
+                    1,
+                ),
+                post(
+                    "c",
+                    "Support",
+                    "Dark mode on mobile",
+                    "2024-05-03T00:00:00Z",
+                    9,
+                ),
+            ])
+            .unwrap();
+        let comment: Comment = serde_json::from_value(fixtures::comment(
+            "c1",
+            "a",
+            "bob",
+            "1",
+            "2024-05-04T00:00:00Z",
+        ))
+        .unwrap();
+        index.add_comment(&comment).unwrap();
+        index.commit().unwrap();
+        assert_eq!(index.len(), 4);
+
+        let mut keywords = ids(index.search(&IndexQuery::new("mode DARK")).unwrap());
+        keywords.sort();
+        assert_eq!(keywords, ["a", "b", "c"]);
+        let mut phrase = ids(index.search(&IndexQuery::new("\"dark mode\"")).unwrap());
+        phrase.sort();
+        assert_eq!(phrase, ["a", "c"]);
+
+        let query = IndexQuery::new("dark").community("support");
+        assert_eq!(ids(index.search(&query).unwrap()), ["c"]);
+        let query = IndexQuery::new("dark")
+            .min_score(5)
+            .until("2024-05-03T00:00:00Z".parse().unwrap());
+        assert_eq!(ids(index.search(&query).unwrap()), ["a"]);
+        let query = IndexQuery::new("")
+            .author("bob")
+            .kind(IndexedKind::Comment)
+            .since("2024-05-04T00:00:00Z".parse().unwrap());
+        assert_eq!(ids(index.search(&query).unwrap()), ["c1"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_replaces_and_persists() {
+        let dir = temp_dir();
+        {
+            let index = LocalIndex::open(&dir).unwrap();
+            index
+                .add_post(&post(
+                    "a",
+                    "general",
+                    "Old title",
+                    "2024-05-01T00:00:00Z",
+                    1,
+                ))
+                .unwrap();
+            index
+                .add_post(&post(
+                    "a",
+                    "general",
+                    "New title",
+                    "2024-05-01T00:00:00Z",
+                    1,
+                ))
+                .unwrap();
+            index.commit().unwrap();
+        }
+
+        let index = LocalIndex::open(&dir).unwrap();
+        assert_eq!(index.len(), 1);
+        assert!(index.search(&IndexQuery::new("old")).unwrap().is_empty());
+        assert_eq!(ids(index.search(&IndexQuery::new("new")).unwrap()), ["a"]);
+        drop(index);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_decode_hook() {
+        use crate::client::DiscuitClient;
+        use crate::mock_server::{MockResponse, MockServer};
+
+        let server = MockServer::start(|request| {
+            if request.path.contains("/comments") {
+                MockResponse::json(200, include_str!("structs/payloads/comments.json"))
+            } else {
+                MockResponse::json(200, include_str!("structs/payloads/post_feed.json"))
+            }
+        })
+        .await;
+        let dir = temp_dir();
+        let index = LocalIndex::open(&dir)
+            .unwrap()
+            .commit_every(5, Duration::from_secs(3600));
+        let client = DiscuitClient::builder(&server.url)
+            .hook(index.clone())
+            .build()
+            .unwrap();
+
+        // The page has three posts with one comment, fewer than a batch.
+        let page = client.get_posts_page(None, None, None).await.unwrap();
+        assert_eq!(index.len(), 0);
+        // Refetching the comment completes the batch, and replaces it.
+        let comments = client
+            .get_post_comments(&page.posts[0].public_id, None)
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 4);
+        let comment = &comments.comments.unwrap()[0];
+        let query = IndexQuery::new("").kind(IndexedKind::Comment);
+        assert_eq!(ids(index.search(&query).unwrap()), [comment.id.to_string()]);
+        drop(index);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -15,12 +15,17 @@
 //! async fn main() {
 //!     let base_url = "https://discuit.net";
 //!
-//!     let mut client = DiscuitClient::new(&base_url);
+//!     let client = DiscuitClient::new(&base_url);
 //!     let response = client.initialize().await;
 //!     println!("{:?}", response);
 //! }
 //! ```
 //!
+//! # Blocking client
+//!
+//! With the `blocking` feature enabled, `discuit_rs::blocking::DiscuitClient`
+//! mirrors every method of the async client without requiring a runtime.
+//!
 //! You can find more examples in the `examples` directory. To run the examples, use the following command:
 //! ```sh
 //! cargo run --example <example_name>
@@ -37,9 +42,88 @@ pub mod structs {
     /// The `api_requests` module contains all API request structs used in the `discuit-rs` library.
     pub mod api_requests;
 
+    /// The `ids` module contains the strongly typed IDs used in the `discuit-rs` library.
+    pub mod ids;
+
     /// The `internal_types` module contains all internal types used in the `discuit-rs` library.
     pub mod internal_types;
+
+    /// Round-trip tests of the structs against synthetic API payloads.
+    #[cfg(test)]
+    mod payloads;
 }
 
+/// The schema module contains the detection of fields missing from the structs.
+pub mod schema;
+
+/// The cache module contains the response cache used by `DiscuitClient`.
+pub mod cache;
+
+/// The capabilities module contains `InstanceCapabilities`, the features an instance supports.
+pub mod capabilities;
+
+/// The search module contains `Search`, which pages through the results of a search.
+pub mod search;
+
+/// The hook module contains `DecodeHook`, which is called with every decoded response.
+pub mod hook;
+
+/// The middleware module contains the `Middleware` trait, which hooks into every request.
+pub mod middleware;
+
+/// The metrics module contains `Metrics`, which counts the client's traffic in the Prometheus format.
+pub mod metrics;
+
 /// The client module contains the `DiscuitClient` struct, which is used to interact with the Discuit API.
 pub mod client;
+
+/// The error module contains the `Error` type returned by `DiscuitClient` methods.
+pub mod error;
+
+/// The builder module contains the `DiscuitClientBuilder`, which is used to configure a `DiscuitClient`.
+pub mod builder;
+
+/// The dry_run module contains the write dispatch and the results synthesized in dry-run mode.
+mod dry_run;
+
+#[cfg(test)]
+mod mock_server;
+
+/// The blocking module contains a blocking version of `DiscuitClient`.
+/// Requires the `blocking` feature.
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// The rate_limit module contains the client-side rate limiter used by `DiscuitClient`.
+pub mod rate_limit;
+
+/// The pool module contains `AccountPool`, which holds the clients of several accounts.
+pub mod pool;
+
+/// The aggregator module contains `Aggregator`, which merges the feeds of several instances.
+pub mod aggregator;
+
+/// The bot module contains a framework for writing bots that react to new posts, comments and notifications.
+pub mod bot;
+
+/// The archive module contains an archiver that mirrors communities to local storage.
+pub mod archive;
+
+/// The index module contains `LocalIndex`, a full-text index of fetched posts and comments.
+/// Requires the `index` feature.
+#[cfg(feature = "index")]
+pub mod index;
+
+/// The store module contains `Store`, a SQLite database of fetched entities.
+/// Requires the `store` feature.
+#[cfg(feature = "store")]
+pub mod store;
+
+/// The syndication module converts post feeds into RSS and Atom documents.
+pub mod syndication;
+
+/// The markdown module parses post and comment bodies and renders them to HTML or plain text.
+pub mod markdown;
+
+#[cfg(test)]
+mod fixtures;
@@ -0,0 +1,740 @@
+//! This module parses the markdown bodies of posts and comments.
+//!
+//! `parse` turns a body into a `Document`, which can be rendered to sanitized
+//! HTML or to plain text, and from which mentions (`@user`, `u/user`),
+//! community references (`+community`) and links can be extracted.
+//!
+//! # Example
+//!
+//! ```
+//! use discuit_rs::markdown;
+//!
+//! let document = markdown::parse("Thanks u/alice, see +rust and https://www.rust-lang.org");
+//!
+//! assert_eq!(document.mentions(), vec!["alice"]);
+//! assert_eq!(document.communities(), vec!["rust"]);
+//! assert_eq!(document.links(), vec!["https://www.rust-lang.org"]);
+//! assert_eq!(
+//!     document.to_plain_text(),
+//!     "Thanks @alice, see +rust and https://www.rust-lang.org"
+//! );
+//! ```
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use std::fmt::Write;
+
+/// The minimum length of usernames and community names recognized in text.
+const MIN_NAME_LENGTH: usize = 3;
+
+/// `Document` is a parsed markdown body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Document {
+    /// The blocks of the document.
+    pub blocks: Vec<Block>,
+}
+
+/// `Block` is a block-level element of a `Document`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    /// A paragraph.
+    Paragraph(Vec<Inline>),
+    /// A heading, with a level from 1 to 6.
+    Heading {
+        /// The level of the heading.
+        level: u8,
+        /// The content of the heading.
+        content: Vec<Inline>,
+    },
+    /// A block quote.
+    BlockQuote(Vec<Block>),
+    /// A list. Ordered lists have the number of their first item.
+    List {
+        /// The number of the first item of an ordered list, or None for bullet lists.
+        start: Option<u64>,
+        /// The items of the list.
+        items: Vec<Vec<Block>>,
+    },
+    /// A code block.
+    CodeBlock {
+        /// The language of a fenced code block, if given.
+        language: Option<String>,
+        /// The code.
+        code: String,
+    },
+    /// A horizontal rule.
+    Rule,
+}
+
+/// `Inline` is an inline element of a `Block`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inline {
+    /// Plain text. HTML in the markdown is kept as text.
+    Text(String),
+    /// Inline code.
+    Code(String),
+    /// Emphasized content.
+    Emphasis(Vec<Inline>),
+    /// Strongly emphasized content.
+    Strong(Vec<Inline>),
+    /// Struck through content.
+    Strikethrough(Vec<Inline>),
+    /// A link, written in markdown or as a bare URL.
+    Link {
+        /// The URL of the link.
+        url: String,
+        /// The content of the link.
+        content: Vec<Inline>,
+    },
+    /// An image.
+    Image {
+        /// The URL of the image.
+        url: String,
+        /// The alternative text of the image.
+        alt: String,
+    },
+    /// A mention of a user, `@username` or `u/username`.
+    Mention(String),
+    /// A reference to a community, `+community`.
+    CommunityRef(String),
+    /// A hard line break.
+    LineBreak,
+    /// A soft line break, a newline within a paragraph.
+    SoftBreak,
+}
+
+/// Parse a markdown body.
+pub fn parse(body: &str) -> Document {
+    let mut events = Parser::new_ext(body, Options::ENABLE_STRIKETHROUGH);
+    Document {
+        blocks: parse_blocks(&mut events),
+    }
+}
+
+/// Render a markdown body to sanitized HTML. Shorthand for `parse(body).to_html()`.
+pub fn to_html(body: &str) -> String {
+    parse(body).to_html()
+}
+
+/// Render a markdown body to plain text. Shorthand for `parse(body).to_plain_text()`.
+pub fn to_plain_text(body: &str) -> String {
+    parse(body).to_plain_text()
+}
+
+/// Parses blocks until the end of the enclosing container.
+fn parse_blocks<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    // Inline content outside of a paragraph, e.g. in the items of tight lists.
+    let mut loose = Vec::new();
+
+    while let Some(event) = events.next() {
+        let block = match event {
+            Event::End(_) => break,
+            Event::Start(Tag::Paragraph) => Block::Paragraph(parse_inlines(events)),
+            Event::Start(Tag::Heading { level, .. }) => Block::Heading {
+                level: level as u8,
+                content: parse_inlines(events),
+            },
+            Event::Start(Tag::BlockQuote(_)) => Block::BlockQuote(parse_blocks(events)),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .map(|language| language.to_string()),
+                    CodeBlockKind::Indented => None,
+                };
+                Block::CodeBlock {
+                    language,
+                    code: collect_text(events),
+                }
+            }
+            Event::Start(Tag::HtmlBlock) => {
+                Block::Paragraph(vec![Inline::Text(collect_text(events))])
+            }
+            Event::Start(Tag::List(start)) => {
+                let mut items = Vec::new();
+                while let Some(Event::Start(Tag::Item)) = events.next() {
+                    items.push(parse_blocks(events));
+                }
+                Block::List { start, items }
+            }
+            Event::Rule => Block::Rule,
+            // Other containers, e.g. footnote definitions, are flattened.
+            Event::Start(
+                Tag::FootnoteDefinition(_)
+                | Tag::Table(_)
+                | Tag::TableHead
+                | Tag::TableRow
+                | Tag::TableCell
+                | Tag::DefinitionList
+                | Tag::DefinitionListTitle
+                | Tag::DefinitionListDefinition
+                | Tag::MetadataBlock(_),
+            ) => {
+                blocks.extend(parse_blocks(events));
+                continue;
+            }
+            event => {
+                if let Some(inline) = parse_inline(event, events) {
+                    loose.push(inline);
+                }
+                continue;
+            }
+        };
+        if !loose.is_empty() {
+            blocks.push(Block::Paragraph(enrich(std::mem::take(&mut loose))));
+        }
+        blocks.push(block);
+    }
+
+    if !loose.is_empty() {
+        blocks.push(Block::Paragraph(enrich(loose)));
+    }
+    blocks
+}
+
+/// Parses inline content until the end of the enclosing element.
+fn parse_inlines<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    while let Some(event) = events.next() {
+        if let Event::End(_) = event {
+            break;
+        }
+        if let Some(inline) = parse_inline(event, events) {
+            inlines.push(inline);
+        }
+    }
+    enrich(inlines)
+}
+
+/// Parses a single inline element starting with the given event.
+fn parse_inline<'a>(
+    event: Event<'a>,
+    events: &mut impl Iterator<Item = Event<'a>>,
+) -> Option<Inline> {
+    Some(match event {
+        Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+            Inline::Text(text.into_string())
+        }
+        Event::Code(code) => Inline::Code(code.into_string()),
+        Event::SoftBreak => Inline::SoftBreak,
+        Event::HardBreak => Inline::LineBreak,
+        Event::TaskListMarker(checked) => {
+            Inline::Text(if checked { "[x] " } else { "[ ] " }.to_string())
+        }
+        Event::Start(Tag::Emphasis) => Inline::Emphasis(parse_inlines(events)),
+        Event::Start(Tag::Strong) => Inline::Strong(parse_inlines(events)),
+        Event::Start(Tag::Strikethrough) => Inline::Strikethrough(parse_inlines(events)),
+        Event::Start(Tag::Link { dest_url, .. }) => {
+            // Mentions and bare URLs aren't recognized within links.
+            let mut content = Vec::new();
+            while let Some(event) = events.next() {
+                if let Event::End(_) = event {
+                    break;
+                }
+                content.extend(parse_inline(event, events));
+            }
+            Inline::Link {
+                url: dest_url.into_string(),
+                content: merge_text(content),
+            }
+        }
+        Event::Start(Tag::Image { dest_url, .. }) => Inline::Image {
+            url: dest_url.into_string(),
+            alt: plain_text(&parse_inlines(events)),
+        },
+        Event::Start(_) => Inline::Text(plain_text(&parse_inlines(events))),
+        _ => return None,
+    })
+}
+
+/// Collects the text of an element, e.g. a code block, until its end.
+fn collect_text<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> String {
+    let mut text = String::new();
+    for event in events.by_ref() {
+        match event {
+            Event::End(_) => break,
+            Event::Text(t) | Event::Html(t) => text.push_str(&t),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Merges adjacent text, which the parser may split up.
+fn merge_text(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut merged: Vec<Inline> = Vec::with_capacity(inlines.len());
+    for inline in inlines {
+        match (merged.last_mut(), inline) {
+            (Some(Inline::Text(last)), Inline::Text(text)) => last.push_str(&text),
+            (_, inline) => merged.push(inline),
+        }
+    }
+    merged
+}
+
+/// Merges adjacent text and splits mentions, community references and bare
+/// URLs out of it.
+fn enrich(inlines: Vec<Inline>) -> Vec<Inline> {
+    merge_text(inlines)
+        .into_iter()
+        .flat_map(|inline| match inline {
+            Inline::Text(text) => split_text(&text),
+            inline => vec![inline],
+        })
+        .collect()
+}
+
+/// Splits mentions, community references and bare URLs out of text.
+fn split_text(text: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+        let prev = text[..i].chars().next_back();
+        let after_word = prev.is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+        let found = if after_word {
+            None
+        } else if rest.starts_with("http://") || rest.starts_with("https://") {
+            let len = url_length(rest);
+            Some((
+                len,
+                Inline::Link {
+                    url: rest[..len].to_string(),
+                    content: vec![Inline::Text(rest[..len].to_string())],
+                },
+            ))
+        } else if let Some(name) = rest.strip_prefix("u/").filter(|_| prev != Some('/')) {
+            name_length(name).map(|len| (len + 2, Inline::Mention(name[..len].to_string())))
+        } else if let Some(name) = rest.strip_prefix('@') {
+            name_length(name).map(|len| (len + 1, Inline::Mention(name[..len].to_string())))
+        } else if let Some(name) = rest.strip_prefix('+') {
+            name_length(name).map(|len| (len + 1, Inline::CommunityRef(name[..len].to_string())))
+        } else {
+            None
+        };
+
+        match found {
+            Some((len, inline)) => {
+                if start < i {
+                    inlines.push(Inline::Text(text[start..i].to_string()));
+                }
+                inlines.push(inline);
+                i += len;
+                start = i;
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    if start < text.len() {
+        inlines.push(Inline::Text(text[start..].to_string()));
+    }
+    inlines
+}
+
+/// Returns the length of the username or community name at the start of `text`.
+fn name_length(text: &str) -> Option<usize> {
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    (len >= MIN_NAME_LENGTH).then_some(len)
+}
+
+/// Returns the length of the bare URL at the start of `text`, leaving out
+/// trailing punctuation.
+fn url_length(text: &str) -> usize {
+    let mut url = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+        .map_or(text, |end| &text[..end]);
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        // Keep closing parentheses that belong to the URL.
+        let trimmed = if trimmed.ends_with(')')
+            && trimmed.matches('(').count() < trimmed.matches(')').count()
+        {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        if trimmed.len() == url.len() {
+            return url.len();
+        }
+        url = trimmed;
+    }
+}
+
+/// Returns true if a URL may be used in a link without risk, e.g. not a
+/// `javascript:` URL.
+fn is_safe_url(url: &str) -> bool {
+    let lower = url.trim().to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+        || (lower.starts_with('/') && !lower.starts_with("//"))
+        || lower.starts_with('#')
+}
+
+/// Escapes text for use in HTML content and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Document {
+    /// Render the document to HTML. All text is escaped, and links with
+    /// unsafe URLs are rendered as their content only. Mentions link to
+    /// `/@username` and community references to `/community`.
+    pub fn to_html(&self) -> String {
+        self.to_html_with_base("")
+    }
+
+    /// Render the document to HTML like `to_html`, with mentions and community
+    /// references linking to the instance at `base_url`, e.g. for emails.
+    pub fn to_html_with_base(&self, base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        let mut html = String::new();
+        for block in &self.blocks {
+            block_html(block, base_url, &mut html);
+        }
+        html
+    }
+
+    /// Render the document to plain text, with blocks separated by blank lines.
+    pub fn to_plain_text(&self) -> String {
+        blocks_text(&self.blocks)
+    }
+
+    /// Returns the usernames mentioned in the document, without duplicates.
+    pub fn mentions(&self) -> Vec<String> {
+        self.collect(|inline| match inline {
+            Inline::Mention(username) => Some(username.clone()),
+            _ => None,
+        })
+    }
+
+    /// Returns the communities referenced in the document, without duplicates.
+    pub fn communities(&self) -> Vec<String> {
+        self.collect(|inline| match inline {
+            Inline::CommunityRef(name) => Some(name.clone()),
+            _ => None,
+        })
+    }
+
+    /// Returns the URLs of the links in the document, without duplicates.
+    pub fn links(&self) -> Vec<String> {
+        self.collect(|inline| match inline {
+            Inline::Link { url, .. } => Some(url.clone()),
+            _ => None,
+        })
+    }
+
+    /// Collects the values `f` returns for the inlines of the document, in
+    /// order and without duplicates.
+    fn collect(&self, f: impl Fn(&Inline) -> Option<String>) -> Vec<String> {
+        fn walk_blocks(
+            blocks: &[Block],
+            f: &dyn Fn(&Inline) -> Option<String>,
+            out: &mut Vec<String>,
+        ) {
+            for block in blocks {
+                match block {
+                    Block::Paragraph(content) | Block::Heading { content, .. } => {
+                        walk_inlines(content, f, out)
+                    }
+                    Block::BlockQuote(blocks) => walk_blocks(blocks, f, out),
+                    Block::List { items, .. } => {
+                        for item in items {
+                            walk_blocks(item, f, out);
+                        }
+                    }
+                    Block::CodeBlock { .. } | Block::Rule => {}
+                }
+            }
+        }
+        fn walk_inlines(
+            inlines: &[Inline],
+            f: &dyn Fn(&Inline) -> Option<String>,
+            out: &mut Vec<String>,
+        ) {
+            for inline in inlines {
+                if let Some(value) = f(inline) {
+                    if !out.contains(&value) {
+                        out.push(value);
+                    }
+                }
+                match inline {
+                    Inline::Emphasis(content)
+                    | Inline::Strong(content)
+                    | Inline::Strikethrough(content)
+                    | Inline::Link { content, .. } => walk_inlines(content, f, out),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        walk_blocks(&self.blocks, &f, &mut out);
+        out
+    }
+}
+
+fn block_html(block: &Block, base_url: &str, html: &mut String) {
+    match block {
+        Block::Paragraph(content) => {
+            html.push_str("<p>");
+            inlines_html(content, base_url, html);
+            html.push_str("</p>\n");
+        }
+        Block::Heading { level, content } => {
+            let _ = write!(html, "<h{}>", level);
+            inlines_html(content, base_url, html);
+            let _ = writeln!(html, "</h{}>", level);
+        }
+        Block::BlockQuote(blocks) => {
+            html.push_str("<blockquote>\n");
+            for block in blocks {
+                block_html(block, base_url, html);
+            }
+            html.push_str("</blockquote>\n");
+        }
+        Block::List { start, items } => {
+            match start {
+                Some(1) => html.push_str("<ol>\n"),
+                Some(start) => {
+                    let _ = writeln!(html, "<ol start=\"{}\">", start);
+                }
+                None => html.push_str("<ul>\n"),
+            }
+            for item in items {
+                html.push_str("<li>");
+                // Render the single paragraph of tight items without <p>.
+                match item.as_slice() {
+                    [Block::Paragraph(content)] => inlines_html(content, base_url, html),
+                    blocks => {
+                        for block in blocks {
+                            block_html(block, base_url, html);
+                        }
+                    }
+                }
+                html.push_str("</li>\n");
+            }
+            html.push_str(if start.is_some() {
+                "</ol>\n"
+            } else {
+                "</ul>\n"
+            });
+        }
+        Block::CodeBlock { language, code } => {
+            match language {
+                Some(language) => {
+                    let _ = write!(html, "<pre><code class=\"language-{}\">", escape(language));
+                }
+                None => html.push_str("<pre><code>"),
+            }
+            html.push_str(&escape(code));
+            html.push_str("</code></pre>\n");
+        }
+        Block::Rule => html.push_str("<hr/>\n"),
+    }
+}
+
+fn inlines_html(inlines: &[Inline], base_url: &str, html: &mut String) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => html.push_str(&escape(text)),
+            Inline::Code(code) => {
+                let _ = write!(html, "<code>{}</code>", escape(code));
+            }
+            Inline::Emphasis(content) => wrap_html("em", content, base_url, html),
+            Inline::Strong(content) => wrap_html("strong", content, base_url, html),
+            Inline::Strikethrough(content) => wrap_html("del", content, base_url, html),
+            Inline::Link { url, content } if is_safe_url(url) => {
+                let _ = write!(
+                    html,
+                    "<a href=\"{}\" rel=\"nofollow noopener noreferrer\">",
+                    escape(url)
+                );
+                inlines_html(content, base_url, html);
+                html.push_str("</a>");
+            }
+            Inline::Link { content, .. } => inlines_html(content, base_url, html),
+            Inline::Image { url, alt } if is_safe_url(url) => {
+                let _ = write!(
+                    html,
+                    "<img src=\"{}\" alt=\"{}\"/>",
+                    escape(url),
+                    escape(alt)
+                );
+            }
+            Inline::Image { alt, .. } => html.push_str(&escape(alt)),
+            Inline::Mention(username) => {
+                let _ = write!(
+                    html,
+                    "<a href=\"{0}/@{1}\">@{1}</a>",
+                    escape(base_url),
+                    escape(username)
+                );
+            }
+            Inline::CommunityRef(name) => {
+                let _ = write!(
+                    html,
+                    "<a href=\"{0}/{1}\">+{1}</a>",
+                    escape(base_url),
+                    escape(name)
+                );
+            }
+            Inline::LineBreak => html.push_str("<br/>\n"),
+            Inline::SoftBreak => html.push('\n'),
+        }
+    }
+}
+
+fn wrap_html(tag: &str, content: &[Inline], base_url: &str, html: &mut String) {
+    let _ = write!(html, "<{}>", tag);
+    inlines_html(content, base_url, html);
+    let _ = write!(html, "</{}>", tag);
+}
+
+fn blocks_text(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(block_text)
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn block_text(block: &Block) -> String {
+    match block {
+        Block::Paragraph(content) | Block::Heading { content, .. } => plain_text(content),
+        Block::BlockQuote(blocks) => blocks_text(blocks),
+        Block::List { start, items } => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = match start {
+                    Some(start) => format!("{}. ", start + i as u64),
+                    None => "- ".to_string(),
+                };
+                format!("{}{}", marker, blocks_text(item))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::CodeBlock { code, .. } => code.trim_end().to_string(),
+        Block::Rule => String::new(),
+    }
+}
+
+/// Renders inline content to plain text.
+fn plain_text(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(t) | Inline::Code(t) => text.push_str(t),
+            Inline::Emphasis(content)
+            | Inline::Strong(content)
+            | Inline::Strikethrough(content) => text.push_str(&plain_text(content)),
+            Inline::Link { url, content } => {
+                let content = plain_text(content);
+                if content == *url || content.is_empty() {
+                    text.push_str(url);
+                } else {
+                    let _ = write!(text, "{} ({})", content, url);
+                }
+            }
+            Inline::Image { alt, .. } => text.push_str(alt),
+            Inline::Mention(username) => {
+                let _ = write!(text, "@{}", username);
+            }
+            Inline::CommunityRef(name) => {
+                let _ = write!(text, "+{}", name);
+            }
+            Inline::LineBreak | Inline::SoftBreak => text.push('\n'),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let document = parse(
+            "Hi @bob and u/alice (cc u/bob), email me at me@example.com.\n\n\
+             > see +rust_lang, not a+b or +1\n\n\
+             `u/code` [u/linked](https://example.com/u/x) https://example.com/a_(b)).",
+        );
+
+        assert_eq!(document.mentions(), vec!["bob", "alice"]);
+        assert_eq!(document.communities(), vec!["rust_lang"]);
+        assert_eq!(
+            document.links(),
+            vec!["https://example.com/u/x", "https://example.com/a_(b)"]
+        );
+    }
+
+    #[test]
+    fn test_html_is_sanitized() {
+        let html = to_html(
+            "<script>alert(1)</script>\n\n[click](javascript:alert(1)) **bold** u/alice\n\n\
+             ```rust\nlet x = 1 < 2;\n```",
+        );
+
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains("<a href=\"/@alice\">@alice</a>"));
+        assert!(parse("+rust")
+            .to_html_with_base("https://discuit.net/")
+            .contains("<a href=\"https://discuit.net/rust\">+rust</a>"));
+        assert!(
+            html.contains("<pre><code class=\"language-rust\">let x = 1 &lt; 2;\n</code></pre>")
+        );
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let text = to_plain_text(
+            "# Title\n\nSome *emphasis* and a [link](https://example.com).\n\n\
+             - one\n- two\n\n3. three\n4. four",
+        );
+
+        assert_eq!(
+            text,
+            "Title\n\nSome emphasis and a link (https://example.com).\n\n- one\n- two\n\n3. three\n4. four"
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        let document = parse("- a\n- b\n\n  more");
+        let Block::List { start, items } = &document.blocks[0] else {
+            panic!("expected a list");
+        };
+
+        assert_eq!(*start, None);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].len(), 2);
+        assert_eq!(
+            document.to_html(),
+            "<ul>\n<li>a</li>\n<li><p>b</p>\n<p>more</p>\n</li>\n</ul>\n"
+        );
+    }
+}
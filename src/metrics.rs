@@ -0,0 +1,491 @@
+//! This module contains `Metrics`, which counts the traffic of a
+//! `DiscuitClient` and renders it in the Prometheus text format.
+//!
+//! Pass a `Metrics` to `DiscuitClientBuilder::metrics` to record:
+//!
+//! - `discuit_requests_total`: requests sent, by method, endpoint and status
+//!   class (`2xx`, `4xx`, ..., or `error` if no response was received).
+//!   Every attempt counts, including retries; responses served from the
+//!   cache don't.
+//! - `discuit_request_duration_seconds`: a histogram of the latency of those
+//!   requests, by method and endpoint.
+//! - `discuit_retries_total`: retried requests, by method and endpoint.
+//! - `discuit_rate_limit_waits_total` and `discuit_rate_limit_wait_seconds_total`:
+//!   how often and how long requests waited for the rate limiter, by kind.
+//! - `discuit_decode_failures_total`: responses that couldn't be decoded, by
+//!   the type they were decoded into.
+//!
+//! Endpoints are paths with their IDs replaced by `{id}`, e.g.
+//! `/api/posts/{id}/comments`, so the number of series stays bounded.
+//! `Metrics::render` returns the text to serve; with the `metrics-server`
+//! feature enabled, `serve` serves it at `/metrics`.
+//!
+//! # Example
+//!
+//! ```
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::metrics::Metrics;
+//!
+//! let metrics = Metrics::new();
+//! let client = DiscuitClient::builder("https://discuit.net")
+//!     .metrics(metrics.clone())
+//!     .build()
+//!     .unwrap();
+//!
+//! // Later, e.g. in a handler of your own HTTP server:
+//! let text = metrics.render();
+//! ```
+
+use crate::rate_limit::RequestKind;
+use reqwest::{Method, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// The upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// `Metrics` counts the requests of the clients it is passed to.
+///
+/// Cloning is cheap; clones share the same counters, so one `Metrics` can be
+/// passed to several clients to add up their traffic.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+/// The counters of a `Metrics`, keyed by their labels.
+#[derive(Debug, Default)]
+struct Registry {
+    /// Keyed by method, endpoint and status class.
+    requests: BTreeMap<(String, String, &'static str), u64>,
+    /// Keyed by method and endpoint.
+    latencies: BTreeMap<(String, String), Histogram>,
+    /// Keyed by method and endpoint.
+    retries: BTreeMap<(String, String), u64>,
+    /// The number of waits and the total time waited, keyed by request kind.
+    rate_limit_waits: BTreeMap<&'static str, (u64, f64)>,
+    /// Keyed by type.
+    decode_failures: BTreeMap<String, u64>,
+}
+
+/// A latency histogram with the `LATENCY_BUCKETS` bounds.
+#[derive(Debug, Default)]
+struct Histogram {
+    /// The number of observations in each bucket, not cumulative.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+impl Metrics {
+    /// Create a new `Metrics` with all counters at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a request sent to `path`, with the status of its response or
+    /// `None` if it failed without one.
+    /// For internal use only.
+    pub(crate) fn record_request(
+        &self,
+        method: &Method,
+        path: &str,
+        status: Option<StatusCode>,
+        latency: Duration,
+    ) {
+        let class = match status.map(|status| status.as_u16() / 100) {
+            Some(1) => "1xx",
+            Some(2) => "2xx",
+            Some(3) => "3xx",
+            Some(4) => "4xx",
+            Some(_) => "5xx",
+            None => "error",
+        };
+        let endpoint = endpoint(path);
+
+        let mut registry = self.lock();
+        *registry
+            .requests
+            .entry((method.to_string(), endpoint.clone(), class))
+            .or_default() += 1;
+        registry
+            .latencies
+            .entry((method.to_string(), endpoint))
+            .or_default()
+            .observe(latency.as_secs_f64());
+    }
+
+    /// Records a retry of a request sent to `path`.
+    /// For internal use only.
+    pub(crate) fn record_retry(&self, method: &Method, path: &str) {
+        *self
+            .lock()
+            .retries
+            .entry((method.to_string(), endpoint(path)))
+            .or_default() += 1;
+    }
+
+    /// Records a request that waited for the rate limiter.
+    /// For internal use only.
+    pub(crate) fn record_rate_limit_wait(&self, kind: RequestKind, waited: Duration) {
+        let kind = match kind {
+            RequestKind::Read => "read",
+            RequestKind::Write => "write",
+        };
+        let mut registry = self.lock();
+        let (count, seconds) = registry.rate_limit_waits.entry(kind).or_default();
+        *count += 1;
+        *seconds += waited.as_secs_f64();
+    }
+
+    /// Records a response that couldn't be decoded into a `T`.
+    /// For internal use only.
+    pub(crate) fn record_decode_failure<T>(&self) {
+        let name = type_label(std::any::type_name::<T>());
+        *self.lock().decode_failures.entry(name).or_default() += 1;
+    }
+
+    /// Returns the total number of requests recorded, over all endpoints.
+    pub fn requests(&self) -> u64 {
+        self.lock().requests.values().sum()
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let registry = self.lock();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "discuit_requests_total",
+            "counter",
+            "Requests sent, by method, endpoint and status class.",
+        );
+        for ((method, endpoint, status), count) in &registry.requests {
+            let labels = labels(&[
+                ("method", method),
+                ("endpoint", endpoint),
+                ("status", status),
+            ]);
+            let _ = writeln!(out, "discuit_requests_total{{{}}} {}", labels, count);
+        }
+
+        header(
+            &mut out,
+            "discuit_request_duration_seconds",
+            "histogram",
+            "The latency of requests, by method and endpoint.",
+        );
+        for ((method, endpoint), histogram) in &registry.latencies {
+            let labels = labels(&[("method", method), ("endpoint", endpoint)]);
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "discuit_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "discuit_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "discuit_request_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "discuit_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+
+        header(
+            &mut out,
+            "discuit_retries_total",
+            "counter",
+            "Retried requests, by method and endpoint.",
+        );
+        for ((method, endpoint), count) in &registry.retries {
+            let labels = labels(&[("method", method), ("endpoint", endpoint)]);
+            let _ = writeln!(out, "discuit_retries_total{{{}}} {}", labels, count);
+        }
+
+        header(
+            &mut out,
+            "discuit_rate_limit_waits_total",
+            "counter",
+            "Requests that waited for the rate limiter, by kind.",
+        );
+        for (kind, (count, _)) in &registry.rate_limit_waits {
+            let labels = labels(&[("kind", kind)]);
+            let _ = writeln!(
+                out,
+                "discuit_rate_limit_waits_total{{{}}} {}",
+                labels, count
+            );
+        }
+
+        header(
+            &mut out,
+            "discuit_rate_limit_wait_seconds_total",
+            "counter",
+            "Time spent waiting for the rate limiter, by kind.",
+        );
+        for (kind, (_, seconds)) in &registry.rate_limit_waits {
+            let labels = labels(&[("kind", kind)]);
+            let _ = writeln!(
+                out,
+                "discuit_rate_limit_wait_seconds_total{{{}}} {}",
+                labels, seconds
+            );
+        }
+
+        header(
+            &mut out,
+            "discuit_decode_failures_total",
+            "counter",
+            "Responses that couldn't be decoded, by type.",
+        );
+        for (name, count) in &registry.decode_failures {
+            let labels = labels(&[("type", name)]);
+            let _ = writeln!(out, "discuit_decode_failures_total{{{}}} {}", labels, count);
+        }
+
+        out
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric.
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Formats label pairs, escaping their values.
+fn labels(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Returns the endpoint of a path, with IDs replaced by `{id}`.
+///
+/// API paths alternate between resources and IDs after `/api`, e.g.
+/// `/api/users/{id}/lists/{id}`.
+fn endpoint(path: &str) -> String {
+    let mut endpoint = String::new();
+    for (i, segment) in path.split('/').filter(|s| !s.is_empty()).enumerate() {
+        endpoint.push('/');
+        if i >= 2 && i % 2 == 0 {
+            endpoint.push_str("{id}");
+        } else {
+            endpoint.push_str(segment);
+        }
+    }
+    if endpoint.is_empty() {
+        endpoint.push('/');
+    }
+    endpoint
+}
+
+/// Returns a type name without its module paths, e.g. `Option<Vec<Community>>`.
+fn type_label(name: &str) -> String {
+    let mut label = String::new();
+    let mut segment = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            label.push_str(segment.rsplit("::").next().unwrap_or_default());
+            segment.clear();
+            label.push(c);
+        }
+    }
+    label.push_str(segment.rsplit("::").next().unwrap_or_default());
+    label
+}
+
+#[cfg(feature = "metrics-server")]
+pub use server::serve;
+
+#[cfg(feature = "metrics-server")]
+mod server {
+    use super::Metrics;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tracing::{info, warn};
+
+    /// Serve the metrics at `/metrics` over HTTP until the listener fails.
+    ///
+    /// Requires the `metrics-server` feature.
+    pub async fn serve(metrics: Metrics, listener: TcpListener) -> std::io::Result<()> {
+        info!(address = %listener.local_addr()?, "Serving metrics ...");
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle(metrics, stream).await {
+                    warn!(error = %e, "Failed to serve metrics");
+                }
+            });
+        }
+    }
+
+    async fn handle(metrics: Metrics, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut buf = vec![0; 8192];
+        let n = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..n]);
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("GET "))
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap_or_default();
+
+        let (status, content_type, body) = match target.split('?').next() {
+            Some("/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics.render()),
+            _ => ("404 Not Found", "text/plain", "Not found".to_string()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::DiscuitClient;
+    use crate::mock_server::{MockResponse, MockServer};
+    use crate::structs::api_types::PublicPostId;
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(endpoint("/api/posts"), "/api/posts");
+        assert_eq!(endpoint("/api/posts/Gq4XzLbA"), "/api/posts/{id}");
+        assert_eq!(
+            endpoint("/api/posts/Gq4XzLbA/comments/17e2a4c1"),
+            "/api/posts/{id}/comments/{id}"
+        );
+        assert_eq!(endpoint("/api/_postVote"), "/api/_postVote");
+        assert_eq!(
+            type_label(std::any::type_name::<
+                Option<Vec<crate::structs::api_types::Community>>,
+            >()),
+            "Option<Vec<Community>>"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_metrics() {
+        let post = include_str!("structs/payloads/post.json");
+        let server = MockServer::start(move |request| {
+            if request.path.starts_with("/api/posts/retry") {
+                MockResponse::json(503, "{}")
+            } else if request.path.starts_with("/api/posts/broken") {
+                MockResponse::json(200, "{\"id\": 1}")
+            } else {
+                MockResponse::json(200, post)
+            }
+        })
+        .await;
+        let metrics = Metrics::new();
+        let client = DiscuitClient::builder(&server.url)
+            .max_retries(1)
+            .metrics(metrics.clone())
+            .build()
+            .unwrap();
+
+        client
+            .get_post(&PublicPostId::from("Gq4XzLbA"))
+            .await
+            .unwrap();
+        client
+            .get_post(&PublicPostId::from("retry"))
+            .await
+            .unwrap_err();
+        client
+            .get_post(&PublicPostId::from("broken"))
+            .await
+            .unwrap_err();
+        assert_eq!(metrics.requests(), 4);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "discuit_requests_total{method=\"GET\",endpoint=\"/api/posts/{id}\",status=\"2xx\"} 2"
+        ));
+        assert!(text.contains(
+            "discuit_requests_total{method=\"GET\",endpoint=\"/api/posts/{id}\",status=\"5xx\"} 2"
+        ));
+        assert!(text.contains(
+            "discuit_request_duration_seconds_count{method=\"GET\",endpoint=\"/api/posts/{id}\"} 4"
+        ));
+        assert!(
+            text.contains("discuit_retries_total{method=\"GET\",endpoint=\"/api/posts/{id}\"} 1")
+        );
+        assert!(text.contains("discuit_decode_failures_total{type=\"Post\"} 1"));
+    }
+
+    #[cfg(feature = "metrics-server")]
+    #[tokio::test]
+    async fn test_serve() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let metrics = Metrics::new();
+        metrics.record_retry(&Method::GET, "/api/posts");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(metrics, listener));
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("text/plain; version=0.0.4"));
+        assert!(
+            response.contains("discuit_retries_total{method=\"GET\",endpoint=\"/api/posts\"} 1")
+        );
+    }
+}
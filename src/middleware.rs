@@ -0,0 +1,535 @@
+//! This module contains the `Middleware` trait, which hooks into every
+//! request `DiscuitClient` sends.
+//!
+//! Requests pass through a stack of middleware before reaching the network.
+//! The client's own caching, logging, retries, rate limiting and metrics are
+//! middleware, in that order, followed by the middleware added with
+//! `DiscuitClientBuilder::middleware`, which thus see every attempt actually
+//! sent, including retries, but not the responses served from the cache.
+//!
+//! Most middleware only need the `before_request`, `after_response` and
+//! `on_error` hooks, e.g. to add headers, sign requests, count responses or
+//! audit writes. Middleware that need full control, e.g. to answer requests
+//! themselves for fault injection, implement `handle` instead.
+//!
+//! # Example
+//!
+//! ```
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::middleware::{Middleware, RequestInfo};
+//! use reqwest::{Request, Response};
+//!
+//! #[derive(Debug)]
+//! struct Audit;
+//!
+//! impl Middleware for Audit {
+//!     fn before_request(&self, request: &mut Request) {
+//!         request
+//!             .headers_mut()
+//!             .insert("X-Request-Source", "moderation-bot".parse().unwrap());
+//!     }
+//!
+//!     fn after_response(&self, request: &RequestInfo, response: Response) -> Response {
+//!         if request.method != reqwest::Method::GET {
+//!             println!("{} {} -> {}", request.method, request.url, response.status());
+//!         }
+//!         response
+//!     }
+//! }
+//!
+//! let client = DiscuitClient::builder("https://discuit.net")
+//!     .middleware(Audit)
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use crate::cache::{CachedResponse, Lookup, ResponseCache};
+use crate::metrics::Metrics;
+use crate::rate_limit::{RateLimiter, RequestKind};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderValue, IF_NONE_MATCH, RETRY_AFTER, SET_COOKIE};
+use reqwest::{Client, Method, Request, Response, StatusCode, Url};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use tracing::{debug, field, info_span, warn, Instrument, Span};
+
+/// The future returned by `Middleware::handle` and `Next::run`.
+pub type MiddlewareFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send + 'a>>;
+
+/// `RequestInfo` describes a request that was sent, for the hooks that run
+/// after it.
+#[derive(Clone, Debug)]
+pub struct RequestInfo {
+    /// The method of the request.
+    pub method: Method,
+    /// The URL of the request.
+    pub url: Url,
+    /// When the request was passed on to the rest of the stack.
+    pub started: Instant,
+}
+
+impl RequestInfo {
+    /// Returns the time since the request was passed on.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// `Middleware` hooks into every request a client sends.
+pub trait Middleware: Send + Sync + fmt::Debug {
+    /// Called before the request is passed on to the rest of the stack.
+    fn before_request(&self, _request: &mut Request) {}
+
+    /// Called with the response of the request, which can be replaced.
+    fn after_response(&self, _request: &RequestInfo, response: Response) -> Response {
+        response
+    }
+
+    /// Called if the request failed without a response, e.g. because the
+    /// connection failed or timed out.
+    fn on_error(&self, _request: &RequestInfo, _error: &reqwest::Error) {}
+
+    /// Handles a request, passing it on with `next.run` and returning its
+    /// response. The default implementation calls the other hooks.
+    fn handle<'a>(&'a self, mut request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            self.before_request(&mut request);
+            let info = RequestInfo {
+                method: request.method().clone(),
+                url: request.url().clone(),
+                started: Instant::now(),
+            };
+            match next.run(request).await {
+                Ok(response) => Ok(self.after_response(&info, response)),
+                Err(e) => {
+                    self.on_error(&info, &e);
+                    Err(e)
+                }
+            }
+        })
+    }
+}
+
+/// `Next` is the rest of the middleware stack, ending in the HTTP client.
+#[derive(Clone, Copy, Debug)]
+pub struct Next<'a> {
+    client: &'a Client,
+    stack: &'a [&'a dyn Middleware],
+}
+
+impl<'a> Next<'a> {
+    /// Creates the stack of the given middleware, in order.
+    /// For internal use only.
+    pub(crate) fn new(client: &'a Client, stack: &'a [&'a dyn Middleware]) -> Self {
+        Self { client, stack }
+    }
+
+    /// Pass a request on to the next middleware, or send it if there is none.
+    /// `Next` can be copied to send a request more than once, e.g. to retry it.
+    pub fn run(self, request: Request) -> MiddlewareFuture<'a> {
+        match self.stack.split_first() {
+            Some((middleware, stack)) => middleware.handle(
+                request,
+                Next {
+                    client: self.client,
+                    stack,
+                },
+            ),
+            None => Box::pin(self.client.execute(request)),
+        }
+    }
+}
+
+/// Serves reads from a `ResponseCache`.
+///
+/// Fresh cached responses are returned without sending the request, stale
+/// ones are revalidated with their ETag, and successful writes invalidate
+/// the cached responses of the resource they changed.
+/// For internal use only.
+#[derive(Debug)]
+pub(crate) struct CacheLayer<'a> {
+    pub(crate) cache: &'a ResponseCache,
+    /// Who the responses are cached for.
+    pub(crate) identity: String,
+}
+
+impl Middleware for CacheLayer<'_> {
+    fn handle<'a>(&'a self, mut request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let cache = self.cache;
+            if request.method() != Method::GET {
+                let path = request.url().path().to_string();
+                let response = next.run(request).await?;
+                if response.status().is_success() {
+                    cache.invalidate_write(&path);
+                }
+                return Ok(response);
+            }
+
+            let key = ResponseCache::key(&self.identity, request.url());
+            let stale = match cache.lookup(&key) {
+                Lookup::Fresh(cached) => {
+                    debug!(path = %request.url().path(), "Serving cached response");
+                    return Ok(cached.into_response());
+                }
+                Lookup::Stale(cached) => {
+                    let etag = cached.etag.as_deref().map(HeaderValue::from_str);
+                    if let Some(Ok(etag)) = etag {
+                        request.headers_mut().insert(IF_NONE_MATCH, etag);
+                    }
+                    Some(cached)
+                }
+                Lookup::Miss => None,
+            };
+
+            let response = next.run(request).await?;
+            if let (StatusCode::NOT_MODIFIED, Some(mut cached)) = (response.status(), stale) {
+                debug!("Cached response revalidated");
+                cached.stored_at = chrono::Utc::now();
+                cache.store(&key, cached.clone());
+                return Ok(cached.into_response());
+            }
+            // Responses setting cookies carry the session and must reach the caller.
+            if !response.status().is_success() || response.headers().contains_key(SET_COOKIE) {
+                return Ok(response);
+            }
+
+            let cached = CachedResponse::read(response).await?;
+            cache.store(&key, cached.clone());
+            Ok(cached.into_response())
+        })
+    }
+}
+
+/// Wraps every request in a `discuit.request` span recording the method,
+/// path, status, latency and number of retries.
+/// For internal use only.
+#[derive(Debug)]
+pub(crate) struct LoggingLayer;
+
+impl Middleware for LoggingLayer {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+        let span = info_span!(
+            "discuit.request",
+            method = %request.method(),
+            path = %request.url().path(),
+            status = field::Empty,
+            latency_ms = field::Empty,
+            retries = field::Empty,
+        );
+
+        Box::pin(
+            async move {
+                let started = Instant::now();
+                let result = next.run(request).await;
+
+                let span = Span::current();
+                span.record("latency_ms", started.elapsed().as_millis() as u64);
+                match &result {
+                    Ok(response) => {
+                        span.record("status", response.status().as_u16());
+                        debug!("Request completed");
+                    }
+                    Err(e) => warn!(error = %e, "Request failed"),
+                }
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// The delay before the first retry, doubled on every further retry.
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest delay between two retries with exponential backoff.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Retries reads up to `max_retries` times on connection errors, timeouts and
+/// `429`/`502`/`503`/`504` responses, with exponential backoff or after the
+/// `Retry-After` of a `429` unless it's longer than `MAX_RETRY_DELAY`; writes
+/// are never retried.
+/// For internal use only.
+#[derive(Debug)]
+pub(crate) struct RetryLayer<'a> {
+    pub(crate) max_retries: u32,
+    pub(crate) metrics: Option<&'a Metrics>,
+}
+
+impl Middleware for RetryLayer<'_> {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let max_retries = match request.method() {
+                &Method::GET => self.max_retries,
+                _ => 0,
+            };
+
+            let mut retries = 0;
+            let result = loop {
+                let attempt = match request.try_clone() {
+                    Some(attempt) if retries < max_retries => attempt,
+                    // Bodies that can't be cloned can't be retried either, so the
+                    // original request is sent on the last attempt.
+                    _ => break next.run(request).await,
+                };
+
+                let delay = match next.run(attempt).await {
+                    Ok(response) => match retry_delay(&response, retries) {
+                        Some(delay) => {
+                            warn!(status = response.status().as_u16(), "Retrying request");
+                            delay
+                        }
+                        None => break Ok(response),
+                    },
+                    Err(e) if e.is_connect() || e.is_timeout() => {
+                        warn!(error = %e, "Retrying request");
+                        backoff(retries)
+                    }
+                    Err(e) => break Err(e),
+                };
+
+                if let Some(metrics) = self.metrics {
+                    metrics.record_retry(request.method(), request.url().path());
+                }
+                tokio::time::sleep(delay).await;
+                retries += 1;
+            };
+
+            Span::current().record("retries", retries);
+            result
+        })
+    }
+}
+
+/// Returns how long to wait before retrying a request that got the given
+/// response after `retries` retries, or `None` if the request should not be
+/// retried. The `Retry-After` of a `429` is honored as sent, but a `429`
+/// asking to wait longer than `MAX_RETRY_DELAY` is returned to the caller.
+/// For internal use only.
+fn retry_delay(response: &Response, retries: u32) -> Option<Duration> {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, Utc::now()));
+            match retry_after {
+                Some(delay) if delay > MAX_RETRY_DELAY => None,
+                Some(delay) => Some(delay),
+                None => Some(backoff(retries)),
+            }
+        }
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+            Some(backoff(retries))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a `Retry-After` value, either a number of seconds or an HTTP date,
+/// into the delay from `now`. Dates in the past mean no delay.
+/// For internal use only.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Returns the exponential backoff after `retries` retries, capped at
+/// `MAX_RETRY_DELAY`.
+/// For internal use only.
+fn backoff(retries: u32) -> Duration {
+    2u32.checked_pow(retries)
+        .and_then(|factor| BASE_RETRY_DELAY.checked_mul(factor))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// Waits for the `RateLimiter` before every attempt of a request.
+/// For internal use only.
+#[derive(Debug)]
+pub(crate) struct RateLimitLayer<'a> {
+    pub(crate) rate_limiter: &'a RateLimiter,
+    pub(crate) metrics: Option<&'a Metrics>,
+}
+
+impl Middleware for RateLimitLayer<'_> {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let kind = if request.method() == Method::GET {
+                RequestKind::Read
+            } else {
+                RequestKind::Write
+            };
+            let waited = self.rate_limiter.acquire(kind).await;
+            if !waited.is_zero() {
+                debug!(waited_ms = waited.as_millis() as u64, "Rate limited");
+                if let Some(metrics) = self.metrics {
+                    metrics.record_rate_limit_wait(kind, waited);
+                }
+            }
+            next.run(request).await
+        })
+    }
+}
+
+/// Records the status and latency of every attempt of a request in `Metrics`.
+/// For internal use only.
+#[derive(Debug)]
+pub(crate) struct MetricsLayer<'a> {
+    pub(crate) metrics: &'a Metrics,
+}
+
+impl Middleware for MetricsLayer<'_> {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let method = request.method().clone();
+            let path = request.url().path().to_string();
+            let started = Instant::now();
+            let result = next.run(request).await;
+            let status = result.as_ref().ok().map(Response::status);
+            self.metrics
+                .record_request(&method, &path, status, started.elapsed());
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::DiscuitClient;
+    use crate::mock_server::{MockResponse, MockServer};
+    use crate::structs::api_types::PublicPostId;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug, Default)]
+    struct Tag {
+        responses: Arc<AtomicUsize>,
+    }
+
+    impl Middleware for Tag {
+        fn before_request(&self, request: &mut Request) {
+            request
+                .headers_mut()
+                .insert("X-Tag", HeaderValue::from_static("test"));
+        }
+
+        fn after_response(&self, _request: &RequestInfo, response: Response) -> Response {
+            self.responses.fetch_add(1, Ordering::SeqCst);
+            response
+        }
+    }
+
+    /// Answers the first request with a `503` without sending it.
+    #[derive(Debug, Default)]
+    struct FailOnce {
+        failed: AtomicUsize,
+    }
+
+    impl Middleware for FailOnce {
+        fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> MiddlewareFuture<'a> {
+            if self.failed.fetch_add(1, Ordering::SeqCst) == 0 {
+                let response = http::Response::builder().status(503).body("").unwrap();
+                return Box::pin(async { Ok(Response::from(response)) });
+            }
+            next.run(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        let post = include_str!("structs/payloads/post.json");
+        let server = MockServer::start(move |_| MockResponse::json(200, post)).await;
+        let responses = Arc::new(AtomicUsize::new(0));
+
+        let client = DiscuitClient::builder(&server.url)
+            .middleware(Tag {
+                responses: responses.clone(),
+            })
+            .build()
+            .unwrap();
+        client
+            .get_post(&PublicPostId::from("Gq4XzLbA"))
+            .await
+            .unwrap();
+        assert_eq!(server.requests()[0].header("X-Tag"), Some("test"));
+        assert_eq!(responses.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(backoff(0), BASE_RETRY_DELAY);
+        assert_eq!(backoff(3), Duration::from_secs(4));
+        assert_eq!(backoff(32), MAX_RETRY_DELAY);
+        assert_eq!(backoff(u32::MAX), MAX_RETRY_DELAY);
+
+        let response = |status: u16, retry_after: Option<&str>| {
+            let mut response = http::Response::builder().status(status);
+            if let Some(retry_after) = retry_after {
+                response = response.header(RETRY_AFTER, retry_after);
+            }
+            Response::from(response.body("").unwrap())
+        };
+        // `Retry-After` isn't multiplied by the backoff.
+        assert_eq!(
+            retry_delay(&response(429, Some("7")), 3),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(retry_delay(&response(429, None), 1), Some(backoff(1)));
+        assert_eq!(
+            retry_delay(&response(429, Some("soon")), 1),
+            Some(backoff(1))
+        );
+        // A `Retry-After` longer than the longest delay isn't waited for.
+        assert_eq!(retry_delay(&response(429, Some("3600")), 0), None);
+        assert_eq!(retry_delay(&response(503, None), 40), Some(MAX_RETRY_DELAY));
+        assert_eq!(retry_delay(&response(500, None), 0), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = "2024-05-01T10:00:00Z".parse().unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 01 May 2024 10:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 01 May 2024 09:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("-1", now), None);
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_injected_faults_are_retried() {
+        let post = include_str!("structs/payloads/post.json");
+        let server = MockServer::start(move |_| MockResponse::json(200, post)).await;
+        let client = DiscuitClient::builder(&server.url)
+            .max_retries(1)
+            .middleware(FailOnce::default())
+            .build()
+            .unwrap();
+
+        let public_id = PublicPostId::from("Gq4XzLbA");
+        let post = client.get_post(&public_id).await.unwrap();
+        assert_eq!(post.public_id, public_id);
+        assert_eq!(server.requests().len(), 1);
+    }
+}
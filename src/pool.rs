@@ -0,0 +1,256 @@
+//! This module contains `AccountPool`, which holds the clients of several
+//! accounts on the same instance.
+//!
+//! Every account gets a `DiscuitClient` of its own, built from the same
+//! `DiscuitClientBuilder`, so each has its own session and cookie jar and no
+//! cookies leak between accounts. Reads can be spread over the accounts with
+//! `AccountPool::rotate`, while writes are made with the client of a chosen
+//! account from `AccountPool::get`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::pool::AccountPool;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), discuit_rs::error::Error> {
+//! let mut pool = AccountPool::new(DiscuitClient::builder("https://discuit.net"))?;
+//! pool.login("modbot", "hunter2").await?;
+//! pool.login("welcomebot", "hunter3").await?;
+//!
+//! // Reads rotate between the accounts.
+//! let posts = pool.rotate().unwrap().get_posts_page(None, Some("general"), None).await?;
+//!
+//! // Writes are pinned to one.
+//! let post = &posts.posts[0];
+//! pool.get("welcomebot").unwrap().vote_post(post, true).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::builder::DiscuitClientBuilder;
+use crate::client::DiscuitClient;
+use crate::error::Error;
+use crate::structs::api_responses::{APIError, UserResponse};
+use crate::structs::internal_types::Session;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::info;
+
+/// `AccountPool` holds one authenticated `DiscuitClient` per account, keyed by
+/// username.
+///
+/// Cloning the pool is cheap; clones share the clients and the rotation of
+/// `rotate`, but accounts added to one clone aren't added to the others.
+#[derive(Clone, Debug)]
+pub struct AccountPool {
+    builder: DiscuitClientBuilder,
+    /// The accounts in the order they were added.
+    accounts: Vec<(String, DiscuitClient)>,
+    /// The index of the account `rotate` returns, modulo the number of accounts.
+    next: Arc<AtomicUsize>,
+}
+
+impl AccountPool {
+    /// Create a new, empty `AccountPool` whose clients are built with the
+    /// given builder.
+    ///
+    /// Clients built from one builder share its rate limiter and cache, so
+    /// the limits apply to the pool as a whole.
+    ///
+    /// Returns an error if a prebuilt HTTP client was passed to
+    /// `DiscuitClientBuilder::http_client`, as every account would share its
+    /// cookie jar and so its session.
+    pub fn new(builder: DiscuitClientBuilder) -> Result<Self, Error> {
+        if builder.has_http_client() {
+            return Err(Error::Api(APIError {
+                status: 400,
+                code: None,
+                message: "The accounts of a pool can't share a prebuilt HTTP client.".to_string(),
+            }));
+        }
+        Ok(Self {
+            builder,
+            accounts: Vec::new(),
+            next: Arc::default(),
+        })
+    }
+
+    /// Log in to an account with a client of its own and add it to the pool,
+    /// replacing an earlier client of the same account.
+    /// Returns the client of the account.
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<&DiscuitClient, Error> {
+        let client = self.builder.clone().build()?;
+        client.initialize().await?;
+        match client.login(username, password).await? {
+            UserResponse::User(_) => {}
+            UserResponse::Error(e) => return Err(Error::Api(e)),
+        }
+
+        info!(username, "Added account to the pool.");
+        Ok(self.insert(username.to_string(), client))
+    }
+
+    /// Add an account from a session saved earlier with `sessions`, replacing
+    /// an earlier client of the same account.
+    /// Returns the client of the account, or `None` if the session isn't
+    /// authenticated.
+    pub fn restore(&mut self, session: Session) -> Result<Option<&DiscuitClient>, Error> {
+        let Some(username) = session.user.as_ref().map(|user| user.username.clone()) else {
+            return Ok(None);
+        };
+        let client = self.builder.clone().build()?;
+        client.set_session(session);
+        Ok(Some(self.insert(username, client)))
+    }
+
+    /// Adds a client under the given username.
+    /// For internal use only.
+    fn insert(&mut self, username: String, client: DiscuitClient) -> &DiscuitClient {
+        let index = match self.accounts.iter().position(|(name, _)| *name == username) {
+            Some(index) => {
+                self.accounts[index].1 = client;
+                index
+            }
+            None => {
+                self.accounts.push((username, client));
+                self.accounts.len() - 1
+            }
+        };
+        &self.accounts[index].1
+    }
+
+    /// Removes an account from the pool, returning its client.
+    /// The client stays logged in; call `logout` on it to end the session.
+    pub fn remove(&mut self, username: &str) -> Option<DiscuitClient> {
+        let index = self
+            .accounts
+            .iter()
+            .position(|(name, _)| name == username)?;
+        Some(self.accounts.remove(index).1)
+    }
+
+    /// Returns the client of the given account, e.g. to make a write as that
+    /// account.
+    pub fn get(&self, username: &str) -> Option<&DiscuitClient> {
+        self.accounts
+            .iter()
+            .find(|(name, _)| name == username)
+            .map(|(_, client)| client)
+    }
+
+    /// Returns the client of the next account in turn, to spread reads over
+    /// all accounts. Returns `None` if the pool is empty.
+    pub fn rotate(&self) -> Option<&DiscuitClient> {
+        if self.accounts.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.accounts.len();
+        Some(&self.accounts[index].1)
+    }
+
+    /// Returns the usernames of the accounts in the order they were added.
+    pub fn usernames(&self) -> impl Iterator<Item = &str> {
+        self.accounts.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the number of accounts in the pool.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Returns true if the pool has no accounts.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Returns the sessions of all accounts, keyed by username, so they can be
+    /// saved and restored later with `restore`.
+    pub fn sessions(&self) -> HashMap<String, Session> {
+        self.accounts
+            .iter()
+            .map(|(name, client)| (name.clone(), client.session()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+
+    async fn server() -> MockServer {
+        let sessions = AtomicUsize::new(0);
+        MockServer::start(move |request| match request.path.as_str() {
+            "/api/_initial" => {
+                let sid = sessions.fetch_add(1, Ordering::Relaxed);
+                MockResponse::json(200, include_str!("structs/payloads/initial.json"))
+                    .header("Set-Cookie", &format!("SID=sid-{}; Path=/", sid))
+            }
+            "/api/_login" => {
+                let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+                let user = fixtures::user(body["username"].as_str().unwrap());
+                MockResponse::json(200, &user.to_string())
+            }
+            _ => MockResponse::json(200, include_str!("structs/payloads/post_feed.json")),
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_accounts_have_separate_sessions() {
+        let server = server().await;
+        let mut pool = AccountPool::new(DiscuitClient::builder(&server.url)).unwrap();
+        pool.login("modbot", "a").await.unwrap();
+        pool.login("welcomebot", "b").await.unwrap();
+
+        assert_eq!(
+            pool.usernames().collect::<Vec<_>>(),
+            ["modbot", "welcomebot"]
+        );
+        let modbot = pool.get("modbot").unwrap();
+        let welcomebot = pool.get("welcomebot").unwrap();
+        assert_eq!(modbot.user().unwrap().username, "modbot");
+        assert_eq!(welcomebot.user().unwrap().username, "welcomebot");
+        assert_ne!(modbot.session_id(), welcomebot.session_id());
+
+        welcomebot.get_posts_page(None, None, None).await.unwrap();
+        let request = server.requests().pop().unwrap();
+        let cookie = format!("SID={}", welcomebot.session_id());
+        assert!(request.header("Cookie").unwrap().contains(&cookie));
+    }
+
+    #[test]
+    fn test_shared_http_client_is_rejected() {
+        let builder =
+            DiscuitClient::builder("https://discuit.net").http_client(reqwest::Client::new());
+        let Err(Error::Api(e)) = AccountPool::new(builder) else {
+            panic!("a pool with a shared HTTP client should be rejected");
+        };
+        assert_eq!(e.status, 400);
+    }
+
+    #[tokio::test]
+    async fn test_rotate() {
+        let server = server().await;
+        let mut pool = AccountPool::new(DiscuitClient::builder(&server.url)).unwrap();
+        assert!(pool.rotate().is_none());
+        for username in ["a", "b", "c"] {
+            pool.login(username, "password").await.unwrap();
+        }
+
+        let order: Vec<_> = (0..4)
+            .map(|_| pool.rotate().unwrap().user().unwrap().username)
+            .collect();
+        assert_eq!(order, ["a", "b", "c", "a"]);
+
+        let session = pool.remove("b").unwrap().session();
+        assert_eq!(pool.len(), 2);
+        pool.restore(session).unwrap();
+        assert_eq!(pool.sessions().len(), 3);
+        assert_eq!(pool.get("b").unwrap().user().unwrap().username, "b");
+    }
+}
@@ -0,0 +1,219 @@
+//! This module contains the client-side rate limiter used by `DiscuitClient`.
+//!
+//! The limiter is a pair of token buckets, one for reads and one for writes
+//! (posting, commenting, voting, ...). Acquiring a token waits until one is
+//! available, so calls queue up instead of failing when the limit is reached.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// `RateLimit` configures a single token bucket.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateLimit {
+    /// The number of tokens added to the bucket every second.
+    pub requests_per_second: f64,
+    /// The maximum number of tokens the bucket can hold, i.e. how many requests
+    /// can be made back to back before the client starts waiting.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Create a new `RateLimit` allowing `requests_per_second` requests on average,
+    /// with bursts of up to `burst` requests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not a positive number or if `burst` is zero.
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        assert!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "requests_per_second must be a positive number"
+        );
+        assert!(burst > 0, "burst must be at least 1");
+
+        Self {
+            requests_per_second,
+            burst,
+        }
+    }
+}
+
+/// `RequestKind` is used to pick the bucket a request draws from.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum RequestKind {
+    /// A request that only reads data, e.g. fetching posts or users.
+    Read,
+    /// A request that changes data, e.g. posting, commenting or voting.
+    Write,
+}
+
+/// `RateLimiter` holds the read and write token buckets of a client.
+///
+/// The limiter is cheap to clone; clones share the same buckets, so a single
+/// limiter can throttle any number of clients and tasks.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    read: Option<Arc<TokenBucket>>,
+    write: Option<Arc<TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Create a new `RateLimiter` with the given limits for reads and writes.
+    pub fn new(reads: RateLimit, writes: RateLimit) -> Self {
+        Self {
+            read: Some(Arc::new(TokenBucket::new(reads))),
+            write: Some(Arc::new(TokenBucket::new(writes))),
+        }
+    }
+
+    /// Create a new `RateLimiter` that only limits reads.
+    pub fn reads_only(reads: RateLimit) -> Self {
+        Self {
+            read: Some(Arc::new(TokenBucket::new(reads))),
+            write: None,
+        }
+    }
+
+    /// Create a new `RateLimiter` that only limits writes.
+    pub fn writes_only(writes: RateLimit) -> Self {
+        Self {
+            read: None,
+            write: Some(Arc::new(TokenBucket::new(writes))),
+        }
+    }
+
+    /// Returns the limit applied to requests of the given kind, if any.
+    pub fn limit(&self, kind: RequestKind) -> Option<RateLimit> {
+        self.bucket(kind).map(|bucket| bucket.limit)
+    }
+
+    /// Wait until a request of the given kind is allowed to be sent.
+    /// Returns how long the caller had to wait.
+    pub async fn acquire(&self, kind: RequestKind) -> Duration {
+        match self.bucket(kind) {
+            Some(bucket) => bucket.acquire().await,
+            None => Duration::ZERO,
+        }
+    }
+
+    fn bucket(&self, kind: RequestKind) -> Option<&Arc<TokenBucket>> {
+        match kind {
+            RequestKind::Read => self.read.as_ref(),
+            RequestKind::Write => self.write.as_ref(),
+        }
+    }
+}
+
+/// `TokenBucket` is a single token bucket.
+/// For internal use only.
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(BucketState {
+                tokens: limit.burst as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    async fn acquire(&self) -> Duration {
+        // The lock is held while sleeping. `tokio::sync::Mutex` is fair, so
+        // waiting callers are served in the order they arrived.
+        let mut state = self.state.lock().await;
+        let started = Instant::now();
+
+        self.refill(&mut state);
+        if state.tokens < 1.0 {
+            let missing = 1.0 - state.tokens;
+            let wait = Duration::from_secs_f64(missing / self.limit.requests_per_second);
+            tokio::time::sleep(wait).await;
+            self.refill(&mut state);
+        }
+
+        state.tokens = (state.tokens - 1.0).max(0.0);
+        started.elapsed()
+    }
+
+    fn refill(&self, state: &mut BucketState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens =
+            (state.tokens + elapsed * self.limit.requests_per_second).min(self.limit.burst as f64);
+        state.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_then_wait() {
+        let limiter = RateLimiter::new(RateLimit::new(2.0, 3), RateLimit::new(1.0, 1));
+
+        for _ in 0..3 {
+            assert_eq!(limiter.acquire(RequestKind::Read).await, Duration::ZERO);
+        }
+
+        let waited = limiter.acquire(RequestKind::Read).await;
+        assert_eq!(waited, Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reads_and_writes_use_separate_buckets() {
+        let limiter = RateLimiter::new(RateLimit::new(1.0, 1), RateLimit::new(1.0, 1));
+
+        assert_eq!(limiter.acquire(RequestKind::Read).await, Duration::ZERO);
+        assert_eq!(limiter.acquire(RequestKind::Write).await, Duration::ZERO);
+        assert_eq!(
+            limiter.acquire(RequestKind::Write).await,
+            Duration::from_secs(1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clones_share_buckets() {
+        let limiter = RateLimiter::writes_only(RateLimit::new(4.0, 1));
+        let clone = limiter.clone();
+
+        assert_eq!(limiter.acquire(RequestKind::Write).await, Duration::ZERO);
+        assert_eq!(
+            clone.acquire(RequestKind::Write).await,
+            Duration::from_millis(250)
+        );
+        assert_eq!(clone.acquire(RequestKind::Read).await, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waiters_queue() {
+        let limiter = RateLimiter::reads_only(RateLimit::new(10.0, 1));
+        let start = Instant::now();
+
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire(RequestKind::Read).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(start.elapsed(), Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,311 @@
+//! This module contains the schema drift detection used by `DiscuitClient`.
+//!
+//! Discuit evolves faster than the structs of this crate, and serde silently
+//! drops any field a struct doesn't know about. With drift detection enabled,
+//! every decoded response is encoded again and compared to the JSON that was
+//! received; fields that were lost on the way are unknown to the structs.
+//!
+//! In `SchemaMode::Capture` the unknown fields are logged and counted in a
+//! `DriftReport`; in `SchemaMode::Strict` decoding a response with unknown
+//! fields fails instead, which is meant for compatibility checks in CI.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::schema::SchemaMode;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = DiscuitClient::builder("https://discuit.net")
+//!     .schema_mode(SchemaMode::Capture)
+//!     .build()
+//!     .unwrap();
+//! client.get_posts_page(None, Some("general"), None).await.unwrap();
+//!
+//! for field in client.schema_drift.report().fields {
+//!     println!("{} has an unknown field `{}`", field.type_name, field.path);
+//! }
+//! # }
+//! ```
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::{debug, warn};
+
+/// `SchemaMode` configures how unknown fields in API responses are handled.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum SchemaMode {
+    /// Unknown fields are ignored, like serde does by default.
+    #[default]
+    Ignore,
+    /// Unknown fields are logged and counted in the `DriftReport`.
+    Capture,
+    /// Unknown fields are counted in the `DriftReport` and make decoding fail.
+    Strict,
+}
+
+/// `UnknownField` is a field of an API response that no struct knows about.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct UnknownField {
+    /// The name of the response type the field was found in, e.g. `PostFeedResponse`.
+    pub type_name: String,
+    /// The path of the field within the response, e.g. `posts[].community.newField`.
+    /// Array indices are written as `[]`, so a field is reported once per type.
+    pub path: String,
+    /// How many times the field was seen.
+    pub count: u64,
+}
+
+/// `DriftReport` lists the unknown fields found in the decoded responses.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct DriftReport {
+    /// The unknown fields, ordered by type name and path.
+    pub fields: Vec<UnknownField>,
+}
+
+impl DriftReport {
+    /// Returns true if no unknown fields were found.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.fields {
+            writeln!(f, "{}: {} ({}x)", field.type_name, field.path, field.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// `SchemaDrift` checks decoded responses for unknown fields and collects them.
+///
+/// It is cheap to clone; clones share the same report, so the drift of every
+/// clone of a client ends up in one place.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaDrift {
+    mode: SchemaMode,
+    /// The number of times each unknown field was seen, keyed by type name and path.
+    seen: Arc<Mutex<BTreeMap<(String, String), u64>>>,
+}
+
+impl SchemaDrift {
+    /// Create a new `SchemaDrift` with the given mode.
+    pub fn new(mode: SchemaMode) -> Self {
+        Self {
+            mode,
+            seen: Arc::default(),
+        }
+    }
+
+    /// Returns the mode unknown fields are handled with.
+    pub fn mode(&self) -> SchemaMode {
+        self.mode
+    }
+
+    /// Returns the unknown fields found so far.
+    pub fn report(&self) -> DriftReport {
+        let fields = self
+            .lock()
+            .iter()
+            .map(|((type_name, path), count)| UnknownField {
+                type_name: type_name.clone(),
+                path: path.clone(),
+                count: *count,
+            })
+            .collect();
+        DriftReport { fields }
+    }
+
+    /// Forgets the unknown fields found so far.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Locks the collected fields.
+    /// For internal use only.
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<(String, String), u64>> {
+        // The map only holds counters, so it is still usable if a thread
+        // panicked while holding the lock.
+        self.seen.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Decodes a response body, checking it for unknown fields unless the
+    /// mode is `SchemaMode::Ignore`.
+    /// For internal use only.
+    pub(crate) fn decode<T>(&self, text: &str) -> Result<T, serde_json::Error>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        if self.mode == SchemaMode::Ignore {
+            return serde_json::from_str(text);
+        }
+
+        let received: Value = serde_json::from_str(text)?;
+        let decoded = T::deserialize(&received)?;
+        let encoded = serde_json::to_value(&decoded)?;
+
+        let mut paths = Vec::new();
+        unknown_fields(&received, &encoded, "", &mut paths);
+        if paths.is_empty() {
+            return Ok(decoded);
+        }
+
+        let type_name = short_type_name(std::any::type_name::<T>());
+        self.record(&type_name, &paths);
+        if self.mode == SchemaMode::Strict {
+            return Err(serde_json::Error::custom(format!(
+                "unknown fields in {}: {}",
+                type_name,
+                paths.join(", ")
+            )));
+        }
+        Ok(decoded)
+    }
+
+    /// Counts the unknown fields of a response, logging the ones seen for the
+    /// first time as warnings.
+    /// For internal use only.
+    fn record(&self, type_name: &str, paths: &[String]) {
+        let mut seen = self.lock();
+        for path in paths {
+            let count = seen
+                .entry((type_name.to_string(), path.clone()))
+                .or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                warn!(type_name, path, "Unknown field in API response.");
+            } else {
+                debug!(type_name, path, "Unknown field in API response.");
+            }
+        }
+    }
+}
+
+/// Collects the paths of the fields of `received` that are missing in `encoded`.
+/// The paths are deduplicated, since the items of an array share a path.
+/// For internal use only.
+fn unknown_fields(received: &Value, encoded: &Value, path: &str, paths: &mut Vec<String>) {
+    match (received, encoded) {
+        (Value::Object(received), Value::Object(encoded)) => {
+            for (key, value) in received {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match encoded.get(key) {
+                    Some(encoded) => unknown_fields(value, encoded, &field_path, paths),
+                    None if !paths.contains(&field_path) => paths.push(field_path),
+                    None => {}
+                }
+            }
+        }
+        (Value::Array(received), Value::Array(encoded)) => {
+            let item_path = format!("{}[]", path);
+            for (value, encoded) in received.iter().zip(encoded) {
+                unknown_fields(value, encoded, &item_path, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Strips the module paths from a type name, so
+/// `alloc::vec::Vec<discuit_rs::structs::api_types::List>` becomes `Vec<List>`.
+/// For internal use only.
+fn short_type_name(name: &str) -> String {
+    let mut short = String::with_capacity(name.len());
+    let mut segment = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            segment.clear();
+        } else if c.is_alphanumeric() || c == '_' {
+            segment.push(c);
+        } else {
+            short.push_str(&segment);
+            segment.clear();
+            short.push(c);
+        }
+    }
+    short.push_str(&segment);
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::api_responses::*;
+    use crate::structs::api_types::*;
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(
+            short_type_name(std::any::type_name::<Vec<List>>()),
+            "Vec<List>"
+        );
+        assert_eq!(
+            short_type_name(std::any::type_name::<PostFeedResponse>()),
+            "PostFeedResponse"
+        );
+    }
+
+    #[test]
+    fn test_known_payloads_have_no_drift() {
+        let drift = SchemaDrift::new(SchemaMode::Strict);
+        drift
+            .decode::<PostFeedResponse>(include_str!("structs/payloads/post_feed.json"))
+            .unwrap();
+        drift
+            .decode::<FeedResponse>(include_str!("structs/payloads/user_feed.json"))
+            .unwrap();
+        drift
+            .decode::<InitialResponse>(include_str!("structs/payloads/initial.json"))
+            .unwrap();
+        assert!(drift.report().is_empty());
+    }
+
+    #[test]
+    fn test_capture_and_strict() {
+        let mut community: Value =
+            serde_json::from_str(include_str!("structs/payloads/community.json")).unwrap();
+        community["flairs"] = Value::Array(Vec::new());
+        community["rules"][0]["severity"] = Value::from(2);
+        let text = community.to_string();
+
+        let drift = SchemaDrift::new(SchemaMode::Capture);
+        let decoded: Community = drift.decode(&text).unwrap();
+        assert_eq!(decoded.name, "DiscuitMeta");
+        drift.decode::<Community>(&text).unwrap();
+
+        let report = drift.report();
+        let fields: Vec<_> = report
+            .fields
+            .iter()
+            .map(|field| (field.type_name.as_str(), field.path.as_str(), field.count))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("Community", "flairs", 2),
+                ("Community", "rules[].severity", 2)
+            ]
+        );
+
+        drift.clear();
+        assert!(drift.report().is_empty());
+
+        let strict = SchemaDrift::new(SchemaMode::Strict);
+        let error = strict.decode::<Community>(&text).unwrap_err();
+        assert!(error.to_string().contains("flairs"));
+        assert_eq!(strict.report().fields.len(), 2);
+    }
+}
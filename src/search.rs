@@ -0,0 +1,466 @@
+//! This module contains `Search`, which pages through the results of a search
+//! for posts, communities or users.
+//!
+//! Searches use the instance's `/api/search` endpoint. Instances without one
+//! get a client-side fallback instead:
+//!
+//! - posts are searched for in a bounded crawl of the newest posts, sitewide
+//!   or of one community;
+//! - communities are matched by name prefix against the list of communities;
+//! - users are looked up by their exact username.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::client::DiscuitClient;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), discuit_rs::error::Error> {
+//! let client = DiscuitClient::new("https://discuit.net");
+//!
+//! let mut search = client.search_posts("rust async").community("programming");
+//! while let Some(posts) = search.next_page().await {
+//!     for post in posts? {
+//!         println!("{}", post.title);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::DiscuitClient;
+use crate::error::Error;
+use crate::structs::api_responses::{APIError, UserResponse};
+use crate::structs::api_types::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use tracing::{debug, info};
+
+/// The number of pages of posts crawled by the fallback when none is configured.
+const DEFAULT_MAX_PAGES: usize = 10;
+
+/// The future returned by `Searchable::crawl`: a page of candidates and the
+/// cursor of the next page, if there is one.
+pub type CrawlFuture<T> =
+    Pin<Box<dyn Future<Output = Result<(Vec<T>, Option<String>), Error>> + Send>>;
+
+/// `Searchable` is implemented by the types that can be searched for.
+pub trait Searchable: DeserializeOwned + Serialize + Send + Sized + 'static {
+    /// The value of the `type` parameter of `/api/search`, e.g. "posts".
+    const KIND: &'static str;
+
+    /// Returns true if the item matches the query in a client-side search.
+    fn matches(&self, query: &str) -> bool;
+
+    /// Fetches a page of candidates for a client-side search, to be filtered
+    /// with `matches`. Pass the cursor returned with the previous page to
+    /// fetch the next one.
+    fn crawl(
+        client: DiscuitClient,
+        query: String,
+        community: Option<String>,
+        next: Option<String>,
+    ) -> CrawlFuture<Self>;
+}
+
+impl Searchable for Post {
+    const KIND: &'static str = "posts";
+
+    /// Every word of the query has to appear in the title or the body,
+    /// ignoring case.
+    fn matches(&self, query: &str) -> bool {
+        let title = self.title.to_lowercase();
+        let body = self.body.as_deref().unwrap_or_default().to_lowercase();
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| title.contains(word) || body.contains(word))
+    }
+
+    /// Crawls the newest posts, sitewide or of the community.
+    fn crawl(
+        client: DiscuitClient,
+        _query: String,
+        community: Option<String>,
+        next: Option<String>,
+    ) -> CrawlFuture<Self> {
+        Box::pin(async move {
+            let page = client
+                .get_posts_page(Some("new"), community.as_deref(), next.as_deref())
+                .await?;
+            Ok((page.posts, page.next))
+        })
+    }
+}
+
+impl Searchable for Community {
+    const KIND: &'static str = "communities";
+
+    /// The name has to start with the query, ignoring case.
+    fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        self.name.to_lowercase().starts_with(&query)
+    }
+
+    /// Fetches every community in one page.
+    fn crawl(
+        client: DiscuitClient,
+        _query: String,
+        _community: Option<String>,
+        _next: Option<String>,
+    ) -> CrawlFuture<Self> {
+        Box::pin(async move { Ok((client.get_communities().await?, None)) })
+    }
+}
+
+impl Searchable for User {
+    const KIND: &'static str = "users";
+
+    /// The username has to start with the query, ignoring case.
+    fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        self.username.to_lowercase().starts_with(&query)
+    }
+
+    /// Users can't be listed, so this looks up the user with the query as
+    /// their username.
+    fn crawl(
+        client: DiscuitClient,
+        query: String,
+        _community: Option<String>,
+        _next: Option<String>,
+    ) -> CrawlFuture<Self> {
+        Box::pin(async move {
+            let query = query.trim();
+            if query.is_empty() {
+                return Ok((Vec::new(), None));
+            }
+            match client.get_user_by_username(query).await? {
+                UserResponse::User(user) => Ok((vec![user], None)),
+                UserResponse::Error(e) if e.status == 404 => Ok((Vec::new(), None)),
+                UserResponse::Error(e) => Err(Error::Api(e)),
+            }
+        })
+    }
+}
+
+/// `SearchMode` selects between server-side and client-side search.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum SearchMode {
+    /// Use the instance's search, falling back to a client-side search if it
+    /// has none.
+    #[default]
+    Auto,
+    /// Only use the instance's search. Searching an instance without one
+    /// fails with a `404` API error.
+    Server,
+    /// Only search client-side, even if the instance has a search.
+    Client,
+}
+
+/// Where a `Search` is at.
+#[derive(Clone, Debug)]
+enum State {
+    /// Nothing was fetched yet.
+    Start,
+    /// Paging through the instance's search, with the cursor of the next page.
+    Server(String),
+    /// Crawling client-side, with the cursor of the next page and the number
+    /// of pages crawled so far.
+    Crawl { next: Option<String>, pages: usize },
+    /// All results were returned.
+    Done,
+}
+
+/// `Search` pages through the results of a search, created with
+/// `DiscuitClient::search_posts`, `search_communities` or `search_users`.
+#[derive(Debug)]
+pub struct Search<T> {
+    client: DiscuitClient,
+    query: String,
+    community: Option<String>,
+    mode: SearchMode,
+    max_pages: usize,
+    state: State,
+    fallback: bool,
+    results: PhantomData<fn() -> T>,
+}
+
+impl<T: Searchable> Search<T> {
+    /// Create a new search for the given query.
+    pub fn new(client: DiscuitClient, query: &str) -> Self {
+        Self {
+            client,
+            query: query.to_string(),
+            community: None,
+            mode: SearchMode::default(),
+            max_pages: DEFAULT_MAX_PAGES,
+            state: State::Start,
+            fallback: false,
+            results: PhantomData,
+        }
+    }
+
+    /// Only search the community with the given name. Only applies to posts.
+    pub fn community(mut self, name: &str) -> Self {
+        self.community = Some(name.to_string());
+        self
+    }
+
+    /// Set whether to search server-side, client-side or both.
+    /// Defaults to `SearchMode::Auto`.
+    pub fn mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the maximum number of pages of posts a client-side search crawls.
+    /// Defaults to 10.
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Returns true if the results come from a client-side search, which only
+    /// covers what was crawled. Only known once the first page was fetched.
+    pub fn is_fallback(&self) -> bool {
+        self.fallback
+    }
+
+    /// Fetch the next page of results.
+    /// Returns `None` once all results were returned. Pages of a client-side
+    /// search without matches are skipped.
+    pub async fn next_page(&mut self) -> Option<Result<Vec<T>, Error>> {
+        loop {
+            let page = match std::mem::replace(&mut self.state, State::Done) {
+                State::Done => return None,
+                State::Start if self.mode == SearchMode::Client => {
+                    self.start_crawl();
+                    continue;
+                }
+                State::Start => self.server_page(None).await,
+                State::Server(next) => self.server_page(Some(next)).await,
+                State::Crawl { next, pages } => self.crawl_page(next, pages).await,
+            };
+            match page {
+                Ok(Some(results)) => return Some(Ok(results)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Switches to a client-side search.
+    /// For internal use only.
+    fn start_crawl(&mut self) {
+        self.fallback = true;
+        self.state = State::Crawl {
+            next: None,
+            pages: 0,
+        };
+    }
+
+    /// Fetches a page of the instance's search, falling back to a client-side
+    /// search if there is none. Returns `None` if there is nothing to return yet.
+    /// For internal use only.
+    async fn server_page(&mut self, next: Option<String>) -> Result<Option<Vec<T>>, Error> {
+        let page = self
+            .client
+            .search_page::<T>(&self.query, self.community.as_deref(), next.as_deref())
+            .await?;
+
+        match page {
+            Some(page) => {
+                if let Some(next) = page.next {
+                    self.state = State::Server(next);
+                }
+                Ok(Some(page.results.unwrap_or_default()))
+            }
+            None if self.mode == SearchMode::Server => Err(Error::Api(APIError {
+                status: 404,
+                code: None,
+                message: "The instance doesn't support search.".to_string(),
+            })),
+            None => {
+                info!(
+                    kind = T::KIND,
+                    "No server-side search, searching client-side."
+                );
+                self.start_crawl();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Crawls a page of candidates and filters it.
+    /// Returns `None` if there is nothing to return yet.
+    /// For internal use only.
+    async fn crawl_page(
+        &mut self,
+        next: Option<String>,
+        pages: usize,
+    ) -> Result<Option<Vec<T>>, Error> {
+        if pages >= self.max_pages {
+            return Ok(None);
+        }
+
+        let (candidates, next) = T::crawl(
+            self.client.clone(),
+            self.query.clone(),
+            self.community.clone(),
+            next,
+        )
+        .await?;
+        let results: Vec<T> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.matches(&self.query))
+            .collect();
+        debug!(
+            kind = T::KIND,
+            pages = pages + 1,
+            matches = results.len(),
+            "Crawled a page."
+        );
+
+        if next.is_some() {
+            self.state = State::Crawl {
+                next,
+                pages: pages + 1,
+            };
+        }
+        Ok((!results.is_empty()).then_some(results))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::json;
+
+    /// Collects every result of a search.
+    async fn collect<T: Searchable>(mut search: Search<T>) -> Vec<T> {
+        let mut results = Vec::new();
+        while let Some(page) = search.next_page().await {
+            results.extend(page.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn test_server_search() {
+        let server = MockServer::start(|request| {
+            let (name, next) = if request.path.contains("next=2") {
+                ("general", None)
+            } else {
+                ("gaming", Some("2"))
+            };
+            let results = [fixtures::community(name)];
+            MockResponse::json(200, &json!({"results": results, "next": next}).to_string())
+        })
+        .await;
+        let client = DiscuitClient::new(&server.url);
+
+        let search = client.search_communities("g");
+        let names: Vec<_> = collect(search).await.into_iter().map(|c| c.name).collect();
+
+        assert_eq!(names, ["gaming", "general"]);
+        let path = &server.requests()[0].path;
+        assert!(path.starts_with("/api/search?"));
+        assert!(path.contains("q=g") && path.contains("type=communities"));
+    }
+
+    #[tokio::test]
+    async fn test_post_fallback_crawl_is_bounded() {
+        let server = MockServer::start(|request| {
+            if request.path.starts_with("/api/search") {
+                return MockResponse::json(404, "404 page not found");
+            }
+            let page: u32 = request
+                .path
+                .split("next=")
+                .nth(1)
+                .map_or(0, |next| next.split('&').next().unwrap().parse().unwrap());
+            let posts: Vec<_> = (0..2)
+                .map(|i| {
+                    let mut post = fixtures::post(
+                        &format!("p{}{}", page, i),
+                        "general",
+                        "user",
+                        "2024-05-01T00:00:00Z",
+                    );
+                    if i == 0 {
+                        post["title"] = json!("Async Rust in practice");
+                    }
+                    post
+                })
+                .collect();
+            MockResponse::json(
+                200,
+                &json!({"posts": posts, "next": (page + 1).to_string()}).to_string(),
+            )
+        })
+        .await;
+        let client = DiscuitClient::new(&server.url);
+
+        let mut search = client
+            .search_posts("rust ASYNC")
+            .community("general")
+            .max_pages(3);
+        let mut ids = Vec::new();
+        while let Some(page) = search.next_page().await {
+            ids.extend(
+                page.unwrap()
+                    .into_iter()
+                    .map(|post| post.public_id.to_string()),
+            );
+        }
+
+        assert!(search.is_fallback());
+        assert_eq!(ids, ["p00", "p10", "p20"]);
+        // One search request and three pages of posts.
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[1].path.contains("sort=new"));
+        assert!(requests[1].path.contains("community=general"));
+    }
+
+    #[tokio::test]
+    async fn test_server_mode_without_search() {
+        let server = MockServer::start(|_| MockResponse::json(404, "404 page not found")).await;
+        let client = DiscuitClient::new(&server.url);
+
+        let mut search = client.search_users("previnder").mode(SearchMode::Server);
+        match search.next_page().await {
+            Some(Err(Error::Api(e))) => assert_eq!(e.status, 404),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(search.next_page().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_fallback() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/api/users/previnder" => {
+                MockResponse::json(200, &fixtures::user("previnder").to_string())
+            }
+            _ => MockResponse::json(
+                404,
+                r#"{"status": 404, "code": "user_not_found", "message": "User not found."}"#,
+            ),
+        })
+        .await;
+        let client = DiscuitClient::new(&server.url);
+
+        let found = collect(client.search_users("previnder").mode(SearchMode::Client)).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].username, "previnder");
+
+        let missing = collect(client.search_users("nobody").mode(SearchMode::Client)).await;
+        assert!(missing.is_empty());
+    }
+}
@@ -0,0 +1,907 @@
+//! This module contains `Store`, which keeps the users, communities, posts,
+//! comments, lists and notifications a client fetches in a SQLite database.
+//! Requires the `store` feature.
+//!
+//! Every entity is stored once per ID, as its latest version, in a table with
+//! a few columns to query by and the whole entity as JSON in `data`; SQLite's
+//! `json_extract` reaches the other fields. The votes of posts and comments
+//! are also kept over time, in `post_votes` and `comment_votes`, with a row
+//! whenever they were seen to change.
+//!
+//! A `Store` is a `DecodeHook`, so passing it to `DiscuitClientBuilder::hook`
+//! records everything the client fetches. The hook hands the entities to a
+//! writer thread rather than writing them while the response is decoded; call
+//! `Store::flush` to wait for them.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::client::DiscuitClient;
+//! use discuit_rs::store::Store;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let store = Store::open("discuit.sqlite3")?;
+//! let client = DiscuitClient::builder("https://discuit.net")
+//!     .hook(store.clone())
+//!     .build()?;
+//!
+//! client.get_posts_page(Some("new"), Some("general"), None).await?;
+//! store.flush();
+//!
+//! let posts: i64 = store
+//!     .connection()
+//!     .query_row("SELECT COUNT(*) FROM posts", [], |row| row.get(0))?;
+//! println!("{} posts stored", posts);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::hook::DecodeHook;
+use crate::structs::api_responses::*;
+use crate::structs::api_types::*;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::any::Any;
+use std::fmt;
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use tracing::{debug, info, warn};
+
+pub use rusqlite;
+
+/// The migrations of the schema, in order. The number of migrations applied to
+/// a database is kept in its `user_version`; new migrations are only ever
+/// appended.
+const MIGRATIONS: [&str; 1] = [r#"
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    points INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+CREATE INDEX users_username ON users (username);
+
+CREATE TABLE communities (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    no_members INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+CREATE INDEX communities_name ON communities (name);
+
+CREATE TABLE posts (
+    id TEXT PRIMARY KEY,
+    public_id TEXT NOT NULL,
+    community_id TEXT NOT NULL,
+    community_name TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    username TEXT NOT NULL,
+    title TEXT NOT NULL,
+    upvotes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    no_comments INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+CREATE INDEX posts_public_id ON posts (public_id);
+CREATE INDEX posts_community_created_at ON posts (community_name, created_at);
+CREATE INDEX posts_username ON posts (username);
+
+CREATE TABLE comments (
+    id TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL,
+    post_public_id TEXT NOT NULL,
+    parent_id TEXT,
+    community_name TEXT NOT NULL,
+    username TEXT NOT NULL,
+    upvotes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+CREATE INDEX comments_post_id ON comments (post_id);
+CREATE INDEX comments_username ON comments (username);
+
+CREATE TABLE lists (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    num_items INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+
+CREATE TABLE notifications (
+    id TEXT PRIMARY KEY,
+    type TEXT NOT NULL,
+    seen INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+
+CREATE TABLE post_votes (
+    post_id TEXT NOT NULL REFERENCES posts (id),
+    observed_at TEXT NOT NULL,
+    upvotes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    hotness INTEGER NOT NULL,
+    no_comments INTEGER NOT NULL,
+    PRIMARY KEY (post_id, observed_at)
+);
+
+CREATE TABLE comment_votes (
+    comment_id TEXT NOT NULL REFERENCES comments (id),
+    observed_at TEXT NOT NULL,
+    upvotes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    PRIMARY KEY (comment_id, observed_at)
+);
+"#];
+
+/// `StoreError` represents everything that can go wrong with a `Store`.
+#[derive(Debug)]
+pub enum StoreError {
+    /// A SQLite operation failed.
+    Sqlite(rusqlite::Error),
+    /// An entity couldn't be encoded as JSON.
+    Encode(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            StoreError::Encode(e) => write!(f, "Encode error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Sqlite(e) => Some(e),
+            StoreError::Encode(e) => Some(e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Encode(e)
+    }
+}
+
+/// The entities found in a decoded response.
+#[derive(Default)]
+struct Entities<'a> {
+    users: Vec<&'a User>,
+    communities: Vec<&'a Community>,
+    posts: Vec<&'a Post>,
+    comments: Vec<&'a Comment>,
+    lists: Vec<&'a List>,
+    notifications: Vec<&'a Notification>,
+}
+
+impl<'a> Entities<'a> {
+    /// Collects the entities of the response types the client decodes.
+    fn of(value: &'a dyn Any) -> Self {
+        let mut entities = Self::default();
+        if let Some(post) = value.downcast_ref::<Post>() {
+            entities.post(post);
+        } else if let Some(page) = value.downcast_ref::<PostFeedResponse>() {
+            page.posts.iter().for_each(|post| entities.post(post));
+        } else if let Some(comment) = value.downcast_ref::<Comment>() {
+            entities.comments.push(comment);
+        } else if let Some(page) = value.downcast_ref::<CommentsResponse>() {
+            entities.comments.extend(page.comments.iter().flatten());
+        } else if let Some(community) = value.downcast_ref::<Community>() {
+            entities.community(community);
+        } else if let Some(communities) = value.downcast_ref::<Option<Vec<Community>>>() {
+            communities
+                .iter()
+                .flatten()
+                .for_each(|community| entities.community(community));
+        } else if let Some(user) = value.downcast_ref::<User>() {
+            entities.users.push(user);
+        } else if let Some(UserResponse::User(user)) = value.downcast_ref::<UserResponse>() {
+            entities.users.push(user);
+        } else if let Some(initial) = value.downcast_ref::<InitialResponse>() {
+            entities.users.extend(&initial.user);
+            let banned_from = initial.banned_from.iter().flatten();
+            entities
+                .communities
+                .extend(initial.communities.iter().chain(banned_from));
+        } else if let Some(FeedResponse::Feed { feed, .. }) = value.downcast_ref::<FeedResponse>() {
+            for item in feed {
+                match item {
+                    FeedItem::Post(post) => entities.post(post),
+                    FeedItem::Comment(comment) => entities.comments.push(comment),
+                }
+            }
+        } else if let Some(page) = value.downcast_ref::<NotificationsResponse>() {
+            entities.notifications.extend(page.items.iter().flatten());
+        } else if let Some(page) = value.downcast_ref::<ReportsResponse>() {
+            for target in page.reports.iter().flatten().flat_map(|r| &r.target) {
+                match target {
+                    ReportTarget::Post(post) => entities.post(post),
+                    ReportTarget::Comment(comment) => entities.comments.push(comment),
+                }
+            }
+        } else if let Some(lists) = value.downcast_ref::<Vec<List>>() {
+            entities.lists.extend(lists);
+        } else if let Some(list) = value.downcast_ref::<List>() {
+            entities.lists.push(list);
+        } else if let Some(page) = value.downcast_ref::<SearchResponse<Post>>() {
+            page.results
+                .iter()
+                .flatten()
+                .for_each(|post| entities.post(post));
+        } else if let Some(page) = value.downcast_ref::<SearchResponse<Community>>() {
+            page.results
+                .iter()
+                .flatten()
+                .for_each(|community| entities.community(community));
+        } else if let Some(page) = value.downcast_ref::<SearchResponse<User>>() {
+            entities.users.extend(page.results.iter().flatten());
+        }
+        entities
+    }
+
+    /// Adds a post with its community, author and comments.
+    fn post(&mut self, post: &'a Post) {
+        self.posts.push(post);
+        self.community(&post.community);
+        self.users.extend(&post.user);
+        self.comments.extend(post.comments.iter().flatten());
+    }
+
+    /// Adds a community with its moderators.
+    fn community(&mut self, community: &'a Community) {
+        self.communities.push(community);
+        self.users.extend(community.mods.iter().flatten());
+    }
+
+    fn is_empty(&self) -> bool {
+        self.users.is_empty()
+            && self.communities.is_empty()
+            && self.posts.is_empty()
+            && self.comments.is_empty()
+            && self.lists.is_empty()
+            && self.notifications.is_empty()
+    }
+}
+
+/// The entities of a response, copied to be written by the writer thread.
+struct Records {
+    users: Vec<User>,
+    communities: Vec<Community>,
+    posts: Vec<Post>,
+    comments: Vec<Comment>,
+    lists: Vec<List>,
+    notifications: Vec<Notification>,
+    seen_at: DateTime<Utc>,
+}
+
+impl Records {
+    fn new(entities: Entities<'_>) -> Self {
+        Self {
+            users: entities.users.into_iter().cloned().collect(),
+            communities: entities.communities.into_iter().cloned().collect(),
+            posts: entities.posts.into_iter().cloned().collect(),
+            comments: entities.comments.into_iter().cloned().collect(),
+            lists: entities.lists.into_iter().cloned().collect(),
+            notifications: entities.notifications.into_iter().cloned().collect(),
+            seen_at: Utc::now(),
+        }
+    }
+
+    fn entities(&self) -> Entities<'_> {
+        Entities {
+            users: self.users.iter().collect(),
+            communities: self.communities.iter().collect(),
+            posts: self.posts.iter().collect(),
+            comments: self.comments.iter().collect(),
+            lists: self.lists.iter().collect(),
+            notifications: self.notifications.iter().collect(),
+        }
+    }
+}
+
+/// A job of the writer thread.
+enum Job {
+    /// Store the entities of a response.
+    Record(Records),
+    /// Signal that the jobs sent before were done.
+    Flush(Sender<()>),
+}
+
+/// `Store` is a SQLite database of Discuit entities.
+///
+/// Cloning the store is cheap; clones share the same connection and writer
+/// thread.
+#[derive(Clone)]
+pub struct Store {
+    connection: Arc<Mutex<Connection>>,
+    /// The queue of the writer thread, started by the first response the
+    /// store receives as a `DecodeHook`, or `None` if it couldn't be started.
+    writer: Arc<OnceLock<Option<Sender<Job>>>>,
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Store").finish_non_exhaustive()
+    }
+}
+
+impl Store {
+    /// Open the database at the given path, creating it if it doesn't exist
+    /// and migrating its schema to the latest version.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Open a new database in memory, e.g. for tests.
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> Result<Self, StoreError> {
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            writer: Arc::default(),
+        })
+    }
+
+    /// Returns the connection to the database, e.g. for analytics queries.
+    /// Writes through the store wait until the guard is dropped.
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        // A transaction that was open when a thread panicked is rolled back
+        // as it's dropped, so the connection is still usable.
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait until the responses the store received as a `DecodeHook` so far
+    /// are written. Blocks the current thread.
+    pub fn flush(&self) {
+        let Some(Some(writer)) = self.writer.get() else {
+            return;
+        };
+        let (done, wait) = mpsc::channel();
+        if writer.send(Job::Flush(done)).is_ok() {
+            // The writer thread only drops the sender early if it panicked.
+            let _ = wait.recv();
+        }
+    }
+
+    /// Returns the queue of the writer thread, starting the thread if needed.
+    /// For internal use only.
+    fn writer(&self) -> Option<&Sender<Job>> {
+        self.writer
+            .get_or_init(|| {
+                let (sender, jobs) = mpsc::channel();
+                // The thread gets a store of its own, so that it exits once
+                // every clone of this one, and so every sender, is dropped.
+                let store = Store {
+                    connection: self.connection.clone(),
+                    writer: Arc::default(),
+                };
+                let spawned = thread::Builder::new()
+                    .name("discuit-store".to_string())
+                    .spawn(move || store.write_jobs(jobs));
+                match spawned {
+                    Ok(_) => Some(sender),
+                    Err(e) => {
+                        warn!(error = %e, "Failed to start the store writer, writing inline");
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// Runs the jobs of the writer thread until every sender is dropped.
+    /// For internal use only.
+    fn write_jobs(&self, jobs: mpsc::Receiver<Job>) {
+        for job in jobs {
+            match job {
+                Job::Record(records) => self.write_records(&records),
+                Job::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    /// Stores the entities of a response, logging the outcome.
+    /// For internal use only.
+    fn write_records(&self, records: &Records) {
+        match self.record_at(records.entities(), records.seen_at) {
+            Ok(()) => debug!("Response stored."),
+            Err(e) => warn!(error = %e, "Failed to store response"),
+        }
+    }
+
+    /// Returns the version of the schema, i.e. the number of migrations applied.
+    pub fn schema_version(&self) -> Result<usize, StoreError> {
+        Ok(schema_version(&self.connection())?)
+    }
+
+    /// Store a user, replacing an earlier version of it.
+    pub fn record_user(&self, user: &User) -> Result<(), StoreError> {
+        self.record(Entities {
+            users: vec![user],
+            ..Entities::default()
+        })
+    }
+
+    /// Store a community, replacing an earlier version of it.
+    pub fn record_community(&self, community: &Community) -> Result<(), StoreError> {
+        self.record(Entities {
+            communities: vec![community],
+            ..Entities::default()
+        })
+    }
+
+    /// Store a post, replacing an earlier version of it, and snapshot its votes.
+    pub fn record_post(&self, post: &Post) -> Result<(), StoreError> {
+        self.record(Entities {
+            posts: vec![post],
+            ..Entities::default()
+        })
+    }
+
+    /// Store a comment, replacing an earlier version of it, and snapshot its votes.
+    pub fn record_comment(&self, comment: &Comment) -> Result<(), StoreError> {
+        self.record(Entities {
+            comments: vec![comment],
+            ..Entities::default()
+        })
+    }
+
+    /// Store a list, replacing an earlier version of it.
+    pub fn record_list(&self, list: &List) -> Result<(), StoreError> {
+        self.record(Entities {
+            lists: vec![list],
+            ..Entities::default()
+        })
+    }
+
+    /// Store a notification, replacing an earlier version of it.
+    pub fn record_notification(&self, notification: &Notification) -> Result<(), StoreError> {
+        self.record(Entities {
+            notifications: vec![notification],
+            ..Entities::default()
+        })
+    }
+
+    /// Store every entity in a decoded response, e.g. a `PostFeedResponse`
+    /// with its posts, their communities and their comments.
+    /// Returns false if the value isn't a type the store knows about.
+    pub fn record_response(&self, value: &dyn Any) -> Result<bool, StoreError> {
+        let entities = Entities::of(value);
+        if entities.is_empty() {
+            return Ok(false);
+        }
+        self.record(entities)?;
+        Ok(true)
+    }
+
+    /// Stores entities in one transaction.
+    /// For internal use only.
+    fn record(&self, entities: Entities<'_>) -> Result<(), StoreError> {
+        self.record_at(entities, Utc::now())
+    }
+
+    /// Stores entities seen at the given time in one transaction.
+    /// For internal use only.
+    fn record_at(&self, entities: Entities<'_>, seen_at: DateTime<Utc>) -> Result<(), StoreError> {
+        let now = timestamp(seen_at);
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        for user in entities.users {
+            upsert(
+                &tx,
+                "users",
+                &["id", "username", "points", "created_at"],
+                params![
+                    user.id.to_string(),
+                    user.username,
+                    user.points,
+                    timestamp(user.created_at),
+                    serde_json::to_string(user)?,
+                    now,
+                ],
+            )?;
+        }
+        for community in entities.communities {
+            upsert(
+                &tx,
+                "communities",
+                &["id", "name", "no_members", "created_at"],
+                params![
+                    community.id.to_string(),
+                    community.name,
+                    community.no_members,
+                    timestamp(community.created_at),
+                    serde_json::to_string(community)?,
+                    now,
+                ],
+            )?;
+        }
+        for post in entities.posts {
+            upsert(
+                &tx,
+                "posts",
+                &[
+                    "id",
+                    "public_id",
+                    "community_id",
+                    "community_name",
+                    "user_id",
+                    "username",
+                    "title",
+                    "upvotes",
+                    "downvotes",
+                    "no_comments",
+                    "created_at",
+                ],
+                params![
+                    post.id.to_string(),
+                    post.public_id.to_string(),
+                    post.community_id.to_string(),
+                    post.community_name,
+                    post.user_id.to_string(),
+                    post.username,
+                    post.title,
+                    post.upvotes,
+                    post.downvotes,
+                    post.no_comments,
+                    timestamp(post.created_at),
+                    serde_json::to_string(post)?,
+                    now,
+                ],
+            )?;
+            snapshot(
+                &tx,
+                "post_votes",
+                "post_id",
+                post.id.as_ref(),
+                &[
+                    ("upvotes", post.upvotes),
+                    ("downvotes", post.downvotes),
+                    ("hotness", post.hotness),
+                    ("no_comments", post.no_comments),
+                ],
+                &now,
+            )?;
+        }
+        for comment in entities.comments {
+            upsert(
+                &tx,
+                "comments",
+                &[
+                    "id",
+                    "post_id",
+                    "post_public_id",
+                    "parent_id",
+                    "community_name",
+                    "username",
+                    "upvotes",
+                    "downvotes",
+                    "created_at",
+                ],
+                params![
+                    comment.id.to_string(),
+                    comment.post_id.to_string(),
+                    comment.post_public_id.to_string(),
+                    comment.parent_id.as_ref().map(|id| id.to_string()),
+                    comment.community_name,
+                    comment.username,
+                    comment.upvotes,
+                    comment.downvotes,
+                    timestamp(comment.created_at),
+                    serde_json::to_string(comment)?,
+                    now,
+                ],
+            )?;
+            snapshot(
+                &tx,
+                "comment_votes",
+                "comment_id",
+                comment.id.as_ref(),
+                &[
+                    ("upvotes", comment.upvotes),
+                    ("downvotes", comment.downvotes),
+                ],
+                &now,
+            )?;
+        }
+        for list in entities.lists {
+            upsert(
+                &tx,
+                "lists",
+                &["id", "user_id", "name", "num_items", "created_at"],
+                params![
+                    list.id.to_string(),
+                    list.user_id.to_string(),
+                    list.name,
+                    list.num_items,
+                    timestamp(list.created_at),
+                    serde_json::to_string(list)?,
+                    now,
+                ],
+            )?;
+        }
+        for notification in entities.notifications {
+            upsert(
+                &tx,
+                "notifications",
+                &["id", "type", "seen", "created_at"],
+                params![
+                    notification.id.to_string(),
+                    notification.notification_type,
+                    notification.seen,
+                    timestamp(notification.created_at),
+                    serde_json::to_string(notification)?,
+                    now,
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl DecodeHook for Store {
+    fn decoded(&self, value: &dyn Any) {
+        let entities = Entities::of(value);
+        if entities.is_empty() {
+            return;
+        }
+        let records = Records::new(entities);
+        match self.writer() {
+            Some(writer) => {
+                if let Err(mpsc::SendError(Job::Record(records))) =
+                    writer.send(Job::Record(records))
+                {
+                    warn!("The store writer stopped, writing inline");
+                    self.write_records(&records);
+                }
+            }
+            None => self.write_records(&records),
+        }
+    }
+}
+
+/// Applies the migrations a database doesn't have yet.
+/// For internal use only.
+fn migrate(connection: &mut Connection) -> Result<(), rusqlite::Error> {
+    let version = schema_version(connection)?;
+    if version >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let tx = connection.transaction()?;
+    for migration in &MIGRATIONS[version..] {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()?;
+    info!(
+        from = version,
+        to = MIGRATIONS.len(),
+        "Store schema migrated."
+    );
+    Ok(())
+}
+
+/// Returns the number of migrations applied to a database.
+/// For internal use only.
+fn schema_version(connection: &Connection) -> Result<usize, rusqlite::Error> {
+    connection.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Inserts an entity or updates the stored version, keeping the time it was
+/// first seen. `columns` starts with the ID; the parameters are the columns,
+/// then the JSON of the entity, then the current time.
+/// For internal use only.
+fn upsert(
+    tx: &Transaction<'_>,
+    table: &str,
+    columns: &[&str],
+    params: &[&dyn rusqlite::ToSql],
+) -> Result<(), rusqlite::Error> {
+    let placeholders: Vec<String> = (1..=columns.len() + 2).map(|i| format!("?{}", i)).collect();
+    let now = placeholders[columns.len() + 1].as_str();
+    let updates: Vec<String> = columns[1..]
+        .iter()
+        .chain(&["data"])
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect();
+    let sql = format!(
+        "INSERT INTO {table} ({columns}, data, first_seen_at, last_seen_at) \
+         VALUES ({values}, {now}) \
+         ON CONFLICT (id) DO UPDATE SET {updates}, last_seen_at = excluded.last_seen_at",
+        columns = columns.join(", "),
+        values = placeholders.join(", "),
+        updates = updates.join(", "),
+    );
+    tx.prepare_cached(&sql)?.execute(params)?;
+    Ok(())
+}
+
+/// Records the votes of a post or comment, unless they didn't change since
+/// the latest snapshot.
+/// For internal use only.
+fn snapshot(
+    tx: &Transaction<'_>,
+    table: &str,
+    key: &str,
+    id: &str,
+    values: &[(&str, i32)],
+    now: &str,
+) -> Result<(), rusqlite::Error> {
+    let columns: Vec<&str> = values.iter().map(|(column, _)| *column).collect();
+    let latest: Option<Vec<i32>> = tx
+        .prepare_cached(&format!(
+            "SELECT {} FROM {table} WHERE {key} = ?1 ORDER BY observed_at DESC LIMIT 1",
+            columns.join(", ")
+        ))?
+        .query_row([id], |row| (0..columns.len()).map(|i| row.get(i)).collect())
+        .optional()?;
+    let current: Vec<i32> = values.iter().map(|(_, value)| *value).collect();
+    if latest.as_ref() == Some(&current) {
+        return Ok(());
+    }
+
+    let placeholders: Vec<String> = (3..columns.len() + 3).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "INSERT OR REPLACE INTO {table} ({key}, observed_at, {}) VALUES (?1, ?2, {})",
+        columns.join(", "),
+        placeholders.join(", ")
+    );
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&id, &now];
+    params.extend(
+        values
+            .iter()
+            .map(|(_, value)| value as &dyn rusqlite::ToSql),
+    );
+    tx.prepare_cached(&sql)?.execute(params.as_slice())?;
+    Ok(())
+}
+
+/// Formats a time for the database with a fixed width, so times sort the
+/// same as text.
+/// For internal use only.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::DiscuitClient;
+    use crate::fixtures;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::json;
+
+    fn count(store: &Store, sql: &str) -> i64 {
+        store
+            .connection()
+            .query_row(sql, [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrations_are_applied_once() {
+        let path = std::env::temp_dir().join(format!("discuit-store-{}", rand::random::<u64>()));
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+        drop(store);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_deduplicates_and_snapshots_votes() {
+        let store = Store::open_in_memory().unwrap();
+        let mut post = fixtures::post("a", "general", "alice", "2024-05-01T00:00:00Z");
+        for upvotes in [1, 1, 5] {
+            post["upvotes"] = json!(upvotes);
+            store
+                .record_post(&serde_json::from_value(post.clone()).unwrap())
+                .unwrap();
+        }
+
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM posts"), 1);
+        assert_eq!(count(&store, "SELECT upvotes FROM posts"), 5);
+        // Unchanged votes aren't snapshotted again.
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM post_votes"), 2);
+    }
+
+    #[test]
+    fn test_poisoned_connection_is_recovered() {
+        let store = Store::open_in_memory().unwrap();
+        let poisoner = store.clone();
+        std::thread::spawn(move || {
+            let _connection = poisoner.connection();
+            panic!("poison the connection");
+        })
+        .join()
+        .unwrap_err();
+
+        let post = fixtures::post("a", "general", "alice", "2024-05-01T00:00:00Z");
+        store
+            .record_post(&serde_json::from_value(post).unwrap())
+            .unwrap();
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM posts"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hook_records_responses() {
+        let server = MockServer::start(|request| {
+            if request.path.contains("/comments") {
+                let comments = [
+                    fixtures::comment("c1", "a", "bob", "hi", "2024-05-01T01:00:00Z"),
+                    fixtures::comment("c2", "a", "carol", "hey", "2024-05-01T02:00:00Z"),
+                ];
+                let body = json!({"comments": comments, "next": null});
+                return MockResponse::json(200, &body.to_string());
+            }
+            let posts = [fixtures::post(
+                "a",
+                "general",
+                "alice",
+                "2024-05-01T00:00:00Z",
+            )];
+            MockResponse::json(200, &json!({"posts": posts, "next": null}).to_string())
+        })
+        .await;
+        let store = Store::open_in_memory().unwrap();
+        let client = DiscuitClient::builder(&server.url)
+            .hook(store.clone())
+            .build()
+            .unwrap();
+
+        let page = client.get_posts_page(None, None, None).await.unwrap();
+        client
+            .get_post_comments(&page.posts[0].public_id, None)
+            .await
+            .unwrap();
+        store.flush();
+
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM posts"), 1);
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM communities"), 1);
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM comments"), 2);
+        assert_eq!(count(&store, "SELECT COUNT(*) FROM comment_votes"), 2);
+    }
+}
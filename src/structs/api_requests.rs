@@ -1,11 +1,102 @@
 //! This module contains all API request structs used in the `discuit-rs` library.
 
+use crate::structs::api_types::PostType;
+use crate::structs::ids::*;
+use crate::structs::internal_types::Redacted;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// `LoginRequest` is used by the /api/_login endpoint to log in to the
-/// Discuit instance.
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+/// Discuit instance. The `Debug` output redacts the password.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
+
+impl fmt::Debug for LoginRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("username", &self.username)
+            .field("password", &Redacted(&self.password))
+            .finish()
+    }
+}
+
+/// `CreatePostRequest` is used by the /api/posts endpoint to create a post.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct CreatePostRequest {
+    /// The type of post, either "text", "image" or "link".
+    #[serde(rename = "type")]
+    pub post_type: PostType,
+    /// The title of the post. Greater than 3 characters.
+    pub title: String,
+    /// The body of the post, only valid for text posts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// The URL of the link, only valid for link posts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The ID of an uploaded image, only valid for image posts.
+    #[serde(rename = "imageId", skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<ImageId>,
+    /// The name of the community to post in.
+    pub community: String,
+}
+
+impl CreatePostRequest {
+    /// Create a request for a text post.
+    pub fn text(community: &str, title: &str, body: &str) -> Self {
+        Self {
+            post_type: PostType::Text,
+            title: title.to_string(),
+            body: Some(body.to_string()),
+            url: None,
+            image_id: None,
+            community: community.to_string(),
+        }
+    }
+
+    /// Create a request for a link post.
+    pub fn link(community: &str, title: &str, url: &str) -> Self {
+        Self {
+            post_type: PostType::Link,
+            title: title.to_string(),
+            body: None,
+            url: Some(url.to_string()),
+            image_id: None,
+            community: community.to_string(),
+        }
+    }
+}
+
+/// `CreateCommentRequest` is used by the /api/posts/{postId}/comments endpoint
+/// to add a comment to a post.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct CreateCommentRequest {
+    /// The ID of the comment being replied to, or null for a top-level comment.
+    #[serde(rename = "parentCommentId")]
+    pub parent_comment_id: Option<CommentId>,
+    /// The body of the comment.
+    pub body: String,
+}
+
+/// `PostVoteRequest` is used by the /api/_postVote endpoint to vote on a post.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct PostVoteRequest {
+    /// The ID of the post.
+    #[serde(rename = "postId")]
+    pub post_id: PostId,
+    /// If the vote is an upvote.
+    pub up: bool,
+}
+
+/// `CommentVoteRequest` is used by the /api/_commentVote endpoint to vote on a comment.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct CommentVoteRequest {
+    /// The ID of the comment.
+    #[serde(rename = "commentId")]
+    pub comment_id: CommentId,
+    /// If the vote is an upvote.
+    pub up: bool,
+}
@@ -65,6 +65,7 @@ pub struct InitialResponse {
 /// `User` or `APIError`.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum UserResponse {
     /// The user object.
     User(User),
@@ -104,6 +105,7 @@ pub enum FeedResponse {
 /// `FeedItem` represents a post or comment in a feed.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum FeedItem {
     /// A post.
     Post(Post),
@@ -116,7 +118,57 @@ pub enum FeedItem {
 /// The cursor can be either a string or an integer, depending on the sort method.
 /// If the sort method is `activity`, the cursor is an integer. Else, it is a string.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
 pub enum Next {
     String(String),
     Int(i32),
 }
+
+/// `NotificationsResponse` represents the response from the `/api/notifications` endpoint.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
+pub struct NotificationsResponse {
+    /// The total number of notifications.
+    pub count: i32,
+    /// The number of new notifications.
+    #[serde(rename = "newCount")]
+    pub new_count: i32,
+    /// Array of notifications. Null if there are no notifications.
+    pub items: Option<Vec<Notification>>,
+    /// Pagination cursor. Null implies end of pagination.
+    pub next: Option<String>,
+}
+
+/// `CommentsResponse` represents the response from the `/api/posts/{postId}/comments` endpoint.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct CommentsResponse {
+    /// Array of comments. Null if there are no comments.
+    pub comments: Option<Vec<Comment>>,
+    /// Pagination cursor. Null implies end of pagination.
+    pub next: Option<String>,
+}
+
+/// `ReportsResponse` represents the response from the `/api/communities/{communityId}/reports` endpoint.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct ReportsResponse {
+    /// The number of reports in the community.
+    pub details: ReportDetails,
+    /// Array of reports on this page. Null if there are no reports.
+    pub reports: Option<Vec<Report>>,
+    /// The number of reports per page.
+    pub limit: i32,
+    /// The current page.
+    pub page: i32,
+    /// The total number of pages.
+    #[serde(rename = "noPages")]
+    pub no_pages: i32,
+}
+
+/// `SearchResponse` represents the response from the `/api/search` endpoint, on
+/// instances that have it.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
+pub struct SearchResponse<T> {
+    /// Array of results. Null if nothing matched.
+    pub results: Option<Vec<T>>,
+    /// Pagination cursor. Null implies end of pagination.
+    pub next: Option<String>,
+}
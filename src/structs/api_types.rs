@@ -1,7 +1,12 @@
 //! This module contains the types that are used and returned by the Discuit API.
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+pub use crate::structs::ids::*;
 
 // TODO: Add missing fields to the structs.
 // - [ ] Comment
@@ -9,10 +14,10 @@ use serde::{Deserialize, Serialize};
 // - [X] Image
 //   - [x] ImageCopy
 // - [ ] Mute
-// - [ ] Notification
+// - [X] Notification
 // - [ ] Post
 // - [ ] List
-// - [ ] Report
+// - [X] Report
 // - [X] ReportReason
 // - [X] User
 // - [X] Response (InitResponse)
@@ -43,7 +48,7 @@ pub struct ReportReason {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct User {
     /// The ID of the user.
-    pub id: String,
+    pub id: UserId,
     /// The username of the user. Minimum 3 characters. Maximum 21 characters.
     pub username: String,
     /// If an email address was provided, the email address of the user, otherwise null.
@@ -89,7 +94,7 @@ pub struct User {
     pub reply_notifications_off: bool,
     /// The feed the user has set as their home feed.
     #[serde(rename = "homeFeed")]
-    pub home_feed: String,
+    pub home_feed: HomeFeed,
     /// If the user wants their feed sort to be remembered.
     #[serde(rename = "rememberFeedSort")]
     pub remember_feed_sort: bool,
@@ -127,10 +132,10 @@ pub struct Badge {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct Community {
     /// The ID of the community.
-    pub id: String,
+    pub id: CommunityId,
     /// ID of the user who created the community.
     #[serde(rename = "userId")]
-    pub user_id: String,
+    pub user_id: UserId,
 
     /// The name of the community.
     pub name: String,
@@ -204,14 +209,14 @@ pub struct CommunityRule {
 
     /// The ID of the community in which this is a rule.
     #[serde(rename = "communityId")]
-    pub community_id: String,
+    pub community_id: CommunityId,
     /// The index of the rule. A smaller value means that the rule is closer to the top.
     #[serde(rename = "zIndex")]
     pub z_index: i32,
 
     /// The ID of the user that created the rule.
     #[serde(rename = "createdBy")]
-    pub created_by: String,
+    pub created_by: UserId,
     /// The time at which the rule was created.
     #[serde(rename = "createdAt")]
     pub created_at: DateTime<Utc>,
@@ -221,7 +226,7 @@ pub struct CommunityRule {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct Image {
     /// The ID of the image.
-    pub id: String,
+    pub id: ImageId,
 
     /// The image format.
     pub format: String,
@@ -263,7 +268,7 @@ pub struct ImageCopy {
     pub box_height: i32,
     /// How the image should fit into a box. Corresponds to the CSS `object-fit` property.
     #[serde(rename = "objectFit")]
-    pub object_fit: String,
+    pub object_fit: ObjectFit,
 
     /// The format of the image copy.
     pub format: String,
@@ -275,16 +280,16 @@ pub struct ImageCopy {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct Mute {
     /// The ID of the mute.
-    pub id: String,
+    pub id: MuteId,
     /// Whether a user or community is being muted.
     #[serde(rename = "type")]
     pub mute_type: String,
     /// If a user is being muted, the ID of the user, otherwise undefined.
     #[serde(rename = "mutedUserId")]
-    pub muted_user_id: Option<String>,
+    pub muted_user_id: Option<UserId>,
     /// If a community is being muted, the ID of the community, otherwise undefined.
     #[serde(rename = "mutedCommunityId")]
-    pub muted_community_id: Option<String>,
+    pub muted_community_id: Option<CommunityId>,
 
     /// The time at which the mute was created.
     #[serde(rename = "createdAt")]
@@ -302,24 +307,24 @@ pub struct Mute {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct Comment {
     /// The ID of the comment.
-    pub id: String,
+    pub id: CommentId,
     /// The ID of the post the comment belongs to.
     #[serde(rename = "postId")]
-    pub post_id: String,
+    pub post_id: PostId,
     /// The public ID of the post the comment belongs to.
     #[serde(rename = "postPublicId")]
-    pub post_public_id: String,
+    pub post_public_id: PublicPostId,
 
     /// The ID of the community in which this comment was made.
     #[serde(rename = "communityId")]
-    pub community_id: String,
+    pub community_id: CommunityId,
     /// The name of the community in which this comment was made.
     #[serde(rename = "communityName")]
     pub community_name: String,
 
     /// The ID of the user that made the comment.
     #[serde(rename = "userId")]
-    pub user_id: Option<String>,
+    pub user_id: Option<UserId>,
     /// The username of the user that made the comment.
     pub username: String,
     /// The ID of the Ghost user in case the author deleted their account, otherwise undefined.
@@ -327,14 +332,14 @@ pub struct Comment {
     pub user_ghost_id: Option<String>,
     /// The capacity in which the comment was created.
     #[serde(rename = "userGroup")]
-    pub user_group: String,
+    pub user_group: UserGroup,
     /// Indicates whether the author account is deleted.
     #[serde(rename = "userDeleted")]
     pub user_deleted: bool,
 
     /// The comment ID of the parent comment if it exists, otherwise null if this is a top-level comment.
     #[serde(rename = "parentId")]
-    pub parent_id: Option<String>,
+    pub parent_id: Option<CommentId>,
     /// How far deep into a comment chain this comment is. Top-level comments have a depth of 0.
     pub depth: i32,
     /// The total number of replies the comment has, including all deeper comments.
@@ -344,7 +349,7 @@ pub struct Comment {
     #[serde(rename = "noDirectReplies")]
     pub no_direct_replies: i32,
     /// The comment IDs of all ancestor comments starting from the top-most comment.
-    pub ancestors: Option<Vec<String>>,
+    pub ancestors: Option<Vec<CommentId>>,
 
     /// The body of the comment.
     pub body: String,
@@ -354,10 +359,10 @@ pub struct Comment {
     pub downvotes: i32,
     /// The time at which the comment was created.
     #[serde(rename = "createdAt")]
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     /// If the comment was edited, the time at which it was last edited, otherwise null.
     #[serde(rename = "editedAt")]
-    pub edited_at: Option<String>,
+    pub edited_at: Option<DateTime<Utc>>,
 
     /// If the content of the comment was deleted, otherwise undefined.
     #[serde(rename = "contentStripped")]
@@ -366,10 +371,10 @@ pub struct Comment {
     pub deleted: bool,
     /// If the comment was deleted, the time at which it was deleted, otherwise null.
     #[serde(rename = "deletedAt")]
-    pub deleted_at: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
     /// If the comment was deleted, in what capacity it was deleted, otherwise undefined.
     #[serde(rename = "deletedAs")]
-    pub deleted_as: Option<String>,
+    pub deleted_as: Option<UserGroup>,
 
     /// The User object of the author of the comment.
     pub author: User,
@@ -392,24 +397,24 @@ pub struct Comment {
     pub post_deleted: bool,
     /// If the post is deleted, in what capacity, otherwise undefined.
     #[serde(rename = "postDeletedAs")]
-    pub post_deleted_as: Option<String>,
+    pub post_deleted_as: Option<UserGroup>,
 }
 
 /// `Post` represents a post.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct Post {
     /// The ID of the post.
-    pub id: String,
+    pub id: PostId,
     /// The type of post.
     #[serde(rename = "type")]
-    pub post_type: String,
+    pub post_type: PostType,
     /// The value in <https://discuit.net/gaming/post/{publicId}>
     #[serde(rename = "publicId")]
-    pub public_id: String,
+    pub public_id: PublicPostId,
 
     /// ID of the author.
     #[serde(rename = "userId")]
-    pub user_id: String,
+    pub user_id: UserId,
     /// Username of the author.
     pub username: String,
     /// The ID of the Ghost user in case the user deleted their account.
@@ -417,7 +422,7 @@ pub struct Post {
     pub user_ghost_id: Option<String>,
     /// In what capacity the post was created. For "speaking officially" as a mod or an admin.
     #[serde(rename = "userGroup")]
-    pub user_group: String,
+    pub user_group: UserGroup,
     /// Indicated whether the author's account is deleted.
     #[serde(rename = "userDeleted")]
     pub user_deleted: bool,
@@ -431,7 +436,7 @@ pub struct Post {
 
     /// The ID of the community the post is posted in.
     #[serde(rename = "communityId")]
-    pub community_id: String,
+    pub community_id: CommunityId,
     /// The name of that community.
     #[serde(rename = "communityName")]
     pub community_name: String,
@@ -455,13 +460,13 @@ pub struct Post {
     pub locked: bool,
     /// Who locked the post.
     #[serde(rename = "lockedBy")]
-    pub locked_by: Option<String>,
+    pub locked_by: Option<UserId>,
     /// In what capacity the post was locked,
     #[serde(rename = "lockedByGroup")]
-    pub locked_by_group: Option<String>,
+    pub locked_by_group: Option<UserGroup>,
     /// Time at which the post was locked.
     #[serde(rename = "lockedAt")]
-    pub locked_at: Option<String>,
+    pub locked_at: Option<DateTime<Utc>>,
 
     /// The number of upvotes the post has.
     pub upvotes: i32,
@@ -471,31 +476,32 @@ pub struct Post {
     pub hotness: i32,
 
     /// The time when the post was created.
-    pub created_at: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
     /// Last edited time.
     #[serde(rename = "editedAt")]
-    pub edited_at: Option<String>,
+    pub edited_at: Option<DateTime<Utc>>,
     /// Either the post created time or, if there are comments on the post, the time the most recent comment was created at.
     #[serde(rename = "lastActivityAt")]
-    pub last_activity_at: String,
+    pub last_activity_at: DateTime<Utc>,
 
     /// If the post was deleted.
     pub deleted: bool,
     /// Time at which the post was deleted, null if the post has not been deleted.
     #[serde(rename = "deletedAt")]
-    pub deleted_at: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
     /// ID of the user who deleted the post.
     #[serde(rename = "deletedBy")]
-    pub deleted_by: Option<String>,
+    pub deleted_by: Option<UserId>,
     /// In what capacity the content was deleted, undefined if the content has not been deleted.
     #[serde(rename = "deletedAs")]
-    pub deleted_as: Option<String>,
+    pub deleted_as: Option<UserGroup>,
     /// If true, the body of the post and all associated links or images are deleted.
     #[serde(rename = "deletedContent")]
     pub deleted_content: bool,
     /// In what capacity the content was deleted, undefined if the content has not been deleted.
     #[serde(rename = "deletedContentAs")]
-    pub deleted_content_as: Option<String>,
+    pub deleted_content_as: Option<UserGroup>,
 
     /// Comment count.
     #[serde(rename = "noComments")]
@@ -541,10 +547,10 @@ pub struct Link {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct List {
     /// The ID of the list.
-    pub id: i32,
+    pub id: ListId,
     /// The ID of the list owner.
     #[serde(rename = "userId")]
-    pub user_id: String,
+    pub user_id: UserId,
     /// The username of the list owner.
     pub username: String,
     /// The name of the list.
@@ -560,7 +566,7 @@ pub struct List {
     #[serde(rename = "numItems")]
     pub num_items: i32,
     /// The current sorting of the list.
-    pub sort: String,
+    pub sort: ListSort,
     /// The time at which the list was created.
     #[serde(rename = "createdAt")]
     pub created_at: DateTime<Utc>,
@@ -573,10 +579,10 @@ pub struct List {
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct ListItem {
     /// The ID of the list item.
-    pub id: i32,
+    pub id: ListItemId,
     /// The ID of the list.
     #[serde(rename = "listId")]
-    pub list_id: i32,
+    pub list_id: ListId,
     /// The type of the target item.
     #[serde(rename = "targetType")]
     pub target_type: String,
@@ -591,8 +597,104 @@ pub struct ListItem {
     pub target_item: Post,
 }
 
+/// `Report` represents a report of a post or comment.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct Report {
+    /// The ID of the report.
+    pub id: ReportId,
+    /// The ID of the community of the reported post or comment.
+    #[serde(rename = "communityId")]
+    pub community_id: CommunityId,
+    /// The ID of the reported post, or of the post of the reported comment.
+    #[serde(rename = "postId")]
+    pub post_id: Option<PostId>,
+    /// The title of the report reason.
+    pub reason: String,
+    /// The description given by the reporter, if any.
+    pub description: Option<String>,
+    /// The ID of the report reason.
+    #[serde(rename = "reasonId")]
+    pub reason_id: i32,
+    /// Whether a post or a comment was reported, either "post" or "comment".
+    #[serde(rename = "type")]
+    pub report_type: String,
+    /// The ID of the reported post or comment.
+    #[serde(rename = "targetId")]
+    pub target_id: String,
+    /// The action taken by the moderators, if any.
+    #[serde(rename = "actionTaken")]
+    pub action_taken: Option<String>,
+    /// If the report was dealt with, the time at which it was, otherwise null.
+    #[serde(rename = "dealtAt")]
+    pub dealt_at: Option<DateTime<Utc>>,
+    /// If the report was dealt with, the ID of the user who dealt with it, otherwise null.
+    #[serde(rename = "dealtBy")]
+    pub dealt_by: Option<UserId>,
+    /// The time at which the report was created.
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    /// The reported post or comment.
+    pub target: Option<ReportTarget>,
+}
+
+/// `ReportTarget` represents the post or comment a report is about.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum ReportTarget {
+    /// A reported post.
+    Post(Post),
+
+    /// A reported comment.
+    Comment(Comment),
+}
+
+/// `Notification` represents a notification of the authenticated user.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Notification {
+    /// The ID of the notification.
+    pub id: NotificationId,
+    /// The type of notification, e.g. "new_comment", "comment_reply" or "new_votes".
+    #[serde(rename = "type")]
+    pub notification_type: String,
+    /// The type specific content of the notification.
+    /// Its fields depend on `notification_type`, e.g. a "comment_reply" notification
+    /// contains the `post` and the `commentId` of the reply.
+    pub notif: serde_json::Value,
+    /// If the notification was seen.
+    pub seen: bool,
+    /// If the notification was seen, the time at which it was seen, otherwise null.
+    #[serde(rename = "seenAt")]
+    pub seen_at: Option<DateTime<Utc>>,
+    /// The time at which the notification was created.
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Notification {
+    /// Returns the post the notification is about, if it contains one.
+    pub fn post(&self) -> Option<Post> {
+        self.notif
+            .get("post")
+            .and_then(|post| serde_json::from_value(post.clone()).ok())
+    }
+
+    /// Returns the ID of the comment the notification is about, if it contains one.
+    pub fn comment_id(&self) -> Option<CommentId> {
+        self.notif
+            .get("commentId")
+            .and_then(|id| id.as_str())
+            .map(CommentId::from)
+    }
+}
+
 /// `Sort` represents the method used to sort posts.
+#[derive(
+    Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
 pub enum Sort {
+    #[default]
     Hot,
     Activity,
     New,
@@ -603,15 +705,197 @@ pub enum Sort {
 }
 
 impl Sort {
-    pub fn to_string(&self) -> String {
+    /// Returns the value of the sort used by the API, e.g. "hot".
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Sort::Hot => "hot".to_string(),
-            Sort::Activity => "activity".to_string(),
-            Sort::New => "new".to_string(),
-            Sort::Day => "day".to_string(),
-            Sort::Week => "week".to_string(),
-            Sort::Month => "month".to_string(),
-            Sort::Year => "year".to_string(),
+            Sort::Hot => "hot",
+            Sort::Activity => "activity",
+            Sort::New => "new",
+            Sort::Day => "day",
+            Sort::Week => "week",
+            Sort::Month => "month",
+            Sort::Year => "year",
+        }
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Sort {
+    type Err = ParseSortError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "hot" => Sort::Hot,
+            "activity" => Sort::Activity,
+            "new" => Sort::New,
+            "day" => Sort::Day,
+            "week" => Sort::Week,
+            "month" => Sort::Month,
+            "year" => Sort::Year,
+            _ => return Err(ParseSortError(s.to_string())),
+        })
+    }
+}
+
+/// `ParseSortError` is returned when parsing an unknown `Sort`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseSortError(pub String);
+
+impl fmt::Display for ParseSortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown sort: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSortError {}
+
+/// Defines an enum of the known values of a string field, with an `Unknown`
+/// variant keeping values added to the API later.
+macro_rules! string_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$vmeta:meta])* $variant:ident => $value:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)*
+            /// A value unknown to this library.
+            Unknown(String),
         }
+
+        impl $name {
+            /// Returns the value used by the API.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    value => $name::Unknown(value.to_string()),
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Infallible;
+
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                Ok(value.into())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                Ok(value.as_str().into())
+            }
+        }
+    };
+}
+
+string_enum!(
+    /// `PostType` is the type of a `Post`.
+    PostType {
+        /// A text post.
+        Text => "text",
+        /// An image post.
+        Image => "image",
+        /// A link post.
+        Link => "link",
+    }
+);
+
+string_enum!(
+    /// `UserGroup` is the capacity in which a user acted, e.g. when creating,
+    /// locking or deleting a post.
+    UserGroup {
+        /// A regular user.
+        Normal => "normal",
+        /// A moderator of the community.
+        Mods => "mods",
+        /// An admin of the instance.
+        Admins => "admins",
+    }
+);
+
+string_enum!(
+    /// `ListSort` is the order of the items of a `List`.
+    ListSort {
+        /// The most recently added items first.
+        AddedDsc => "addedDsc",
+        /// The least recently added items first.
+        AddedAsc => "addedAsc",
+        /// The most recently created items first.
+        CreatedDsc => "createdDsc",
+        /// The least recently created items first.
+        CreatedAsc => "createdAsc",
+    }
+);
+
+string_enum!(
+    /// `HomeFeed` is the feed a user has set as their home feed.
+    HomeFeed {
+        /// Posts from all communities.
+        All => "all",
+        /// Posts from the communities the user joined.
+        Subscriptions => "subscriptions",
+    }
+);
+
+string_enum!(
+    /// `ObjectFit` is how an `ImageCopy` fits into its box, like the CSS
+    /// `object-fit` property.
+    ObjectFit {
+        /// The image covers the box, cropping it if needed.
+        Cover => "cover",
+        /// The image is contained in the box, letterboxed if needed.
+        Contain => "contain",
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(Sort::Activity.to_string(), "activity");
+        assert_eq!("week".parse::<Sort>(), Ok(Sort::Week));
+        assert!("latest".parse::<Sort>().is_err());
+        assert_eq!(serde_json::to_string(&Sort::New).unwrap(), r#""new""#);
+        assert_eq!(serde_json::from_str::<Sort>(r#""hot""#).unwrap(), Sort::Hot);
+    }
+
+    #[test]
+    fn test_string_enum() {
+        assert_eq!(
+            serde_json::from_str::<UserGroup>(r#""mods""#).unwrap(),
+            UserGroup::Mods
+        );
+        let unknown: PostType = serde_json::from_str(r#""poll""#).unwrap();
+        assert_eq!(unknown, PostType::Unknown("poll".to_string()));
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), r#""poll""#);
+        assert_eq!(ListSort::AddedDsc.to_string(), "addedDsc");
     }
 }
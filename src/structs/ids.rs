@@ -0,0 +1,187 @@
+//! This module contains the strongly typed IDs of Discuit objects.
+//!
+//! Every ID is a newtype that (de)serializes exactly like the raw value, so
+//! a `PostId` can't be passed where a `PublicPostId` is expected.
+
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// Defines a newtype for IDs that are strings.
+macro_rules! string_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Deserialize, Serialize)]
+        #[serde(transparent)]
+        pub struct $name(pub String);
+
+        impl $name {
+            /// Create a new ID from its raw value.
+            pub fn new(id: impl Into<String>) -> Self {
+                Self(id.into())
+            }
+
+            /// Returns the raw value of the ID.
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.to_string())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Infallible;
+
+            fn from_str(id: &str) -> Result<Self, Self::Err> {
+                Ok(Self(id.to_string()))
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+/// Defines a newtype for IDs that are integers.
+macro_rules! int_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Deserialize, Serialize)]
+        #[serde(transparent)]
+        pub struct $name(pub i32);
+
+        impl $name {
+            /// Returns the raw value of the ID.
+            pub fn get(self) -> i32 {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(id: i32) -> Self {
+                Self(id)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(id: &str) -> Result<Self, Self::Err> {
+                id.parse().map(Self)
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+string_id!(
+    /// `UserId` is the ID of a `User`.
+    UserId
+);
+string_id!(
+    /// `CommunityId` is the ID of a `Community`.
+    CommunityId
+);
+string_id!(
+    /// `PostId` is the ID of a `Post`, used e.g. to vote on it.
+    PostId
+);
+string_id!(
+    /// `PublicPostId` is the public ID of a `Post`, used in its URL and most endpoints.
+    PublicPostId
+);
+string_id!(
+    /// `CommentId` is the ID of a `Comment`.
+    CommentId
+);
+string_id!(
+    /// `ImageId` is the ID of an `Image`.
+    ImageId
+);
+string_id!(
+    /// `MuteId` is the ID of a `Mute`.
+    MuteId
+);
+int_id!(
+    /// `ListId` is the ID of a `List`.
+    ListId
+);
+int_id!(
+    /// `ListItemId` is the ID of a `ListItem`.
+    ListItemId
+);
+int_id!(
+    /// `ReportId` is the ID of a `Report`.
+    ReportId
+);
+int_id!(
+    /// `NotificationId` is the ID of a `Notification`.
+    NotificationId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_is_transparent() {
+        let id: PublicPostId = serde_json::from_str(r#""abc123""#).unwrap();
+        assert_eq!(id, "abc123");
+        assert_eq!(serde_json::to_string(&id).unwrap(), r#""abc123""#);
+
+        let id: ListId = serde_json::from_str("42").unwrap();
+        assert_eq!(id.get(), 42);
+        assert_eq!(serde_json::to_string(&id).unwrap(), "42");
+        assert_eq!(id.to_string(), "42");
+    }
+}
@@ -1,11 +1,56 @@
 //! This module contains things used internally by `discuit-rs`.
 
+use crate::structs::api_types::User;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use tracing::level_filters::LevelFilter;
+
+/// Session represents the authentication state of a `DiscuitClient`.
+/// It can be saved and restored with `DiscuitClient::session` and
+/// `DiscuitClient::set_session` to resume a session later.
+///
+/// The `Debug` output redacts the CSRF token and the session ID.
+#[derive(Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Session {
+    /// The CSRF token.
+    pub csrf_token: String,
+    /// The session ID.
+    pub session_id: String,
+    /// The User object of the authenticated user.
+    /// If the client is not authenticated, this is None.
+    pub user: Option<User>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("csrf_token", &Redacted(&self.csrf_token))
+            .field("session_id", &Redacted(&self.session_id))
+            .field("user", &self.user.as_ref().map(|user| &user.username))
+            .finish()
+    }
+}
+
+/// Redacted formats a secret for `Debug` output without revealing it.
+/// Empty secrets are shown as `""` so that it's still visible whether one is set.
+pub(crate) struct Redacted<'a>(pub(crate) &'a str);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            write!(f, "\"\"")
+        } else {
+            write!(f, "\"<redacted>\"")
+        }
+    }
+}
 
 /// LogLevel represents the level of logging to use.
+///
+/// The client logs through `tracing`, so this is only kept for compatibility;
+/// convert it into a `LevelFilter` to configure a subscriber with it.
 /// The levels are, in order of verbosity:
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+#[derive(Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub enum LogLevel {
     /// Log everything.
     /// This is the most verbose level and should be used for debugging.
@@ -14,6 +59,7 @@ pub enum LogLevel {
     /// Log informational messages.
     /// This level includes less verbose levels as well as information about the client
     /// and requests that are made, e.g., the URL of the request.
+    #[default]
     Info,
     /// Log warnings.
     /// This level includes less verbose levels as well as warnings about potential issues.
@@ -30,12 +76,6 @@ pub enum LogLevel {
     Silent,
 }
 
-impl Default for LogLevel {
-    fn default() -> Self {
-        LogLevel::Info
-    }
-}
-
 impl fmt::Display for LogLevel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -47,3 +87,40 @@ impl fmt::Display for LogLevel {
         }
     }
 }
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Warning => LevelFilter::WARN,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Silent => LevelFilter::OFF,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_debug_is_redacted() {
+        let session = Session {
+            csrf_token: "csrf-secret".to_string(),
+            session_id: String::new(),
+            user: None,
+        };
+        let debug = format!("{:?}", session);
+
+        assert!(!debug.contains("csrf-secret"));
+        assert!(debug.contains("csrf_token: \"<redacted>\""));
+        assert!(debug.contains("session_id: \"\""));
+    }
+
+    #[test]
+    fn test_log_level_into_level_filter() {
+        assert_eq!(LevelFilter::from(LogLevel::Warning), LevelFilter::WARN);
+        assert_eq!(LevelFilter::from(LogLevel::Silent), LevelFilter::OFF);
+    }
+}
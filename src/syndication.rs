@@ -0,0 +1,476 @@
+//! This module converts post feeds into RSS 2.0 and Atom documents.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use discuit_rs::client::*;
+//! use discuit_rs::syndication::Syndication;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = DiscuitClient::new("https://discuit.net");
+//!     let posts = client.get_posts_page(Some("hot"), Some("general"), None).await.unwrap();
+//!
+//!     let feed = Syndication::new("https://discuit.net", "general - hot");
+//!     println!("{}", feed.rss(&posts));
+//! }
+//! ```
+//!
+//! With the `feed-server` feature enabled, `serve` exposes the feeds of any
+//! community and sort over HTTP, so feed readers can subscribe to them.
+
+use crate::markdown;
+use crate::structs::api_responses::*;
+use crate::structs::api_types::*;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// `PostSource` is implemented by everything posts can be syndicated from.
+pub trait PostSource {
+    /// Returns the posts, in feed order.
+    fn posts(&self) -> Vec<&Post>;
+}
+
+impl PostSource for PostFeedResponse {
+    fn posts(&self) -> Vec<&Post> {
+        self.posts.iter().collect()
+    }
+}
+
+/// Comments in the feed are skipped; an error response has no posts.
+impl PostSource for FeedResponse {
+    fn posts(&self) -> Vec<&Post> {
+        match self {
+            FeedResponse::Feed { feed, .. } => feed
+                .iter()
+                .filter_map(|item| match item {
+                    FeedItem::Post(post) => Some(post),
+                    FeedItem::Comment(_) => None,
+                })
+                .collect(),
+            FeedResponse::Error(_) => Vec::new(),
+        }
+    }
+}
+
+impl PostSource for [Post] {
+    fn posts(&self) -> Vec<&Post> {
+        self.iter().collect()
+    }
+}
+
+impl PostSource for Vec<Post> {
+    fn posts(&self) -> Vec<&Post> {
+        self.iter().collect()
+    }
+}
+
+/// `Syndication` holds the metadata of a feed and renders posts into it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Syndication {
+    base_url: String,
+    title: String,
+    description: String,
+    self_url: Option<String>,
+}
+
+impl Syndication {
+    /// Create a new Syndication for the instance at `base_url`, e.g. "https://discuit.net".
+    pub fn new(base_url: &str, title: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            title: title.to_string(),
+            description: title.to_string(),
+            self_url: None,
+        }
+    }
+
+    /// Set the description of the feed. Defaults to the title.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// Set the URL the feed itself is served at.
+    pub fn self_url(mut self, self_url: &str) -> Self {
+        self.self_url = Some(self_url.to_string());
+        self
+    }
+
+    /// Returns the public URL of a post, `{base_url}/{communityName}/post/{publicId}`.
+    pub fn post_url(&self, post: &Post) -> String {
+        format!(
+            "{}/{}/post/{}",
+            self.base_url, post.community_name, post.public_id
+        )
+    }
+
+    /// Returns the absolute URL of an image.
+    fn image_url(&self, image: &Image) -> String {
+        if image.url.starts_with("http://") || image.url.starts_with("https://") {
+            image.url.clone()
+        } else {
+            format!("{}{}", self.base_url, image.url)
+        }
+    }
+
+    /// Returns the HTML content of a post: its link, image and body.
+    fn content(&self, post: &Post) -> String {
+        let mut html = String::new();
+        if let Some(link) = &post.link {
+            let _ = write!(
+                html,
+                "<p><a href=\"{}\">{}</a></p>",
+                escape(&link.url),
+                escape(&link.hostname)
+            );
+        }
+        if let Some(image) = &post.image {
+            let _ = write!(
+                html,
+                "<p><img src=\"{}\"/></p>",
+                escape(&self.image_url(image))
+            );
+        }
+        if let Some(body) = &post.body {
+            html.push_str(&markdown::parse(body).to_html_with_base(&self.base_url));
+        }
+        html
+    }
+
+    /// Render the posts as an RSS 2.0 document.
+    pub fn rss<S: PostSource + ?Sized>(&self, source: &S) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<channel>\n",
+        );
+        let _ = writeln!(xml, "<title>{}</title>", escape(&self.title));
+        let _ = writeln!(xml, "<link>{}</link>", escape(&self.base_url));
+        let _ = writeln!(
+            xml,
+            "<description>{}</description>",
+            escape(&self.description)
+        );
+        if let Some(self_url) = &self.self_url {
+            let _ = writeln!(
+                xml,
+                "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+                escape(self_url)
+            );
+        }
+
+        for post in source.posts() {
+            let url = escape(&self.post_url(post));
+            xml.push_str("<item>\n");
+            let _ = writeln!(xml, "<title>{}</title>", escape(&post.title));
+            let _ = writeln!(xml, "<link>{}</link>", url);
+            let _ = writeln!(xml, "<guid isPermaLink=\"true\">{}</guid>", url);
+            let _ = writeln!(xml, "<dc:creator>{}</dc:creator>", escape(&post.username));
+            let _ = writeln!(xml, "<category>{}</category>", escape(&post.community_name));
+            let _ = writeln!(xml, "<pubDate>{}</pubDate>", post.created_at.to_rfc2822());
+            let _ = writeln!(
+                xml,
+                "<description>{}</description>",
+                escape(&self.content(post))
+            );
+            if let Some(image) = &post.image {
+                let _ = writeln!(
+                    xml,
+                    "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>",
+                    escape(&self.image_url(image)),
+                    image.size,
+                    escape(&image.mimetype)
+                );
+            }
+            xml.push_str("</item>\n");
+        }
+
+        xml.push_str("</channel>\n</rss>\n");
+        xml
+    }
+
+    /// Render the posts as an Atom document.
+    pub fn atom<S: PostSource + ?Sized>(&self, source: &S) -> String {
+        let posts = source.posts();
+        // The feed was last updated when its most recent post was.
+        let updated = posts
+            .iter()
+            .map(|post| updated_at(post))
+            .max()
+            .unwrap_or_else(Utc::now);
+        let id = self.self_url.as_deref().unwrap_or(&self.base_url);
+
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+        );
+        let _ = writeln!(xml, "<id>{}</id>", escape(id));
+        let _ = writeln!(xml, "<title>{}</title>", escape(&self.title));
+        let _ = writeln!(xml, "<subtitle>{}</subtitle>", escape(&self.description));
+        let _ = writeln!(xml, "<updated>{}</updated>", updated.to_rfc3339());
+        let _ = writeln!(xml, "<link href=\"{}\"/>", escape(&self.base_url));
+        if let Some(self_url) = &self.self_url {
+            let _ = writeln!(xml, "<link href=\"{}\" rel=\"self\"/>", escape(self_url));
+        }
+
+        for post in posts {
+            let url = escape(&self.post_url(post));
+            xml.push_str("<entry>\n");
+            let _ = writeln!(xml, "<id>{}</id>", url);
+            let _ = writeln!(xml, "<title>{}</title>", escape(&post.title));
+            let _ = writeln!(xml, "<link href=\"{}\"/>", url);
+            let _ = writeln!(
+                xml,
+                "<author><name>{}</name></author>",
+                escape(&post.username)
+            );
+            let _ = writeln!(xml, "<category term=\"{}\"/>", escape(&post.community_name));
+            let _ = writeln!(
+                xml,
+                "<published>{}</published>",
+                post.created_at.to_rfc3339()
+            );
+            let _ = writeln!(xml, "<updated>{}</updated>", updated_at(post).to_rfc3339());
+            if let Some(link) = &post.link {
+                let _ = writeln!(
+                    xml,
+                    "<link href=\"{}\" rel=\"related\"/>",
+                    escape(&link.url)
+                );
+            }
+            if let Some(image) = &post.image {
+                let _ = writeln!(
+                    xml,
+                    "<link href=\"{}\" rel=\"enclosure\" type=\"{}\" length=\"{}\"/>",
+                    escape(&self.image_url(image)),
+                    escape(&image.mimetype),
+                    image.size
+                );
+            }
+            let _ = writeln!(
+                xml,
+                "<content type=\"html\">{}</content>",
+                escape(&self.content(post))
+            );
+            xml.push_str("</entry>\n");
+        }
+
+        xml.push_str("</feed>\n");
+        xml
+    }
+}
+
+/// Returns when a post was last edited, or created if it never was.
+fn updated_at(post: &Post) -> DateTime<Utc> {
+    post.edited_at.unwrap_or(post.created_at)
+}
+
+/// Escapes text for use in XML content and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(feature = "feed-server")]
+pub use server::serve;
+
+#[cfg(feature = "feed-server")]
+mod server {
+    use super::Syndication;
+    use crate::client::DiscuitClient;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tracing::{info, warn};
+
+    /// Serve RSS and Atom feeds over HTTP until the listener fails.
+    ///
+    /// Feeds are served at `/{community}/rss` and `/{community}/atom`, and the
+    /// site-wide feed at `/rss` and `/atom`. The sort is taken from the `sort`
+    /// query parameter, e.g. `/general/rss?sort=new`, and defaults to "hot".
+    ///
+    /// Requires the `feed-server` feature.
+    pub async fn serve(client: DiscuitClient, listener: TcpListener) -> std::io::Result<()> {
+        info!(address = %listener.local_addr()?, "Serving feeds ...");
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let client = client.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle(client, stream).await {
+                    warn!(error = %e, "Failed to serve feed");
+                }
+            });
+        }
+    }
+
+    async fn handle(client: DiscuitClient, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut buf = vec![0; 8192];
+        let n = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..n]);
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("GET "))
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap_or_default();
+
+        let (status, content_type, body) = match route(&client, target).await {
+            Ok(Some((content_type, body))) => ("200 OK", content_type, body),
+            Ok(None) => ("404 Not Found", "text/plain", "Not found".to_string()),
+            Err(e) => ("502 Bad Gateway", "text/plain", e.to_string()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// Renders the feed requested by the target, or returns None if the
+    /// target isn't a feed.
+    async fn route(
+        client: &DiscuitClient,
+        target: &str,
+    ) -> Result<Option<(&'static str, String)>, crate::error::Error> {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let sort = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("sort="))
+            .filter(|sort| sort.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("hot");
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let (community, format) = match segments.as_slice() {
+            [format] => (None, *format),
+            [community, format] => (Some(*community), *format),
+            _ => return Ok(None),
+        };
+        if format != "rss" && format != "atom" {
+            return Ok(None);
+        }
+
+        let posts = client.get_posts_page(Some(sort), community, None).await?;
+        let title = match community {
+            Some(community) => format!("{} - {}", community, sort),
+            None => format!("Discuit - {}", sort),
+        };
+        let feed = Syndication::new(&client.base_url, &title);
+        Ok(Some(if format == "rss" {
+            ("application/rss+xml", feed.rss(&posts))
+        } else {
+            ("application/atom+xml", feed.atom(&posts))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use serde_json::json;
+
+    fn posts() -> PostFeedResponse {
+        let mut linked = fixtures::post("b", "general", "alice", "2024-06-01T10:00:00Z");
+        linked["title"] = json!("Links & <things>");
+        linked["link"] = json!({ "url": "https://example.com/?a=1&b=2", "hostname": "example.com", "image": null });
+        linked["image"] = fixtures::image("img");
+        serde_json::from_value(json!({
+            "posts": [
+                fixtures::post("a", "general", "bob", "2024-06-02T10:00:00Z"),
+                linked,
+            ],
+            "next": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rss() {
+        let rss = Syndication::new("https://discuit.net/", "general").rss(&posts());
+
+        assert!(rss.contains("<link>https://discuit.net/general/post/a</link>"));
+        assert!(rss.contains("<title>Links &amp; &lt;things&gt;</title>"));
+        assert!(rss.contains("<dc:creator>alice</dc:creator>"));
+        assert!(rss.contains("<pubDate>Sun, 2 Jun 2024 10:00:00 +0000</pubDate>"));
+        assert!(rss.contains(
+            "<enclosure url=\"https://discuit.net/images/img.jpeg\" length=\"1024\" type=\"image/jpeg\"/>"
+        ));
+        assert!(rss.contains("https://example.com/?a=1&amp;amp;b=2"));
+        assert_eq!(rss.matches("<item>").count(), 2);
+    }
+
+    #[test]
+    fn test_atom() {
+        let atom = Syndication::new("https://discuit.net", "general")
+            .self_url("http://localhost/general/atom")
+            .atom(&posts());
+
+        assert!(atom.contains("<id>http://localhost/general/atom</id>"));
+        assert!(atom.contains("<updated>2024-06-02T10:00:00+00:00</updated>"));
+        assert!(atom.contains("<author><name>bob</name></author>"));
+        assert!(atom.contains("<link href=\"https://example.com/?a=1&amp;b=2\" rel=\"related\"/>"));
+        assert!(atom.contains("&lt;p&gt;Hello, world!&lt;/p&gt;"));
+        assert_eq!(atom.matches("<entry>").count(), 2);
+    }
+
+    #[test]
+    fn test_feed_response_skips_comments() {
+        let feed: FeedResponse = serde_json::from_value(json!({
+            "feed": [
+                fixtures::post("a", "general", "bob", "2024-06-02T10:00:00Z"),
+                fixtures::comment("c", "a", "bob", "hi", "2024-06-02T10:00:00Z"),
+            ],
+            "next": null
+        }))
+        .unwrap();
+
+        assert_eq!(feed.posts().len(), 1);
+    }
+
+    #[cfg(feature = "feed-server")]
+    #[tokio::test]
+    async fn test_serve() {
+        use crate::client::DiscuitClient;
+        use crate::mock_server::{MockResponse, MockServer};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = MockServer::start(|_| {
+            let body = json!({
+                "posts": [fixtures::post("a", "general", "bob", "2024-06-02T10:00:00Z")],
+                "next": null
+            });
+            MockResponse::json(200, &body.to_string())
+        })
+        .await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(DiscuitClient::new(&server.url), listener));
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /general/atom?sort=new HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("application/atom+xml"));
+        assert!(response.contains("/general/post/a"));
+        let path = &server.requests()[0].path;
+        assert!(path.contains("sort=new") && path.contains("community=general"));
+    }
+}